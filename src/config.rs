@@ -0,0 +1,429 @@
+use crate::display::{check_date_format, ClockFormat, DurationStyle, Rounding, RoundingMode, Style};
+use crate::profiles::ensure_parent_dir;
+use anyhow::{anyhow, Error};
+use chrono::Weekday;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use toml::value::{Table, Value};
+
+// every setting the config file can hold, in the order they're listed
+pub const SETTINGS: [&str; 10] = [
+    "default_project",
+    "date_format",
+    "clock",
+    "week_start",
+    "duration_style",
+    "round_to_minutes",
+    "rounding",
+    "color",
+    "project_check",
+    "history_limit",
+];
+
+const WEEKDAY_NAMES: [&str; 7] = ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = anyhow::Error;
+
+    fn from_str(color: &str) -> Result<Self, Self::Err> {
+        match color {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(anyhow!("'{}' is not a supported color setting;  use auto, always or never", color)),
+        }
+    }
+}
+
+// what happens when a task is given a project that hasn't been added or is archived
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProjectCheck {
+    Warn,
+    Error,
+}
+
+impl FromStr for ProjectCheck {
+    type Err = anyhow::Error;
+
+    fn from_str(check: &str) -> Result<Self, Self::Err> {
+        match check {
+            "warn" => Ok(ProjectCheck::Warn),
+            "error" => Ok(ProjectCheck::Error),
+            _ => Err(anyhow!("'{}' is not a supported project_check setting;  use warn or error", check)),
+        }
+    }
+}
+
+impl ColorChoice {
+    // auto only colors output written to a terminal, and respects NO_COLOR
+    pub fn enabled(&self, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => is_terminal && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    pub default_project: String,
+    pub date_format: String,
+    pub clock: ClockFormat,
+    pub week_start: Weekday,
+    pub duration_style: DurationStyle,
+    pub rounding: Rounding,
+    pub color: ColorChoice,
+    pub project_check: ProjectCheck,
+    pub history_limit: usize,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        let style = Style::default();
+
+        Config {
+            default_project: "default".to_string(),
+            date_format: style.date_format,
+            clock: style.clock,
+            week_start: Weekday::Mon,
+            duration_style: style.duration_style,
+            rounding: style.rounding,
+            color: ColorChoice::Auto,
+            project_check: ProjectCheck::Warn,
+            history_limit: 1000,
+        }
+    }
+}
+
+impl Config {
+    // a missing config file means every setting has its default
+    pub fn load(path: &Path) -> Result<Config, Error> {
+        Config::from_table(&read_table(path)?).map_err(|err| anyhow!("{}: {}", path.display(), err))
+    }
+
+    // like `load`, but an invalid setting keeps its default rather than failing, with an error for
+    // each one, so that the config command can still be used to fix the file
+    pub fn load_lenient(path: &Path) -> Result<(Config, Vec<Error>), Error> {
+        let mut config = Config::default();
+        let mut errors = Vec::new();
+        for (key, value) in &read_table(path)? {
+            if let Err(err) = config.apply(key, value) {
+                errors.push(anyhow!("{}: {}", path.display(), err));
+            }
+        }
+
+        Ok((config, errors))
+    }
+
+    pub fn from_table(table: &Table) -> Result<Config, Error> {
+        let mut config = Config::default();
+        for (key, value) in table {
+            config.apply(key, value)?;
+        }
+
+        Ok(config)
+    }
+
+    fn apply(&mut self, key: &str, value: &Value) -> Result<(), Error> {
+        if key == "round_to_minutes" {
+            let minutes = value
+                .as_integer()
+                .filter(|minutes| (0..=1440).contains(minutes))
+                .ok_or(anyhow!("'round_to_minutes' should be a number of minutes from 0 to 1440, such as 15"))?;
+            self.rounding.minutes = minutes;
+            return Ok(());
+        }
+        if key == "history_limit" {
+            self.history_limit = value
+                .as_integer()
+                .filter(|limit| *limit >= 0)
+                .map(|limit| limit as usize)
+                .ok_or(anyhow!("'history_limit' should be a number of changes to keep, such as 1000, or 0 to keep every change"))?;
+            return Ok(());
+        }
+
+        check_key(key)?;
+        let text = value.as_str().ok_or(anyhow!("'{}' should be a string, such as '{}'", key, Config::default().get(key)?))?;
+        match key {
+            "default_project" if text.is_empty() => return Err(anyhow!("'default_project' can't be empty")),
+            "default_project" => self.default_project = text.to_string(),
+            "date_format" => {
+                check_date_format(text)?;
+                self.date_format = text.to_string();
+            }
+            "clock" => self.clock = text.parse()?,
+            "week_start" => self.week_start = parse_weekday(text)?,
+            "duration_style" => self.duration_style = text.parse()?,
+            "rounding" => self.rounding.mode = text.parse()?,
+            "project_check" => self.project_check = text.parse()?,
+            _ => self.color = text.parse()?,
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Result<String, Error> {
+        check_key(key)?;
+
+        Ok(match key {
+            "default_project" => self.default_project.clone(),
+            "date_format" => self.date_format.clone(),
+            "clock" => match self.clock {
+                ClockFormat::TwentyFourHour => "24h",
+                ClockFormat::TwelveHour => "12h",
+            }
+            .to_string(),
+            "week_start" => WEEKDAY_NAMES[self.week_start.num_days_from_monday() as usize].to_string(),
+            "duration_style" => match self.duration_style {
+                DurationStyle::Long => "long",
+                DurationStyle::Short => "short",
+                DurationStyle::Decimal => "decimal",
+            }
+            .to_string(),
+            "round_to_minutes" => self.rounding.minutes.to_string(),
+            "history_limit" => self.history_limit.to_string(),
+            "rounding" => match self.rounding.mode {
+                RoundingMode::Nearest => "nearest",
+                RoundingMode::Up => "up",
+                RoundingMode::Down => "down",
+            }
+            .to_string(),
+            "project_check" => match self.project_check {
+                ProjectCheck::Warn => "warn",
+                ProjectCheck::Error => "error",
+            }
+            .to_string(),
+            _ => match self.color {
+                ColorChoice::Auto => "auto",
+                ColorChoice::Always => "always",
+                ColorChoice::Never => "never",
+            }
+            .to_string(),
+        })
+    }
+
+    // every setting with its current value
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        SETTINGS.iter().map(|key| (*key, self.get(key).unwrap_or_default())).collect()
+    }
+
+    pub fn style(&self, is_terminal: bool) -> Style {
+        Style {
+            date_format: self.date_format.clone(),
+            clock: self.clock,
+            duration_style: self.duration_style,
+            rounding: self.rounding,
+            color: self.color.enabled(is_terminal),
+        }
+    }
+}
+
+// uses $XDG_CONFIG_HOME/taskmao/config.toml, falling back to ~/.config/taskmao/config.toml
+pub fn config_path() -> Option<PathBuf> {
+    let xdg_config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute());
+
+    xdg_config_home
+        .or_else(|| dirs::home_dir().map(|home| home.join(".config")))
+        .map(|config_home| config_home.join("taskmao").join("config.toml"))
+}
+
+fn read_table(path: &Path) -> Result<Table, Error> {
+    if !path.exists() {
+        return Ok(Table::new());
+    }
+
+    let text = fs::read_to_string(path)?;
+    toml::from_str(&text).map_err(|err| anyhow!("{} isn't a valid config file: {}", path.display(), err))
+}
+
+// changes a single setting in the config file, keeping every other setting as it was.  only the
+// new value is checked, so a setting can be changed while another one is invalid.  returns the
+// value as `get` shows it
+pub fn set_value(path: &Path, key: &str, value: &str) -> Result<String, Error> {
+    check_key(key)?;
+    let value = match key {
+        "round_to_minutes" => Value::Integer(
+            value
+                .parse()
+                .map_err(|_| anyhow!("'{}' isn't a number of minutes, such as 15", value))?,
+        ),
+        "history_limit" => Value::Integer(
+            value
+                .parse()
+                .map_err(|_| anyhow!("'{}' isn't a number of changes, such as 1000", value))?,
+        ),
+        _ => Value::String(value.to_string()),
+    };
+
+    let mut config = Config::default();
+    config.apply(key, &value)?;
+
+    let mut table = read_table(path)?;
+    table.insert(key.to_string(), value);
+    ensure_parent_dir(path)?;
+    fs::write(path, toml::to_string(&table)?)?;
+
+    config.get(key)
+}
+
+fn parse_weekday(day: &str) -> Result<Weekday, Error> {
+    day.parse()
+        .map_err(|_| anyhow!("'{}' is not a day of the week, such as 'monday' or 'sunday'", day))
+}
+
+fn check_key(key: &str) -> Result<(), Error> {
+    if SETTINGS.contains(&key) {
+        return Ok(());
+    }
+
+    let closest = SETTINGS
+        .iter()
+        .map(|setting| (edit_distance(key, setting), setting))
+        .min()
+        .filter(|(distance, _)| *distance <= 3);
+
+    match closest {
+        Some((_, setting)) => Err(anyhow!(
+            "'{}' isn't a setting.  did you mean '{}'?  run 'taskmao config list' to see every setting",
+            key,
+            setting
+        )),
+        None => Err(anyhow!("'{}' isn't a setting.  run 'taskmao config list' to see every setting", key)),
+    }
+}
+
+// the number of single character edits it takes to turn `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + if a_char == *b_char { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn parse(text: &str) -> Result<Config, Error> {
+        Config::from_table(&toml::from_str(text)?)
+    }
+
+    #[test]
+    fn test_empty_config_has_defaults() -> Result<(), Error> {
+        let config = parse("")?;
+        assert_eq!(config, Config::default());
+        assert_eq!(config.style(false), Style::default());
+        Ok(())
+    }
+
+    #[test]
+    fn test_settings_are_read() -> Result<(), Error> {
+        let config = parse(
+            "default_project = \"work\"\nclock = \"12h\"\nweek_start = \"sunday\"\nround_to_minutes = 15\nrounding = \"up\"\ncolor = \"never\"\nproject_check = \"error\"\nhistory_limit = 50\n",
+        )?;
+
+        assert_eq!(config.default_project, "work");
+        assert_eq!(config.clock, ClockFormat::TwelveHour);
+        assert_eq!(config.week_start, Weekday::Sun);
+        assert_eq!(config.rounding, Rounding { minutes: 15, mode: RoundingMode::Up });
+        assert_eq!(config.get("week_start")?, "sunday");
+        assert_eq!(config.get("round_to_minutes")?, "15");
+        assert_eq!(config.project_check, ProjectCheck::Error);
+        assert_eq!(config.history_limit, 50);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_keys_suggest_a_setting() {
+        let err = parse("colour = \"never\"").unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "'colour' isn't a setting.  did you mean 'color'?  run 'taskmao config list' to see every setting"
+        );
+
+        let err = parse("editor = \"vim\"").unwrap_err().to_string();
+        assert_eq!(err, "'editor' isn't a setting.  run 'taskmao config list' to see every setting");
+    }
+
+    #[test]
+    fn test_invalid_values_are_rejected() {
+        for text in &[
+            "clock = \"13h\"",
+            "clock = 12",
+            "week_start = \"someday\"",
+            "date_format = \"%Q\"",
+            "round_to_minutes = -5",
+            "round_to_minutes = \"15\"",
+            "default_project = \"\"",
+            "project_check = \"ignore\"",
+            "history_limit = -1",
+        ] {
+            assert!(parse(text).is_err(), "'{}' should be rejected", text);
+        }
+    }
+
+    #[test]
+    fn test_set_value_keeps_other_settings() -> Result<(), Error> {
+        let path = std::env::temp_dir()
+            .join(format!("taskmao-test-{}", Uuid::new_v4()))
+            .join("config.toml");
+
+        set_value(&path, "clock", "12h")?;
+        assert_eq!(set_value(&path, "round_to_minutes", "6")?, "6");
+        let config = Config::load(&path)?;
+        assert_eq!(config.clock, ClockFormat::TwelveHour);
+        assert_eq!(config.rounding.minutes, 6);
+
+        assert!(set_value(&path, "clock", "25h").is_err());
+        assert!(set_value(&path, "clocks", "12h").is_err());
+        assert_eq!(Config::load(&path)?.get("clock")?, "12h");
+
+        fs::remove_dir_all(path.parent().unwrap())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_value_works_beside_an_invalid_setting() -> Result<(), Error> {
+        let path = std::env::temp_dir()
+            .join(format!("taskmao-test-{}", Uuid::new_v4()))
+            .join("config.toml");
+        ensure_parent_dir(&path)?;
+        fs::write(&path, "colour = \"never\"\nclock = \"13h\"\n")?;
+        assert!(Config::load(&path).is_err());
+
+        assert_eq!(set_value(&path, "week_start", "sunday")?, "sunday");
+        let (config, errors) = Config::load_lenient(&path)?;
+        assert_eq!(config.week_start, Weekday::Sun);
+        assert_eq!(config.clock, ClockFormat::TwentyFourHour);
+        assert_eq!(errors.len(), 2);
+
+        set_value(&path, "clock", "12h")?;
+        let (config, errors) = Config::load_lenient(&path)?;
+        assert_eq!(config.clock, ClockFormat::TwelveHour);
+        assert_eq!(errors.len(), 1);
+
+        fs::remove_dir_all(path.parent().unwrap())?;
+        Ok(())
+    }
+}
@@ -1,125 +1,928 @@
 extern crate rusqlite;
 
-use anyhow::Error;
-use rusqlite::{params, Connection, Result};
+use crate::migrations;
+use crate::search::{Query, SearchHit};
+use anyhow::{anyhow, Error};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+use uuid::Uuid;
 
-#[derive(Debug)]
-pub struct TaskDto {
-    pub end_time: String,
+// format that timestamps are persisted in.  it matches the output of sqlite's
+// DATETIME() so that timestamps can be compared within queries
+const DB_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+// tags and breaks are read back alongside each task as comma separated columns.  each break is
+// written as `start/end`, with an empty end while the break is ongoing.  notes can hold any text,
+// so they're read back as a json array of `[time, text]` pairs instead
+const TASK_COLUMNS: &str = "description, project_name, running, end_time, start_time, unique_id, deleted_at,
+    (SELECT GROUP_CONCAT(tag, ',') FROM task_tags WHERE task_tags.task_unique_id = tasks.unique_id),
+    (SELECT GROUP_CONCAT(task_breaks.start_time || '/' || COALESCE(task_breaks.end_time, ''), ',') FROM task_breaks WHERE task_breaks.task_unique_id = tasks.unique_id),
+    (SELECT json_group_array(json_array(time, text)) FROM (SELECT time, text FROM task_notes WHERE task_notes.task_unique_id = tasks.unique_id ORDER BY time, id))";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskStatus {
+    Running,
+    Ended,
+}
+
+// the status is stored within the `running` column as 'true' / 'false'
+impl ToSql for TaskStatus {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        let running = match self {
+            TaskStatus::Running => "true",
+            TaskStatus::Ended => "false",
+        };
+
+        Ok(ToSqlOutput::from(running))
+    }
+}
+
+impl FromSql for TaskStatus {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "true" => Ok(TaskStatus::Running),
+            "false" => Ok(TaskStatus::Ended),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+// wrapper used to read and write utc timestamps in the format used by the tasks table
+struct DbTimestamp(DateTime<Utc>);
+
+impl ToSql for DbTimestamp {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.0.format(DB_TIMESTAMP_FORMAT).to_string()))
+    }
+}
+
+impl FromSql for DbTimestamp {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let parsed = NaiveDateTime::parse_from_str(value.as_str()?, DB_TIMESTAMP_FORMAT)
+            .map_err(|err| FromSqlError::Other(Box::new(err)))?;
+
+        Ok(DbTimestamp(DateTime::<Utc>::from_utc(parsed, Utc)))
+    }
+}
+
+// a span of time within a task that wasn't worked, recorded by `pause` and `resume`
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaskBreak {
+    pub start_time: DateTime<Utc>,
+    pub end_time: Option<DateTime<Utc>>,
+}
+
+// the colors a project can be shown in
+pub const PROJECT_COLORS: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+// a project that tasks can be kept in.  tasks refer to projects by name, so a project's tasks are
+// rewritten when it's renamed or merged into another
+#[derive(Clone, Debug, PartialEq)]
+pub struct Project {
+    pub name: String,
     pub description: String,
+    pub color: Option<String>,
+    pub archived: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Project {
+    pub fn new(name: &str, created_at: DateTime<Utc>) -> Project {
+        Project {
+            name: name.to_string(),
+            description: String::new(),
+            color: None,
+            archived: false,
+            created_at,
+        }
+    }
+
+    pub fn save_to_db(&self, conn: &Connection) -> Result<(), Error> {
+        conn.execute(
+            "INSERT INTO projects (name, description, color, archived, created_at) VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(name) DO UPDATE SET
+                description=excluded.description,
+                color=excluded.color,
+                archived=excluded.archived,
+                created_at=excluded.created_at;",
+            params![self.name, self.description, self.color, self.archived, DbTimestamp(self.created_at)],
+        )?;
+
+        Ok(())
+    }
+}
+
+pub fn check_project_color(color: &str) -> Result<(), Error> {
+    if !PROJECT_COLORS.contains(&color) {
+        return Err(anyhow!(
+            "'{}' isn't a color taskmao can show projects in;  use one of {}",
+            color,
+            PROJECT_COLORS.join(", ")
+        ));
+    }
+
+    Ok(())
+}
+
+// returns every project, ordered by name
+pub fn get_projects(conn: &Connection) -> Result<Vec<Project>, Error> {
+    let mut stmt = conn.prepare("SELECT name, description, color, archived, created_at FROM projects ORDER BY name")?;
+    let projects = stmt
+        .query_map([], |r| {
+            Ok(Project {
+                name: r.get(0)?,
+                description: r.get(1)?,
+                color: r.get(2)?,
+                archived: r.get(3)?,
+                created_at: r.get::<_, DbTimestamp>(4)?.0,
+            })
+        })?
+        .collect::<Result<Vec<Project>>>()?;
+
+    Ok(projects)
+}
+
+pub fn delete_project_by_name(conn: &Connection, name: &str) -> Result<(), Error> {
+    conn.execute("DELETE FROM projects WHERE name = ?1;", params![name])?;
+
+    Ok(())
+}
+
+// a timestamped annotation added to a task by `note`
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaskNote {
+    pub time: DateTime<Utc>,
+    pub text: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Task {
+    pub breaks: Vec<TaskBreak>,
+    pub description: String,
+    pub end_time: Option<DateTime<Utc>>,
+    pub notes: Vec<TaskNote>,
     pub project_name: String,
-    pub running: String,
-    pub start_time: String,
+    pub start_time: DateTime<Utc>,
+    pub status: TaskStatus,
+    pub tags: Vec<String>,
     pub unique_id: String,
+    // when the task was moved to the trash.  tasks in the trash are left out of everything except
+    // looking them up by their exact id
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
-impl TaskDto {
-    pub fn end_task(&mut self, end_time: String) {
-        self.running = "false".to_string();
-        self.end_time = end_time;
+impl Task {
+    pub fn new(description: &str, project_name: &str, start_time: DateTime<Utc>) -> Task {
+        Task {
+            breaks: Vec::new(),
+            description: description.to_string(),
+            end_time: None,
+            notes: Vec::new(),
+            project_name: project_name.to_string(),
+            start_time,
+            status: TaskStatus::Running,
+            tags: Vec::new(),
+            unique_id: Uuid::new_v4().to_string(),
+            deleted_at: None,
+        }
+    }
+
+    // returns the time spent on the task, up until `now` if it is still running, less any breaks
+    pub fn duration(&self, now: DateTime<Utc>) -> Duration {
+        self.duration_between(self.start_time, self.end_time.unwrap_or(now), now)
+    }
+
+    // returns the time spent on the task between `from` and `to`, less any breaks
+    pub fn duration_between(&self, from: DateTime<Utc>, to: DateTime<Utc>, now: DateTime<Utc>) -> Duration {
+        let overlap = |start: DateTime<Utc>, end: DateTime<Utc>| {
+            let start = std::cmp::max(start, from);
+            let end = std::cmp::min(end, to);
+            if end > start {
+                end - start
+            } else {
+                Duration::zero()
+            }
+        };
+
+        let end_time = self.end_time.unwrap_or(now);
+        let worked = overlap(self.start_time, end_time);
+
+        self.breaks.iter().fold(worked, |worked, task_break| {
+            let break_start = std::cmp::max(task_break.start_time, self.start_time);
+            let break_end = std::cmp::min(task_break.end_time.unwrap_or(now), end_time);
+            worked - overlap(break_start, break_end)
+        })
+    }
+
+    // ending a paused task also ends its break
+    pub fn end_task(&mut self, end_time: DateTime<Utc>) {
+        if let Some(task_break) = self.breaks.iter_mut().find(|b| b.end_time.is_none()) {
+            task_break.end_time = Some(std::cmp::max(task_break.start_time, end_time));
+        }
+
+        self.status = TaskStatus::Ended;
+        self.end_time = Some(end_time);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.breaks.iter().any(|b| b.end_time.is_none())
+    }
+
+    pub fn pause(&mut self, time: DateTime<Utc>) -> Result<(), Error> {
+        if !self.is_running() {
+            return Err(anyhow!("only a running task can be paused"));
+        }
+        if self.is_paused() {
+            return Err(anyhow!("the current task is already paused"));
+        }
+
+        let last_activity = self.breaks.iter().filter_map(|b| b.end_time).max().unwrap_or(self.start_time);
+        if time < last_activity {
+            return Err(anyhow!("a task can't be paused before it started or was last resumed"));
+        }
+
+        self.breaks.push(TaskBreak { start_time: time, end_time: None });
+        Ok(())
+    }
+
+    pub fn resume(&mut self, time: DateTime<Utc>) -> Result<(), Error> {
+        let task_break = self
+            .breaks
+            .iter_mut()
+            .find(|b| b.end_time.is_none())
+            .ok_or(anyhow!("the current task isn't paused"))?;
+
+        if time < task_break.start_time {
+            return Err(anyhow!("a task can't be resumed before it was paused"));
+        }
+
+        task_break.end_time = Some(time);
+        Ok(())
+    }
+
+    pub fn has_tags(&self, tags: &[String]) -> bool {
+        tags.iter().all(|tag| self.tags.contains(tag))
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.status == TaskStatus::Running
+    }
+
+    fn save_breaks(&self, conn: &Connection) -> Result<(), Error> {
+        conn.execute("DELETE FROM task_breaks WHERE task_unique_id = ?1;", params![self.unique_id])?;
+
+        for task_break in &self.breaks {
+            conn.execute(
+                "INSERT INTO task_breaks (task_unique_id, start_time, end_time) VALUES (?1, ?2, ?3);",
+                params![
+                    self.unique_id,
+                    DbTimestamp(task_break.start_time),
+                    task_break.end_time.map(DbTimestamp)
+                ],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn save_notes(&self, conn: &Connection) -> Result<(), Error> {
+        conn.execute("DELETE FROM task_notes WHERE task_unique_id = ?1;", params![self.unique_id])?;
+
+        for note in &self.notes {
+            conn.execute(
+                "INSERT INTO task_notes (task_unique_id, time, text) VALUES (?1, ?2, ?3);",
+                params![self.unique_id, DbTimestamp(note.time), note.text],
+            )?;
+        }
+
+        Ok(())
     }
 
+    fn save_tags(&self, conn: &Connection) -> Result<(), Error> {
+        conn.execute("DELETE FROM task_tags WHERE task_unique_id = ?1;", params![self.unique_id])?;
+
+        for tag in &self.tags {
+            conn.execute(
+                "INSERT INTO task_tags (task_unique_id, tag) VALUES (?1, ?2);",
+                params![self.unique_id, tag],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // inserts the task, or updates it if it has already been saved.  a different task sharing
+    // its start time is an error rather than being overwritten
     pub fn save_to_db(&self, conn: &Connection) -> Result<(), Error> {
         conn.execute(
-            "INSERT INTO tasks (end_time, description, project_name, running, start_time, unique_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6)
-             ON CONFLICT(start_time) DO UPDATE SET
+            "INSERT INTO tasks (end_time, description, project_name, running, start_time, unique_id, deleted_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT(unique_id) DO UPDATE SET
                 end_time=excluded.end_time,
                 description=excluded.description,
                 project_name=excluded.project_name,
                 running=excluded.running,
-                unique_id=excluded.unique_id;",
-            params![self.end_time, self.description, self.project_name, self.running, self.start_time, self.unique_id],
+                start_time=excluded.start_time,
+                deleted_at=excluded.deleted_at;",
+            params![
+                self.end_time.map(DbTimestamp),
+                self.description,
+                self.project_name,
+                self.status,
+                DbTimestamp(self.start_time),
+                self.unique_id,
+                self.deleted_at.map(DbTimestamp)
+            ],
+        )?;
+        self.save_tags(conn)?;
+        self.save_breaks(conn)?;
+        self.save_notes(conn)?;
+
+        Ok(())
+    }
+
+    // unlike `save_to_db`, this looks the task up by its unique id so that its start time can change
+    pub fn update_in_db(&self, conn: &Connection) -> Result<(), Error> {
+        conn.execute(
+            "UPDATE tasks SET end_time = ?1, description = ?2, project_name = ?3, running = ?4, start_time = ?5, deleted_at = ?7 WHERE unique_id = ?6;",
+            params![
+                self.end_time.map(DbTimestamp),
+                self.description,
+                self.project_name,
+                self.status,
+                DbTimestamp(self.start_time),
+                self.unique_id,
+                self.deleted_at.map(DbTimestamp)
+            ],
         )?;
+        self.save_tags(conn)?;
+        self.save_breaks(conn)?;
+        self.save_notes(conn)?;
 
         Ok(())
     }
 }
 
+fn parse_db_timestamp(timestamp: &str) -> FromSqlResult<DateTime<Utc>> {
+    DbTimestamp::column_result(ValueRef::Text(timestamp.as_bytes())).map(|t| t.0)
+}
+
+fn breaks_from_column(breaks: &str) -> FromSqlResult<Vec<TaskBreak>> {
+    let mut parsed = breaks
+        .split(',')
+        .map(|task_break| {
+            let (start_time, end_time) = task_break.split_once('/').ok_or(FromSqlError::InvalidType)?;
+
+            Ok(TaskBreak {
+                start_time: parse_db_timestamp(start_time)?,
+                end_time: match end_time {
+                    "" => None,
+                    end_time => Some(parse_db_timestamp(end_time)?),
+                },
+            })
+        })
+        .collect::<FromSqlResult<Vec<TaskBreak>>>()?;
+    parsed.sort_by_key(|b| b.start_time);
+
+    Ok(parsed)
+}
+
+fn notes_from_column(notes: &str) -> FromSqlResult<Vec<TaskNote>> {
+    let pairs: Vec<(String, String)> = serde_json::from_str(notes).map_err(|err| FromSqlError::Other(Box::new(err)))?;
+
+    pairs
+        .into_iter()
+        .map(|(time, text)| {
+            Ok(TaskNote {
+                time: parse_db_timestamp(&time)?,
+                text,
+            })
+        })
+        .collect()
+}
+
+fn task_from_row(r: &Row) -> Result<Task> {
+    Ok(Task {
+        breaks: match r.get::<_, Option<String>>(8)? {
+            Some(breaks) => breaks_from_column(&breaks)
+                .map_err(|err| rusqlite::Error::FromSqlConversionFailure(8, rusqlite::types::Type::Text, Box::new(err)))?,
+            None => Vec::new(),
+        },
+        description: r.get(0)?,
+        notes: notes_from_column(&r.get::<_, String>(9)?)
+            .map_err(|err| rusqlite::Error::FromSqlConversionFailure(9, rusqlite::types::Type::Text, Box::new(err)))?,
+        project_name: r.get(1)?,
+        status: r.get(2)?,
+        end_time: r.get::<_, Option<DbTimestamp>>(3)?.map(|t| t.0),
+        start_time: r.get::<_, DbTimestamp>(4)?.0,
+        unique_id: r.get(5)?,
+        deleted_at: r.get::<_, Option<DbTimestamp>>(6)?.map(|t| t.0),
+        tags: match r.get::<_, Option<String>>(7)? {
+            Some(tags) => {
+                let mut tags: Vec<String> = tags.split(',').map(String::from).collect();
+                tags.sort();
+                tags
+            }
+            None => Vec::new(),
+        },
+    })
+}
+
 pub fn delete_task_by_id(conn: &Connection, task_unique_id: &str) -> Result<(), Error> {
     conn.execute("DELETE from tasks where unique_id = ?1;", params![task_unique_id])?;
+    conn.execute("DELETE FROM task_tags WHERE task_unique_id = ?1;", params![task_unique_id])?;
+    conn.execute("DELETE FROM task_breaks WHERE task_unique_id = ?1;", params![task_unique_id])?;
+    conn.execute("DELETE FROM task_notes WHERE task_unique_id = ?1;", params![task_unique_id])?;
 
     Ok(())
 }
 
-pub fn find_task_by_id(conn: &Connection, task_unique_id: &str) -> Result<TaskDto, Error> {
-    let stmt = "SELECT description, project_name, running, end_time, start_time, unique_id FROM tasks WHERE unique_id = ?1";
-    let task: TaskDto = conn.query_row(stmt, [task_unique_id], |r| {
-        Ok(TaskDto {
-            description: r.get(0)?,
-            project_name: r.get(1)?,
-            running: r.get(2)?,
-            end_time: r.get(3)?,
-            start_time: r.get(4)?,
-            unique_id: r.get(5)?,
-        })
-    })?;
+pub fn find_task_by_id(conn: &Connection, task_unique_id: &str) -> Result<Option<Task>, Error> {
+    let stmt = format!("SELECT {} FROM tasks WHERE unique_id = ?1", TASK_COLUMNS);
+    let task = conn.query_row(&stmt, [task_unique_id], task_from_row).optional()?;
 
     Ok(task)
 }
 
-pub fn get_most_recent_task(conn: &Connection) -> Result<TaskDto, Error> {
-    let stmt = "SELECT description, project_name, running, end_time, start_time, unique_id FROM tasks WHERE id = (SELECT MAX(id) FROM tasks) and running = 'true'";
-    let task: TaskDto = conn.query_row(stmt, [], |r| {
-        Ok(TaskDto {
-            description: r.get(0)?,
-            project_name: r.get(1)?,
-            running: r.get(2)?,
-            end_time: r.get(3)?,
-            start_time: r.get(4)?,
-            unique_id: r.get(5)?,
-        })
-    })?;
+pub fn get_all_tasks(conn: &Connection) -> Result<Vec<Task>, Error> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM tasks WHERE tasks.deleted_at IS NULL ORDER BY tasks.start_time", TASK_COLUMNS))?;
+    let tasks = stmt.query_map([], task_from_row)?.collect::<Result<Vec<Task>>>()?;
+
+    Ok(tasks)
+}
+
+pub fn get_most_recent_task(conn: &Connection) -> Result<Option<Task>, Error> {
+    // logged tasks can be inserted after the running task, so the newest row isn't always the running one
+    let stmt = format!(
+        "SELECT {} FROM tasks WHERE running = 'true' AND tasks.deleted_at IS NULL ORDER BY id DESC LIMIT 1",
+        TASK_COLUMNS
+    );
+    let task = conn.query_row(&stmt, [], task_from_row).optional()?;
 
     Ok(task)
 }
 
-pub fn get_tasks_start_with(conn: &Connection, id: &str) -> Result<Vec<TaskDto>, Error> {
-    let mut stmt = conn.prepare(&("SELECT description, project_name, running, end_time, start_time, unique_id FROM tasks WHERE tasks.unique_id LIKE '%".to_owned() + id +"%'"))?;
-    let mut rows = stmt.query([])?;
+pub fn get_most_recent_ended_task(conn: &Connection) -> Result<Option<Task>, Error> {
+    let stmt = format!(
+        "SELECT {} FROM tasks WHERE running = 'false' AND tasks.deleted_at IS NULL ORDER BY end_time DESC, id DESC LIMIT 1",
+        TASK_COLUMNS
+    );
+    let task = conn.query_row(&stmt, [], task_from_row).optional()?;
 
-    let mut tasks = Vec::new();
-    while let Some(r) = rows.next()? {
-        tasks.push(TaskDto {
-            description: r.get(0)?,
-            project_name: r.get(1)?,
-            running: r.get(2)?,
-            end_time: r.get(3)?,
-            start_time: r.get(4)?,
-            unique_id: r.get(5)?,
-        });
-    }
+    Ok(task)
+}
+
+// returns the tasks whose time span intersects the span from `start_time` to `end_time`.
+// running tasks are treated as spanning up until `now`, and tasks sharing a start time
+// always overlap since start times are unique
+pub fn get_overlapping_tasks(
+    conn: &Connection,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Result<Vec<Task>, Error> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM tasks WHERE ((tasks.start_time < ?2 AND COALESCE(tasks.end_time, ?3) > ?1) OR tasks.start_time = ?1) AND tasks.deleted_at IS NULL ORDER BY tasks.start_time",
+        TASK_COLUMNS
+    ))?;
+    let tasks = stmt
+        .query_map(params![DbTimestamp(start_time), DbTimestamp(end_time), DbTimestamp(now)], task_from_row)?
+        .collect::<Result<Vec<Task>>>()?;
 
     Ok(tasks)
 }
 
-pub fn get_todays_tasks(conn: &Connection) -> Result<Vec<TaskDto>, Error> {
-    let mut stmt = conn.prepare("SELECT description, project_name, running, end_time, start_time, unique_id FROM tasks WHERE tasks.end_time >= DATETIME('now', '-24 hour')")?;
-    let mut rows = stmt.query([])?;
+// returns the tasks that were worked on at some point between `from` and `to`.  running tasks
+// are treated as running up until `now`
+pub fn get_tasks_in_range(conn: &Connection, from: DateTime<Utc>, to: DateTime<Utc>, now: DateTime<Utc>) -> Result<Vec<Task>, Error> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM tasks WHERE tasks.start_time < ?2 AND COALESCE(tasks.end_time, ?3) > ?1 AND tasks.deleted_at IS NULL ORDER BY tasks.start_time",
+        TASK_COLUMNS
+    ))?;
+    let tasks = stmt
+        .query_map(params![DbTimestamp(from), DbTimestamp(to), DbTimestamp(now)], task_from_row)?
+        .collect::<Result<Vec<Task>>>()?;
 
-    let mut tasks = Vec::new();
+    Ok(tasks)
+}
 
-    while let Some(r) = rows.next()? {
-        tasks.push(TaskDto {
-            description: r.get(0)?,
-            project_name: r.get(1)?,
-            running: r.get(2)?,
-            end_time: r.get(3)?,
-            start_time: r.get(4)?,
-            unique_id: r.get(5)?,
-        });
-    }
+// compares the start of each id rather than using LIKE, so that '%' and '_' in the prefix
+// aren't treated as wildcards
+pub fn get_tasks_with_id_prefix(conn: &Connection, prefix: &str) -> Result<Vec<Task>, Error> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM tasks WHERE substr(tasks.unique_id, 1, length(?1)) = ?1 AND tasks.deleted_at IS NULL ORDER BY tasks.start_time",
+        TASK_COLUMNS
+    ))?;
+    let tasks = stmt.query_map(params![prefix], task_from_row)?.collect::<Result<Vec<Task>>>()?;
 
     Ok(tasks)
 }
 
+// searches the full-text index of descriptions, projects and notes, best matches first.  tasks in
+// the trash are left out
+pub fn search_task_index(conn: &Connection, query: &Query) -> Result<Vec<SearchHit>, Error> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, search.matched_description, search.matched_project, search.matched_notes, search.score FROM tasks
+         JOIN (SELECT rowid AS task_id,
+                      highlight(task_search, 0, char(2), char(3)) AS matched_description,
+                      highlight(task_search, 1, char(2), char(3)) AS matched_project,
+                      highlight(task_search, 2, char(2), char(3)) AS matched_notes,
+                      bm25(task_search) AS score
+               FROM task_search WHERE task_search MATCH ?1) AS search
+         ON tasks.id = search.task_id
+         WHERE tasks.deleted_at IS NULL ORDER BY search.score, tasks.start_time DESC",
+        TASK_COLUMNS
+    ))?;
+    let hits = stmt
+        .query_map([query.to_fts5()], |r| {
+            let task = task_from_row(r)?;
+            let notes: String = r.get(12)?;
+            Ok(SearchHit {
+                description: r.get(10)?,
+                project: r.get(11)?,
+                // the notes were indexed as one text, separated by char(30)
+                notes: match task.notes.is_empty() {
+                    true => Vec::new(),
+                    false => notes.split('\u{1e}').map(String::from).collect(),
+                },
+                // bm25 scores are lower for better matches
+                score: -r.get::<_, f64>(13)?,
+                task,
+            })
+        })?
+        .collect::<Result<Vec<SearchHit>>>()?;
 
+    Ok(hits)
+}
 
-pub fn set_up_sqlite(conn: &Connection) -> Result<()> {
-    let create_sql = r"
-        CREATE TABLE IF NOT EXISTS tasks (id INTEGER PRIMARY KEY, start_time TEXT UNIQUE, end_time TEXT, project_name TEXT, running TEXT, description TEXT, unique_id TEXT UNIQUE);
-        ";
+// returns the tasks in the trash, in the order they were deleted
+pub fn get_trashed_tasks(conn: &Connection) -> Result<Vec<Task>, Error> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM tasks WHERE tasks.deleted_at IS NOT NULL ORDER BY tasks.deleted_at, tasks.start_time",
+        TASK_COLUMNS
+    ))?;
+    let tasks = stmt.query_map([], task_from_row)?.collect::<Result<Vec<Task>>>()?;
 
-    conn.execute_batch(create_sql)?;
+    Ok(tasks)
+}
 
-    Ok(())
+pub fn get_task_ids(conn: &Connection) -> Result<Vec<String>, Error> {
+    let mut stmt = conn.prepare("SELECT tasks.unique_id FROM tasks WHERE tasks.deleted_at IS NULL ORDER BY tasks.unique_id")?;
+    let ids = stmt.query_map([], |row| row.get(0))?.collect::<Result<Vec<String>>>()?;
+
+    Ok(ids)
+}
+
+// returns the tasks worked on in the 24 hours before `now`
+pub fn get_todays_tasks(conn: &Connection, now: DateTime<Utc>) -> Result<Vec<Task>, Error> {
+    // running tasks have no end time, so they are included by when they started
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM tasks WHERE COALESCE(tasks.end_time, tasks.start_time) >= ?1 AND tasks.deleted_at IS NULL",
+        TASK_COLUMNS
+    ))?;
+    let tasks = stmt
+        .query_map(params![DbTimestamp(now - Duration::hours(24))], task_from_row)?
+        .collect::<Result<Vec<Task>>>()?;
+
+    Ok(tasks)
+}
+
+pub fn set_up_sqlite(conn: &Connection) -> Result<(), Error> {
+    migrations::run_migrations(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::parse_query;
+    use chrono::TimeZone;
+
+    fn set_up_test_db() -> Result<Connection, Error> {
+        let conn = Connection::open_in_memory()?;
+        set_up_sqlite(&conn)?;
+        Ok(conn)
+    }
+
+    #[test]
+    fn test_task_round_trips_through_db() -> Result<(), Error> {
+        let conn = set_up_test_db()?;
+        let mut task = Task::new("write tests", "taskmao", Utc.ymd(2021, 3, 18).and_hms(23, 36, 24));
+        task.save_to_db(&conn)?;
+
+        assert_eq!(find_task_by_id(&conn, &task.unique_id)?.unwrap(), task);
+        assert_eq!(get_most_recent_task(&conn)?.unwrap(), task);
+
+        task.end_task(Utc.ymd(2021, 3, 19).and_hms(1, 38, 59));
+        task.save_to_db(&conn)?;
+
+        assert_eq!(find_task_by_id(&conn, &task.unique_id)?.unwrap(), task);
+        assert!(get_most_recent_task(&conn)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_ranks_and_understands_fts_queries() -> Result<(), Error> {
+        let conn = set_up_test_db()?;
+        let once = Task::new("read about the oauth flow in the long spec document", "work", Utc.ymd(2021, 3, 18).and_hms(9, 0, 0));
+        let twice = Task::new("oauth oauth", "work", Utc.ymd(2021, 3, 18).and_hms(10, 0, 0));
+        let other = Task::new("lunch", "client", Utc.ymd(2021, 3, 18).and_hms(11, 0, 0));
+        for task in &[&once, &twice, &other] {
+            task.save_to_db(&conn)?;
+        }
+
+        let ids = |query: &str| -> Result<Vec<String>, Error> {
+            Ok(search_task_index(&conn, &parse_query(query)?)?.into_iter().map(|hit| hit.task.unique_id).collect())
+        };
+        assert_eq!(ids("oauth")?, vec![twice.unique_id.clone(), once.unique_id.clone()]);
+        assert_eq!(ids("oauth NOT spec")?, vec![twice.unique_id.clone()]);
+        assert_eq!(ids("project:client OR flow")?.len(), 2);
+        assert_eq!(ids("lunch OR (flow NOT spec)")?, vec![other.unique_id.clone()]);
+
+        let mut noted = other.clone();
+        noted.notes = vec![
+            TaskNote {
+                time: Utc.ymd(2021, 3, 18).and_hms(12, 0, 0),
+                text: "talked about the roadmap".to_string(),
+            },
+            TaskNote {
+                time: Utc.ymd(2021, 3, 18).and_hms(12, 30, 0),
+                text: "roadmap\nsent afterwards".to_string(),
+            },
+        ];
+        noted.save_to_db(&conn)?;
+        let hits = search_task_index(&conn, &parse_query("roadmap")?)?;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].notes, vec!["talked about the \u{2}roadmap\u{3}", "\u{2}roadmap\u{3}\nsent afterwards"]);
+
+        noted.notes.clear();
+        noted.save_to_db(&conn)?;
+        assert!(ids("roadmap")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tags_round_trip_through_db() -> Result<(), Error> {
+        let conn = set_up_test_db()?;
+        let mut task = Task::new("weekly sync", "taskmao", Utc.ymd(2021, 3, 18).and_hms(10, 0, 0));
+        task.tags = vec!["billable".to_string(), "meeting".to_string()];
+        task.save_to_db(&conn)?;
+
+        assert_eq!(find_task_by_id(&conn, &task.unique_id)?.unwrap().tags, task.tags);
+
+        task.tags = vec!["meeting".to_string()];
+        task.update_in_db(&conn)?;
+        assert_eq!(find_task_by_id(&conn, &task.unique_id)?.unwrap().tags, task.tags);
+
+        delete_task_by_id(&conn, &task.unique_id)?;
+        let count: u32 = conn.query_row("SELECT COUNT(*) FROM task_tags", [], |r| r.get(0))?;
+        assert_eq!(count, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_projects_round_trip_through_db() -> Result<(), Error> {
+        let conn = set_up_test_db()?;
+        let mut project = Project::new("work", Utc.ymd(2021, 3, 18).and_hms(10, 0, 0));
+        project.save_to_db(&conn)?;
+        Project::new("client", Utc.ymd(2021, 3, 18).and_hms(11, 0, 0)).save_to_db(&conn)?;
+
+        project.description = "the day job".to_string();
+        project.color = Some("blue".to_string());
+        project.archived = true;
+        project.save_to_db(&conn)?;
+        let projects = get_projects(&conn)?;
+        assert_eq!(projects.iter().map(|p| p.name.as_str()).collect::<Vec<&str>>(), vec!["client", "work"]);
+        assert_eq!(projects[1], project);
+
+        delete_project_by_name(&conn, "client")?;
+        assert_eq!(get_projects(&conn)?, vec![project]);
+        assert!(check_project_color("blue").is_ok());
+        assert!(check_project_color("teal").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_notes_round_trip_through_db() -> Result<(), Error> {
+        let conn = set_up_test_db()?;
+        let mut task = Task::new("weekly sync", "taskmao", Utc.ymd(2021, 3, 18).and_hms(10, 0, 0));
+        task.notes = vec![
+            TaskNote {
+                time: Utc.ymd(2021, 3, 18).and_hms(10, 30, 0),
+                text: "agreed on \"the plan\", mostly\n- ship it".to_string(),
+            },
+            TaskNote {
+                time: Utc.ymd(2021, 3, 18).and_hms(10, 45, 0),
+                text: "follow up, with [brackets]".to_string(),
+            },
+        ];
+        task.save_to_db(&conn)?;
+        assert_eq!(find_task_by_id(&conn, &task.unique_id)?.unwrap(), task);
+
+        delete_task_by_id(&conn, &task.unique_id)?;
+        let count: u32 = conn.query_row("SELECT COUNT(*) FROM task_notes", [], |r| r.get(0))?;
+        assert_eq!(count, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_to_db_does_not_overwrite_tasks_sharing_a_start_time() -> Result<(), Error> {
+        let conn = set_up_test_db()?;
+        let mut first = Task::new("first", "taskmao", Utc.ymd(2021, 3, 18).and_hms(10, 0, 0));
+        first.end_task(Utc.ymd(2021, 3, 18).and_hms(11, 0, 0));
+        first.save_to_db(&conn)?;
+
+        let second = Task::new("second", "taskmao", Utc.ymd(2021, 3, 18).and_hms(10, 0, 0));
+        assert!(second.save_to_db(&conn).is_err());
+        assert_eq!(find_task_by_id(&conn, &first.unique_id)?.unwrap(), first);
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_in_db_keeps_unique_id() -> Result<(), Error> {
+        let conn = set_up_test_db()?;
+        let mut task = Task::new("write tets", "taskmao", Utc.ymd(2021, 3, 18).and_hms(10, 0, 0));
+        task.end_task(Utc.ymd(2021, 3, 18).and_hms(11, 0, 0));
+        task.save_to_db(&conn)?;
+
+        task.description = "write tests".to_string();
+        task.start_time = Utc.ymd(2021, 3, 18).and_hms(9, 30, 0);
+        task.update_in_db(&conn)?;
+
+        assert_eq!(find_task_by_id(&conn, &task.unique_id)?.unwrap(), task);
+        let count: u32 = conn.query_row("SELECT COUNT(*) FROM tasks", [], |r| r.get(0))?;
+        assert_eq!(count, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_overlapping_tasks() -> Result<(), Error> {
+        let conn = set_up_test_db()?;
+        let now = Utc.ymd(2021, 3, 21).and_hms(0, 0, 0);
+        let mut task = Task::new("write tests", "taskmao", Utc.ymd(2021, 3, 18).and_hms(10, 0, 0));
+        task.end_task(Utc.ymd(2021, 3, 18).and_hms(11, 0, 0));
+        task.save_to_db(&conn)?;
+
+        let overlapping = get_overlapping_tasks(&conn, Utc.ymd(2021, 3, 18).and_hms(10, 30, 0), Utc.ymd(2021, 3, 18).and_hms(12, 0, 0), now)?;
+        assert_eq!(overlapping, vec![task.clone()]);
+
+        // tasks that only touch at their boundaries do not overlap
+        let adjacent = get_overlapping_tasks(&conn, Utc.ymd(2021, 3, 18).and_hms(11, 0, 0), Utc.ymd(2021, 3, 18).and_hms(12, 0, 0), now)?;
+        assert!(adjacent.is_empty());
+
+        let same_start = get_overlapping_tasks(&conn, Utc.ymd(2021, 3, 18).and_hms(10, 0, 0), Utc.ymd(2021, 3, 18).and_hms(10, 0, 0), now)?;
+        assert_eq!(same_start, vec![task.clone()]);
+
+        let running = Task::new("still going", "taskmao", Utc.ymd(2021, 3, 19).and_hms(9, 0, 0));
+        running.save_to_db(&conn)?;
+
+        let overlapping = get_overlapping_tasks(&conn, Utc.ymd(2021, 3, 20).and_hms(9, 0, 0), Utc.ymd(2021, 3, 20).and_hms(10, 0, 0), now)?;
+        assert_eq!(overlapping, vec![running.clone()]);
+
+        // running tasks only run up until now
+        let after_now = get_overlapping_tasks(&conn, now, now + Duration::hours(1), now)?;
+        assert!(after_now.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_most_recent_task_survives_logged_tasks() -> Result<(), Error> {
+        let conn = set_up_test_db()?;
+        let running = Task::new("still going", "taskmao", Utc.ymd(2021, 3, 19).and_hms(9, 0, 0));
+        running.save_to_db(&conn)?;
+
+        let mut logged = Task::new("forgot to start", "taskmao", Utc.ymd(2021, 3, 18).and_hms(10, 0, 0));
+        logged.end_task(Utc.ymd(2021, 3, 18).and_hms(11, 0, 0));
+        logged.save_to_db(&conn)?;
+
+        assert_eq!(get_most_recent_task(&conn)?.unwrap(), running);
+        Ok(())
+    }
+
+    #[test]
+    fn test_most_recent_ended_task() -> Result<(), Error> {
+        let conn = set_up_test_db()?;
+        assert!(get_most_recent_ended_task(&conn)?.is_none());
+
+        let mut later = Task::new("later", "taskmao", Utc.ymd(2021, 3, 18).and_hms(12, 0, 0));
+        later.end_task(Utc.ymd(2021, 3, 18).and_hms(13, 0, 0));
+        later.save_to_db(&conn)?;
+        // logged after the later task, but ended before it
+        let mut earlier = Task::new("earlier", "taskmao", Utc.ymd(2021, 3, 18).and_hms(10, 0, 0));
+        earlier.end_task(Utc.ymd(2021, 3, 18).and_hms(11, 0, 0));
+        earlier.save_to_db(&conn)?;
+        Task::new("running", "taskmao", Utc.ymd(2021, 3, 18).and_hms(14, 0, 0)).save_to_db(&conn)?;
+
+        assert_eq!(get_most_recent_ended_task(&conn)?.unwrap(), later);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_tasks_in_range() -> Result<(), Error> {
+        let conn = set_up_test_db()?;
+        let mut before = Task::new("before", "taskmao", Utc.ymd(2021, 3, 17).and_hms(10, 0, 0));
+        before.end_task(Utc.ymd(2021, 3, 17).and_hms(11, 0, 0));
+        before.save_to_db(&conn)?;
+        let mut spanning = Task::new("spanning", "taskmao", Utc.ymd(2021, 3, 17).and_hms(23, 0, 0));
+        spanning.end_task(Utc.ymd(2021, 3, 18).and_hms(1, 0, 0));
+        spanning.save_to_db(&conn)?;
+        let mut within = Task::new("within", "taskmao", Utc.ymd(2021, 3, 18).and_hms(10, 0, 0));
+        within.end_task(Utc.ymd(2021, 3, 18).and_hms(11, 0, 0));
+        within.save_to_db(&conn)?;
+
+        let tasks = get_tasks_in_range(
+            &conn,
+            Utc.ymd(2021, 3, 18).and_hms(0, 0, 0),
+            Utc.ymd(2021, 3, 19).and_hms(0, 0, 0),
+            Utc.ymd(2021, 3, 20).and_hms(0, 0, 0),
+        )?;
+        assert_eq!(tasks, vec![spanning, within]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_todays_tasks() -> Result<(), Error> {
+        let conn = set_up_test_db()?;
+        let mut old = Task::new("old", "taskmao", Utc.ymd(2021, 3, 17).and_hms(10, 0, 0));
+        old.end_task(Utc.ymd(2021, 3, 17).and_hms(11, 0, 0));
+        old.save_to_db(&conn)?;
+        let mut recent = Task::new("recent", "taskmao", Utc.ymd(2021, 3, 18).and_hms(10, 0, 0));
+        recent.end_task(Utc.ymd(2021, 3, 18).and_hms(11, 0, 0));
+        recent.save_to_db(&conn)?;
+
+        assert_eq!(get_todays_tasks(&conn, Utc.ymd(2021, 3, 18).and_hms(12, 0, 0))?, vec![recent]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_running_value_is_rejected() -> Result<(), Error> {
+        let conn = set_up_test_db()?;
+        conn.execute_batch(
+            "INSERT INTO tasks (start_time, end_time, project_name, running, description, unique_id) VALUES ('2021-03-18 23:36:24', NULL, 'default', 'maybe', 'bad task', 'abc');",
+        )?;
+
+        assert!(find_task_by_id(&conn, "abc").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_timestamp_is_rejected() -> Result<(), Error> {
+        let conn = set_up_test_db()?;
+        conn.execute_batch(
+            "INSERT INTO tasks (start_time, end_time, project_name, running, description, unique_id) VALUES ('009:009:009', NULL, 'default', 'true', 'bad task', 'abc');",
+        )?;
+
+        assert!(find_task_by_id(&conn, "abc").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_task_duration() {
+        let mut task = Task::new("write tests", "taskmao", Utc.ymd(2021, 3, 18).and_hms(23, 36, 24));
+        let now = Utc.ymd(2021, 3, 18).and_hms(23, 36, 59);
+
+        assert_eq!(task.duration(now), Duration::seconds(35));
+
+        task.end_task(Utc.ymd(2021, 3, 19).and_hms(1, 38, 59));
+        assert_eq!(task.duration(now), Duration::seconds(7355));
+    }
+
+    #[test]
+    fn test_paused_time_is_not_counted() -> Result<(), Error> {
+        let mut task = Task::new("write tests", "taskmao", Utc.ymd(2021, 3, 18).and_hms(10, 0, 0));
+        task.pause(Utc.ymd(2021, 3, 18).and_hms(11, 0, 0))?;
+        assert!(task.is_paused());
+        assert!(task.pause(Utc.ymd(2021, 3, 18).and_hms(11, 30, 0)).is_err());
+
+        // the clock stops while the task is paused
+        assert_eq!(task.duration(Utc.ymd(2021, 3, 18).and_hms(12, 0, 0)), Duration::hours(1));
+
+        task.resume(Utc.ymd(2021, 3, 18).and_hms(12, 0, 0))?;
+        assert!(!task.is_paused());
+        assert_eq!(task.duration(Utc.ymd(2021, 3, 18).and_hms(13, 0, 0)), Duration::hours(2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_breaks_round_trip_through_db() -> Result<(), Error> {
+        let conn = set_up_test_db()?;
+        let mut task = Task::new("write tests", "taskmao", Utc.ymd(2021, 3, 18).and_hms(10, 0, 0));
+        task.pause(Utc.ymd(2021, 3, 18).and_hms(11, 0, 0))?;
+        task.resume(Utc.ymd(2021, 3, 18).and_hms(11, 30, 0))?;
+        task.pause(Utc.ymd(2021, 3, 18).and_hms(12, 0, 0))?;
+        task.save_to_db(&conn)?;
+
+        assert_eq!(get_most_recent_task(&conn)?.unwrap(), task);
+
+        // ending a paused task closes its break
+        task.end_task(Utc.ymd(2021, 3, 18).and_hms(13, 0, 0));
+        task.save_to_db(&conn)?;
+        let saved = find_task_by_id(&conn, &task.unique_id)?.unwrap();
+        assert_eq!(saved, task);
+        assert_eq!(saved.duration(Utc.ymd(2021, 3, 18).and_hms(14, 0, 0)), Duration::minutes(90));
+
+        delete_task_by_id(&conn, &task.unique_id)?;
+        let count: u32 = conn.query_row("SELECT COUNT(*) FROM task_breaks", [], |r| r.get(0))?;
+        assert_eq!(count, 0);
+        Ok(())
+    }
 }
@@ -1,9 +1,380 @@
 extern crate chrono;
 
-use crate::data::TaskDto;
-use crate::time::{
-    convert_to_local_timestamp, get_local_datetime, get_time_between_stamps, get_todays_date,
+use crate::data::{Project, Task, TaskNote, PROJECT_COLORS};
+use crate::error::Error;
+use crate::ids::ShortIds;
+use crate::journal::{Change, Operation, ProjectChange};
+use crate::json::{
+    ConfigJson, ErrorJson, ImportJson, MessageJson, OperationJson, OperationsJson, ProfilesJson, ProjectEventJson, ProjectJson,
+    ProjectListEntryJson, ProjectUsageJson, ProjectsJson, ReportJson, SearchHitJson, SearchJson, TaskEventJson, TaskJson,
+    TasksJson, UnknownProjectJson,
 };
+use crate::profiles::Profile;
+use crate::report::Report;
+use crate::search::{SearchHit, MATCH_END, MATCH_START};
+use crate::time::{get_todays_local_date, Clock};
+use crate::transfer::ImportSummary;
+use anyhow::anyhow;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use serde::Serialize;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    // one json object per line.  only differs from `Json` for lists of tasks
+    Jsonl,
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            _ => Err(anyhow!("'{}' is not a supported output format;  use text, json or jsonl", format)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClockFormat {
+    TwentyFourHour,
+    TwelveHour,
+}
+
+impl FromStr for ClockFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(clock: &str) -> Result<Self, Self::Err> {
+        match clock {
+            "24h" => Ok(ClockFormat::TwentyFourHour),
+            "12h" => Ok(ClockFormat::TwelveHour),
+            _ => Err(anyhow!("'{}' is not a supported clock;  use 24h or 12h", clock)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DurationStyle {
+    // 0 days, 1 hours, 3 minutes and 10 seconds
+    Long,
+    // 1h 03m 10s
+    Short,
+    // 1.05h
+    Decimal,
+}
+
+impl FromStr for DurationStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(style: &str) -> Result<Self, Self::Err> {
+        match style {
+            "long" => Ok(DurationStyle::Long),
+            "short" => Ok(DurationStyle::Short),
+            "decimal" => Ok(DurationStyle::Decimal),
+            _ => Err(anyhow!("'{}' is not a supported duration style;  use long, short or decimal", style)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoundingMode {
+    Nearest,
+    Up,
+    Down,
+}
+
+impl FromStr for RoundingMode {
+    type Err = anyhow::Error;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode {
+            "nearest" => Ok(RoundingMode::Nearest),
+            "up" => Ok(RoundingMode::Up),
+            "down" => Ok(RoundingMode::Down),
+            _ => Err(anyhow!("'{}' is not a supported rounding;  use nearest, up or down", mode)),
+        }
+    }
+}
+
+// rounds durations to a multiple of `minutes`.  zero minutes leaves durations as they are
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rounding {
+    pub minutes: i64,
+    pub mode: RoundingMode,
+}
+
+impl Rounding {
+    pub fn apply(&self, duration: Duration) -> Duration {
+        if self.minutes <= 0 {
+            return duration;
+        }
+
+        let step = self.minutes * 60;
+        let seconds = duration.num_seconds();
+        let rounded_down = seconds - seconds.rem_euclid(step);
+        let rounded = match self.mode {
+            RoundingMode::Down => rounded_down,
+            RoundingMode::Up if rounded_down == seconds => seconds,
+            RoundingMode::Up => rounded_down + step,
+            RoundingMode::Nearest if seconds - rounded_down >= (step + 1) / 2 => rounded_down + step,
+            RoundingMode::Nearest => rounded_down,
+        };
+
+        Duration::seconds(rounded)
+    }
+}
+
+// errors if `date_format` has anything that isn't a valid strftime specifier
+pub fn check_date_format(date_format: &str) -> Result<(), anyhow::Error> {
+    if date_format.is_empty() || StrftimeItems::new(date_format).any(|item| item == Item::Error) {
+        return Err(anyhow!("'{}' is not a valid date format, such as '%Y-%m-%d'", date_format));
+    }
+
+    Ok(())
+}
+
+// how text output is written.  json output is never styled
+#[derive(Clone, Debug, PartialEq)]
+pub struct Style {
+    pub date_format: String,
+    pub clock: ClockFormat,
+    pub duration_style: DurationStyle,
+    pub rounding: Rounding,
+    pub color: bool,
+}
+
+impl Default for Style {
+    fn default() -> Style {
+        Style {
+            date_format: "%Y-%m-%d".to_string(),
+            clock: ClockFormat::TwentyFourHour,
+            duration_style: DurationStyle::Long,
+            rounding: Rounding {
+                minutes: 0,
+                mode: RoundingMode::Nearest,
+            },
+            color: false,
+        }
+    }
+}
+
+impl Style {
+    pub fn date(&self, date: NaiveDate) -> String {
+        date.format(&self.date_format).to_string()
+    }
+
+    pub fn timestamp(&self, utc_date_time: &DateTime<Utc>, tz: &impl TimeZone, should_display_date: bool) -> String {
+        let time_format = match self.clock {
+            ClockFormat::TwentyFourHour => "%H:%M:%S",
+            ClockFormat::TwelveHour => "%-I:%M:%S %p",
+        };
+        let local = utc_date_time.with_timezone(tz).naive_local();
+
+        match should_display_date {
+            true => format!("{} {}", self.date(local.date()), local.format(time_format)),
+            false => local.format(time_format).to_string(),
+        }
+    }
+
+    pub fn duration(&self, duration: Duration) -> String {
+        let duration = self.rounding.apply(duration);
+
+        match self.duration_style {
+            DurationStyle::Long => create_duration_str(duration),
+            DurationStyle::Short => format!(
+                "{}h {:02}m {:02}s",
+                duration.num_hours(),
+                duration.num_minutes() % 60,
+                duration.num_seconds() % 60
+            ),
+            DurationStyle::Decimal => format!("{:.2}h", duration.num_seconds() as f64 / 3600.0),
+        }
+    }
+
+    // task descriptions stand out in bold cyan
+    pub fn highlight(&self, text: &str) -> String {
+        match self.color {
+            true => format!("\x1b[1;36m{}\x1b[0m", text),
+            false => text.to_string(),
+        }
+    }
+
+    // search matches are underlined, which keeps the color of the text around them, or put in
+    // brackets without color
+    pub fn search_matches(&self, text: &str) -> String {
+        let (start, end) = match self.color {
+            true => ("\x1b[4m", "\x1b[24m"),
+            false => ("[", "]"),
+        };
+
+        text.replace(MATCH_START, start).replace(MATCH_END, end)
+    }
+
+    // projects are shown in their own color, if they have one
+    pub fn project(&self, name: &str, color: Option<&str>) -> String {
+        match (self.color, color.and_then(|color| PROJECT_COLORS.iter().position(|c| *c == color))) {
+            (true, Some(idx)) => format!("\x1b[{}m{}\x1b[0m", 30 + idx, name),
+            _ => name.to_string(),
+        }
+    }
+
+    fn error_prefix(&self) -> &'static str {
+        match self.color {
+            true => "\x1b[1;31mtaskmao:\x1b[0m",
+            false => "taskmao:",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Output {
+    pub format: OutputFormat,
+    pub style: Style,
+}
+
+impl Output {
+    pub fn new(format: OutputFormat) -> Output {
+        Output {
+            format,
+            style: Style::default(),
+        }
+    }
+}
+
+fn write_json(value: &impl Serialize, mut writer: impl std::io::Write) -> Result<(), anyhow::Error> {
+    serde_json::to_writer(&mut writer, value)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+fn task_event_json(event: &'static str, task: &Task, ended_task: Option<&Task>, now: DateTime<Utc>) -> TaskEventJson {
+    TaskEventJson {
+        event,
+        task: TaskJson::new(task, now),
+        ended_task: ended_task.map(|t| TaskJson::new(t, now)),
+    }
+}
+
+fn write_tasks_json(
+    kind: &'static str,
+    tasks: &[Task],
+    now: DateTime<Utc>,
+    output: &Output,
+    mut writer: impl std::io::Write,
+) -> Result<(), anyhow::Error> {
+    let tasks: Vec<TaskJson> = tasks.iter().map(|t| TaskJson::new(t, now)).collect();
+
+    if output.format == OutputFormat::Jsonl {
+        for task in &tasks {
+            write_json(task, &mut writer)?;
+        }
+        return Ok(());
+    }
+
+    write_json(&TasksJson { kind, tasks }, writer)
+}
+
+fn tags_str(task: &Task) -> String {
+    task.tags.iter().map(|tag| format!("+{}", tag)).collect::<Vec<String>>().join(" ")
+}
+
+// writes a task's notes, oldest first, with the lines after the first of each note indented
+fn write_notes(task: &Task, tz: &impl TimeZone, style: &Style, mut writer: impl std::io::Write) -> Result<(), anyhow::Error> {
+    if task.notes.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(writer, "    Notes:")?;
+    for note in &task.notes {
+        writeln!(
+            writer,
+            "        {}: {}",
+            style.timestamp(&note.time, tz, true),
+            note.text.replace('\n', "\n            ")
+        )?;
+    }
+
+    Ok(())
+}
+
+// writes the block of details shared by the task listings, which show the short form of the id.
+// tasks in the trash also show when they were deleted
+fn write_task_details(
+    task: &Task,
+    short_id: &str,
+    with_notes: bool,
+    now: DateTime<Utc>,
+    tz: &impl TimeZone,
+    style: &Style,
+    mut writer: impl std::io::Write,
+) -> Result<(), anyhow::Error> {
+    let start_time = style.timestamp(&task.start_time, tz, true);
+    let project = if task.tags.is_empty() {
+        task.project_name.clone()
+    } else {
+        format!("{}\n    Tags: {}", task.project_name, tags_str(task))
+    };
+    let short_id = match task.deleted_at {
+        Some(deleted_at) => format!("{}\n    Deleted: {}", short_id, style.timestamp(&deleted_at, tz, true)),
+        None => short_id.to_string(),
+    };
+
+    if task.is_running() {
+        let label = if task.deleted_at.is_some() { "Cancelled Task" } else { "Current Task" };
+        writeln!(
+            writer,
+            "{}: {}\n    Project: {}\n    Start Time: {}\n    Task Id: {}",
+            label,
+            style.highlight(&task.description),
+            project,
+            start_time,
+            short_id
+        )?;
+        if with_notes {
+            write_notes(task, tz, style, &mut writer)?;
+        }
+        writeln!(writer)?;
+    } else {
+        let end_time = style.timestamp(&task.end_time.unwrap_or(task.start_time), tz, true);
+        let duration = task.duration(now);
+
+        writeln!(
+            writer,
+            "    Task: {}\n    Project: {}\n    Start Time: {}\n    End Time: {}\n    Duration: {}\n    Task Id: {}",
+            style.highlight(&task.description),
+            project,
+            start_time,
+            end_time,
+            style.duration(duration),
+            short_id
+        )?;
+        if with_notes {
+            write_notes(task, tz, style, &mut writer)?;
+        }
+        writeln!(writer, "---\n")?;
+    }
+
+    Ok(())
+}
+
+// shows a task before a command that asks for confirmation changes it
+pub fn task_summary(
+    task: &Task,
+    short_id: &str,
+    clock: &dyn Clock,
+    tz: &impl TimeZone,
+    output: &Output,
+    writer: impl std::io::Write,
+) -> Result<(), anyhow::Error> {
+    write_task_details(task, short_id, false, clock.now(), tz, &output.style, writer)
+}
 
 pub fn create_duration_str(duration: chrono::Duration) -> String {
     let seconds = if duration.num_seconds() < 60 {
@@ -22,148 +393,951 @@ pub fn create_duration_str(duration: chrono::Duration) -> String {
         duration.num_hours() % 24
     };
 
-    format!(
-        "{} days, {} hours, {} minutes and {} seconds",
-        duration.num_days(),
-        hours,
-        minutes,
-        seconds
+    format!(
+        "{} days, {} hours, {} minutes and {} seconds",
+        duration.num_days(),
+        hours,
+        minutes,
+        seconds
+    )
+}
+
+pub fn custom_message(
+    message_to_display: &str,
+    output: &Output,
+    mut writer: impl std::io::Write,
+) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_json(&MessageJson { kind: "message", message: message_to_display }, writer);
+    }
+
+    writeln!(writer, "taskmao: {}", message_to_display)?;
+    Ok(())
+}
+
+// a problem that doesn't stop the command, such as a task being started in a project that
+// hasn't been added.  like errors, warnings are written to stderr
+pub fn warning(err: &dyn std::fmt::Display, output: &Output, mut writer: impl std::io::Write) -> Result<(), anyhow::Error> {
+    let message = err.to_string();
+    if output.format != OutputFormat::Text {
+        return write_json(&MessageJson { kind: "warning", message: &message }, writer);
+    }
+
+    writeln!(writer, "{} warning: {}", output.style.error_prefix(), message)?;
+    Ok(())
+}
+
+pub fn error(
+    err: &anyhow::Error,
+    clock: &dyn Clock,
+    tz: &impl TimeZone,
+    output: &Output,
+    mut writer: impl std::io::Write,
+) -> Result<(), anyhow::Error> {
+    let (conflicting_tasks, candidates) = match err.downcast_ref::<Error>() {
+        Some(Error::Overlap(tasks)) => (tasks.as_slice(), &[][..]),
+        Some(Error::AmbiguousId { candidates, .. }) => (&[][..], candidates.as_slice()),
+        _ => (&[][..], &[][..]),
+    };
+
+    if output.format != OutputFormat::Text {
+        let now = clock.now();
+        return write_json(
+            &ErrorJson {
+                kind: "error",
+                message: err.to_string(),
+                conflicting_tasks: conflicting_tasks.iter().map(|t| TaskJson::new(t, now)).collect(),
+                candidates: candidates.iter().map(|t| TaskJson::new(t, now)).collect(),
+            },
+            writer,
+        );
+    }
+
+    writeln!(writer, "{} {}", output.style.error_prefix(), err)?;
+    for task in conflicting_tasks.iter().chain(candidates) {
+        let start_time = output.style.timestamp(&task.start_time, tz, true);
+        let end_time = match task.end_time {
+            Some(end_time) => output.style.timestamp(&end_time, tz, true),
+            None => "now".to_string(),
+        };
+
+        writeln!(
+            writer,
+            "    {} - {}: {} ({})",
+            start_time, end_time, task.description, task.unique_id
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_operations_json(
+    kind: &'static str,
+    operations: &[Operation],
+    now: DateTime<Utc>,
+    writer: impl std::io::Write,
+) -> Result<(), anyhow::Error> {
+    let operations = operations.iter().map(|op| OperationJson::new(op, now)).collect();
+    write_json(&OperationsJson { kind, operations }, writer)
+}
+
+// how a change to a task reads in the history
+fn change_str(change: &Change, style: &Style) -> String {
+    match (&change.before, &change.after) {
+        (None, Some(after)) => format!("added '{}'", style.highlight(&after.description)),
+        (Some(before), None) => format!("removed '{}'", style.highlight(&before.description)),
+        (Some(before), Some(after)) if before.deleted_at.is_none() && after.deleted_at.is_some() => {
+            format!("deleted '{}'", style.highlight(&after.description))
+        }
+        (Some(before), Some(after)) if before.deleted_at.is_some() && after.deleted_at.is_none() => {
+            format!("restored '{}'", style.highlight(&after.description))
+        }
+        (Some(before), Some(after)) if before.is_running() && !after.is_running() => {
+            format!("ended '{}'", style.highlight(&after.description))
+        }
+        (Some(before), Some(after)) if after.notes.len() > before.notes.len() => {
+            format!("added a note to '{}'", style.highlight(&after.description))
+        }
+        (_, Some(after)) => format!("changed '{}'", style.highlight(&after.description)),
+        (None, None) => format!("changed '{}'", change.task_unique_id),
+    }
+}
+
+fn project_change_str(change: &ProjectChange) -> String {
+    match (&change.before, &change.after) {
+        (None, Some(_)) => format!("added project '{}'", change.name),
+        (Some(_), None) => format!("removed project '{}'", change.name),
+        (Some(before), Some(after)) if !before.archived && after.archived => format!("archived project '{}'", change.name),
+        (Some(before), Some(after)) if before.archived && !after.archived => format!("unarchived project '{}'", change.name),
+        _ => format!("changed project '{}'", change.name),
+    }
+}
+
+fn write_changes(operation: &Operation, style: &Style, mut writer: impl std::io::Write) -> Result<(), anyhow::Error> {
+    for change in &operation.project_changes {
+        writeln!(writer, "    {}", project_change_str(change))?;
+    }
+    for change in &operation.changes {
+        writeln!(writer, "    {}", change_str(change, style))?;
+    }
+
+    Ok(())
+}
+
+pub fn history(
+    operations: &[Operation],
+    clock: &dyn Clock,
+    tz: &impl TimeZone,
+    output: &Output,
+    mut writer: impl std::io::Write,
+) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_operations_json("history", operations, clock.now(), writer);
+    }
+
+    if operations.is_empty() {
+        return custom_message("there are no changes in the history yet", output, writer);
+    }
+
+    for operation in operations {
+        let undone = if operation.undone { " (undone)" } else { "" };
+        writeln!(
+            writer,
+            "{}: {} at {}{}",
+            operation.id,
+            operation.command,
+            output.style.timestamp(&operation.time, tz, true),
+            undone
+        )?;
+        write_changes(operation, &output.style, &mut writer)?;
+    }
+
+    Ok(())
+}
+
+// `undo` and `redo` both write each operation they went through
+fn operations_reverted(
+    verb: &str,
+    operations: &[Operation],
+    tz: &impl TimeZone,
+    output: &Output,
+    mut writer: impl std::io::Write,
+) -> Result<(), anyhow::Error> {
+    for operation in operations {
+        writeln!(
+            writer,
+            "taskmao: {} '{}' from {}",
+            verb,
+            operation.command,
+            output.style.timestamp(&operation.time, tz, true)
+        )?;
+        write_changes(operation, &output.style, &mut writer)?;
+    }
+
+    Ok(())
+}
+
+// lists the tasks a bulk command matched, as they'll be once it's made its change
+pub fn bulk_preview(
+    command: &str,
+    tasks: &[Task],
+    short_ids: &ShortIds,
+    clock: &dyn Clock,
+    tz: &impl TimeZone,
+    output: &Output,
+    mut writer: impl std::io::Write,
+) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_tasks_json("bulk_preview", tasks, clock.now(), output, writer);
+    }
+
+    let task_str = if tasks.len() == 1 { "task" } else { "tasks" };
+    writeln!(writer, "\n{} would change {} {}:\n---\n", command, tasks.len(), task_str)?;
+    for task in tasks {
+        write_task_details(task, short_ids.get(&task.unique_id), false, clock.now(), tz, &output.style, &mut writer)?;
+    }
+
+    Ok(())
+}
+
+pub fn bulk_edit(tasks: &[Task], clock: &dyn Clock, output: &Output, writer: impl std::io::Write) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_tasks_json("tasks_updated", tasks, clock.now(), output, writer);
+    }
+
+    let task_str = if tasks.len() == 1 { "task" } else { "tasks" };
+    custom_message(
+        &format!("updated {} {}.  run 'taskmao undo' to change them back", tasks.len(), task_str),
+        output,
+        writer,
+    )
+}
+
+pub fn bulk_delete(tasks: &[Task], clock: &dyn Clock, output: &Output, writer: impl std::io::Write) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_tasks_json("tasks_deleted", tasks, clock.now(), output, writer);
+    }
+
+    let task_str = if tasks.len() == 1 { "task" } else { "tasks" };
+    custom_message(
+        &format!("moved {} {} to the trash.  run 'taskmao undo' to bring them back", tasks.len(), task_str),
+        output,
+        writer,
+    )
+}
+
+pub fn trash_list(
+    tasks: Vec<Task>,
+    short_ids: &ShortIds,
+    clock: &dyn Clock,
+    tz: &impl TimeZone,
+    output: &Output,
+    mut writer: impl std::io::Write,
+) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_tasks_json("trash", &tasks, clock.now(), output, writer);
+    }
+
+    if tasks.is_empty() {
+        return custom_message("the trash is empty", output, writer);
+    }
+
+    let (verb, task_str) = if tasks.len() == 1 { ("is", "task") } else { ("are", "tasks") };
+    writeln!(writer, "\nThere {} {} {} in the trash\n---\n", verb, tasks.len(), task_str)?;
+    for task in &tasks {
+        write_task_details(task, short_ids.get(&task.unique_id), false, clock.now(), tz, &output.style, &mut writer)?;
+    }
+
+    Ok(())
+}
+
+pub fn trash_restore(task: &Task, clock: &dyn Clock, output: &Output, writer: impl std::io::Write) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_json(&task_event_json("task_restored", task, None, clock.now()), writer);
+    }
+
+    custom_message(
+        &format!(
+            "restored task '{}' with id '{}' from the trash",
+            output.style.highlight(&task.description),
+            task.unique_id
+        ),
+        output,
+        writer,
+    )
+}
+
+pub fn trash_empty(tasks: &[Task], clock: &dyn Clock, output: &Output, writer: impl std::io::Write) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_tasks_json("trash_emptied", tasks, clock.now(), output, writer);
+    }
+
+    let task_str = if tasks.len() == 1 { "task" } else { "tasks" };
+    custom_message(
+        &format!("removed {} {} from the trash", tasks.len(), task_str),
+        output,
+        writer,
+    )
+}
+
+pub fn task_undo(
+    operations: &[Operation],
+    clock: &dyn Clock,
+    tz: &impl TimeZone,
+    output: &Output,
+    writer: impl std::io::Write,
+) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_operations_json("undo", operations, clock.now(), writer);
+    }
+
+    operations_reverted("undid", operations, tz, output, writer)
+}
+
+pub fn task_redo(
+    operations: &[Operation],
+    clock: &dyn Clock,
+    tz: &impl TimeZone,
+    output: &Output,
+    writer: impl std::io::Write,
+) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_operations_json("redo", operations, clock.now(), writer);
+    }
+
+    operations_reverted("redid", operations, tz, output, writer)
+}
+
+pub fn import_summary(summary: &ImportSummary, output: &Output, mut writer: impl std::io::Write) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_json(&ImportJson::new(summary), writer);
+    }
+
+    let task_str = if summary.imported.len() == 1 { "task" } else { "tasks" };
+    writeln!(writer, "taskmao: imported {} {}", summary.imported.len(), task_str)?;
+
+    if !summary.duplicates.is_empty() {
+        writeln!(
+            writer,
+            "    skipped {} already existing: {}",
+            summary.duplicates.len(),
+            summary.duplicates.join(", ")
+        )?;
+    }
+
+    if !summary.errors.is_empty() {
+        writeln!(writer, "    {} rows could not be imported:", summary.errors.len())?;
+        for error in &summary.errors {
+            writeln!(writer, "        line {}: {}", error.line, error.message)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn config_get(key: &str, value: &str, output: &Output, mut writer: impl std::io::Write) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_json(&ConfigJson::new(&[(key, value.to_string())]), writer);
+    }
+
+    writeln!(writer, "{}", value)?;
+
+    Ok(())
+}
+
+pub fn config_list(settings: &[(&str, String)], output: &Output, mut writer: impl std::io::Write) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_json(&ConfigJson::new(settings), writer);
+    }
+
+    for (key, value) in settings {
+        writeln!(writer, "{} = {}", key, value)?;
+    }
+
+    Ok(())
+}
+
+pub fn config_set(key: &str, value: &str, output: &Output, writer: impl std::io::Write) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_json(&ConfigJson::new(&[(key, value.to_string())]), writer);
+    }
+
+    custom_message(&format!("set '{}' to '{}'", key, value), output, writer)
+}
+
+// lists the profiles, marking the one in use.  a database given with --db or TASKMAO_DB that
+// isn't a profile's is listed after them
+pub fn profiles(profiles: &[Profile], active_path: &Path, output: &Output, mut writer: impl std::io::Write) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_json(&ProfilesJson::new(profiles, active_path), writer);
+    }
+
+    for profile in profiles {
+        let marker = if profile.path == active_path { "*" } else { " " };
+        writeln!(writer, "{} {}: {}", marker, profile.name, profile.path.display())?;
+    }
+    if !profiles.iter().any(|profile| profile.path == active_path) {
+        writeln!(writer, "* {}", active_path.display())?;
+    }
+
+    Ok(())
+}
+
+pub fn report(report: &Report, tz: &impl TimeZone, output: &Output, mut writer: impl std::io::Write) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_json(&ReportJson::new(report), writer);
+    }
+
+    let from_date = output.style.date(report.from.with_timezone(tz).naive_local().date());
+    // the end of a report is exclusive, so the last day covered is the one before it
+    let to_date = output.style.date((report.to - chrono::Duration::seconds(1)).with_timezone(tz).naive_local().date());
+
+    if report.by_project.is_empty() {
+        writeln!(
+            writer,
+            "taskmao: there was no time tracked from {} to {}",
+            from_date, to_date
+        )?;
+        return Ok(());
+    }
+
+    writeln!(
+        writer,
+        "\nReport from {} to {}\n---\nTotal: {}\n",
+        from_date,
+        to_date,
+        output.style.duration(report.total)
+    )?;
+
+    writeln!(writer, "By Project:")?;
+    for (project, duration) in &report.by_project {
+        writeln!(writer, "    {}: {}", project, output.style.duration(*duration))?;
+    }
+
+    writeln!(writer, "\nBy Day:")?;
+    for (day, duration) in &report.by_day {
+        writeln!(writer, "    {}: {}", output.style.date(*day), output.style.duration(*duration))?;
+    }
+
+    writeln!(writer, "\nBy Description:")?;
+    for (project, description, duration) in &report.by_description {
+        writeln!(writer, "    {} / {}: {}", project, description, output.style.duration(*duration))?;
+    }
+
+    if !report.by_tag.is_empty() {
+        writeln!(writer, "\nBy Tag:")?;
+        for (tag, duration) in &report.by_tag {
+            writeln!(writer, "    +{}: {}", tag, output.style.duration(*duration))?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn task_cancel(task: &Task, clock: &dyn Clock, output: &Output, writer: impl std::io::Write) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_json(&task_event_json("task_cancelled", task, None, clock.now()), writer);
+    }
+
+    custom_message(
+        &format!(
+            "cancelled current task and moved it to the trash.  run 'taskmao trash restore {}' to bring it back",
+            task.unique_id
+        ),
+        output,
+        writer,
     )
 }
 
-pub fn custom_message(
-    message_to_display: &str,
+pub fn task_delete(task: &Task, clock: &dyn Clock, output: &Output, writer: impl std::io::Write) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_json(&task_event_json("task_deleted", task, None, clock.now()), writer);
+    }
+
+    custom_message(
+        &format!(
+            "moved task with id '{}' to the trash.  run 'taskmao trash restore {}' to bring it back",
+            task.unique_id, task.unique_id
+        ),
+        output,
+        writer,
+    )
+}
+
+pub fn task_edit(
+    task: &Task,
+    clock: &dyn Clock,
+    tz: &impl TimeZone,
+    output: &Output,
     mut writer: impl std::io::Write,
 ) -> Result<(), anyhow::Error> {
-    writeln!(writer, "taskmao: {}", message_to_display)?;
+    if output.format != OutputFormat::Text {
+        return write_json(&task_event_json("task_updated", task, None, clock.now()), writer);
+    }
+
+    let start_time = output.style.timestamp(&task.start_time, tz, true);
+    let end_time = match task.end_time {
+        Some(end_time) => output.style.timestamp(&end_time, tz, true),
+        None => "still running".to_string(),
+    };
+
+    writeln!(
+        writer,
+        "taskmao: updated task '{}'\n    Project: {}\n    Start Time: {}\n    End Time: {}\n    Task Id: {}",
+        output.style.highlight(&task.description),
+        task.project_name,
+        start_time,
+        end_time,
+        task.unique_id
+    )?;
+    if !task.tags.is_empty() {
+        writeln!(writer, "    Tags: {}", tags_str(task))?;
+    }
+
     Ok(())
 }
 
-pub fn task_end(task_end_timestamp: &str, task_desc: &str) -> Result<(), anyhow::Error> {
-    let time = convert_to_local_timestamp(task_end_timestamp, false)?;
+pub fn task_end(
+    task: &Task,
+    clock: &dyn Clock,
+    tz: &impl TimeZone,
+    output: &Output,
+    mut writer: impl std::io::Write,
+) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_json(&task_event_json("task_ended", task, None, clock.now()), writer);
+    }
+
+    let time = output.style.timestamp(&task.end_time.unwrap_or(task.start_time), tz, false);
 
-    println!("taskmao: stopped running '{}' at {}", task_desc, time);
+    writeln!(writer, "taskmao: stopped running '{}' at {}", output.style.highlight(&task.description), time)?;
 
     Ok(())
 }
 
-pub fn task_find(tasks: Vec<TaskDto>, task_id: &str, mut writer: impl std::io::Write) -> Result<(), anyhow::Error> {
-    if tasks.len() < 1 {
+pub fn task_find(
+    tasks: Vec<Task>,
+    task_id: &str,
+    short_ids: &ShortIds,
+    clock: &dyn Clock,
+    tz: &impl TimeZone,
+    output: &Output,
+    mut writer: impl std::io::Write,
+) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_tasks_json("tasks", &tasks, clock.now(), output, writer);
+    }
+
+    if tasks.is_empty() {
         writeln!(
             writer,
-            "taskmao: there were no tasks found with the string '{}' in their id\nlook up another id and try again",
+            "taskmao: there were no tasks found whose id starts with '{}'\nlook up another id and try again",
             task_id
         )?;
     } else {
-        let task_strs = if tasks.len() == 1 { ("task", "contains") } else { ("tasks", "contain") };
+        let task_str = if tasks.len() == 1 { "task" } else { "tasks" };
 
         writeln!(
             writer,
-            "\nFound {} {} that {} the id '{}':\n---\n",
+            "\nFound {} {} whose id starts with '{}':\n---\n",
             tasks.len(),
-            task_strs.0,
-            task_strs.1,
+            task_str,
             task_id,
         )?;
 
         for task in &tasks {
-            let start_time = convert_to_local_timestamp(&task.start_time, true)?;
-            let end_time = convert_to_local_timestamp(&task.end_time, true)?;
-
-            if task.running == "true" {
-                writeln!(
-                    writer,
-                    "Current Task: {}\n    Project: {}\n    Start Time: {}\n    Task Id: {}\n",
-                    task.description,
-                    task.project_name,
-                    start_time,
-                    task.unique_id
-                )?;
-            } else {
-                let duration = get_time_between_stamps(&start_time, &end_time)?;
-
-                writeln!(
-                    writer,
-                    "    Task: {}\n    Project: {}\n    Start Time: {}\n    End Time: {}\n    Duration: {}\n    Task Id: {}\n---\n",
-                    task.description,
-                    task.project_name,
-                    start_time,
-                    end_time,
-                    create_duration_str(duration),
-                    task.unique_id
-                )?;
+            write_task_details(task, short_ids.get(&task.unique_id), true, clock.now(), tz, &output.style, &mut writer)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn search(
+    hits: &[SearchHit],
+    query: &str,
+    short_ids: &ShortIds,
+    clock: &dyn Clock,
+    tz: &impl TimeZone,
+    output: &Output,
+    mut writer: impl std::io::Write,
+) -> Result<(), anyhow::Error> {
+    let now = clock.now();
+    match output.format {
+        OutputFormat::Jsonl => {
+            for hit in hits {
+                write_json(&SearchHitJson::new(hit, now), &mut writer)?;
             }
+            return Ok(());
+        }
+        OutputFormat::Json => {
+            let hits = hits.iter().map(|hit| SearchHitJson::new(hit, now)).collect();
+            return write_json(&SearchJson { kind: "search", query: query.to_string(), hits }, writer);
         }
+        OutputFormat::Text => (),
     }
+
+    if hits.is_empty() {
+        return custom_message(&format!("there were no tasks found matching '{}'", query), output, writer);
+    }
+
+    let task_str = if hits.len() == 1 { "task" } else { "tasks" };
+    writeln!(writer, "\nFound {} {} matching '{}', best matches first:\n---\n", hits.len(), task_str, query)?;
+    for hit in hits {
+        let mut task = hit.task.clone();
+        task.description = output.style.search_matches(&hit.description);
+        task.project_name = output.style.search_matches(&hit.project);
+        // only the notes with a match are shown
+        task.notes = task
+            .notes
+            .into_iter()
+            .zip(&hit.notes)
+            .filter(|(_, matched)| matched.contains(MATCH_START))
+            .map(|(note, matched)| TaskNote {
+                text: output.style.search_matches(matched),
+                ..note
+            })
+            .collect();
+        write_task_details(&task, short_ids.get(&task.unique_id), true, now, tz, &output.style, &mut writer)?;
+    }
+
     Ok(())
 }
 
-pub fn task_info(task: TaskDto, mut writer: impl std::io::Write) -> Result<(), anyhow::Error> {
-    let time = convert_to_local_timestamp(&task.start_time, true)?;
+pub fn task_info(
+    task: Task,
+    clock: &dyn Clock,
+    tz: &impl TimeZone,
+    output: &Output,
+    mut writer: impl std::io::Write,
+) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_json(&task_event_json("task", &task, None, clock.now()), writer);
+    }
+
+    let time = output.style.timestamp(&task.start_time, tz, true);
 
-    let duration = get_time_between_stamps(&time, &get_local_datetime())?;
+    let duration = task.duration(clock.now());
     writeln!(
         writer,
         "taskmao: currently running '{}' that started at '{}'\n    Duration: {}",
-        task.description,
+        output.style.highlight(&task.description),
         time,
-        create_duration_str(duration)
+        output.style.duration(duration)
     )?;
+    if let Some(task_break) = task.breaks.iter().find(|b| b.end_time.is_none()) {
+        writeln!(
+            writer,
+            "    Paused since: {}",
+            output.style.timestamp(&task_break.start_time, tz, true)
+        )?;
+    }
+    write_notes(&task, tz, &output.style, writer)?;
 
     Ok(())
 }
 
-pub fn task_list(tasks: Vec<TaskDto>, mut writer: impl std::io::Write) -> Result<(), anyhow::Error> {
+// what `list` was asked for: the days from the first up to and including the last, or the last day
+// when there are none, and whether to show each task's notes
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ListOptions {
+    pub days: Option<(NaiveDate, NaiveDate)>,
+    pub verbose: bool,
+}
+
+pub fn task_list(
+    tasks: Vec<Task>,
+    options: ListOptions,
+    short_ids: &ShortIds,
+    clock: &dyn Clock,
+    tz: &impl TimeZone,
+    output: &Output,
+    mut writer: impl std::io::Write,
+) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_tasks_json("tasks", &tasks, clock.now(), output, writer);
+    }
+
     let task_str = if tasks.len() == 1 { "task" } else { "tasks" };
+    let when = match options.days {
+        Some((from, to)) if from == to => format!("on {}", output.style.date(from)),
+        Some((from, to)) => format!("from {} to {}", output.style.date(from), output.style.date(to)),
+        None => format!("on the previous day, {}", output.style.date(get_todays_local_date(clock, tz))),
+    };
 
-    println!(
-        "\nYou have completed {} {} on the previous day, {}\n---\n",
-        tasks.len(),
-        task_str,
-        get_todays_date()
-    );
+    writeln!(writer, "\nYou have completed {} {} {}\n---\n", tasks.len(), task_str, when)?;
     for task in &tasks {
-        let start_time = convert_to_local_timestamp(&task.start_time, true)?;
-        let end_time = convert_to_local_timestamp(&task.end_time, true)?;
-        let duration = get_time_between_stamps(&start_time, &end_time)?;
+        write_task_details(task, short_ids.get(&task.unique_id), options.verbose, clock.now(), tz, &output.style, &mut writer)?;
+    }
+
+    Ok(())
+}
+
+pub fn task_log(
+    task: &Task,
+    clock: &dyn Clock,
+    tz: &impl TimeZone,
+    output: &Output,
+    mut writer: impl std::io::Write,
+) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_json(&task_event_json("task_logged", task, None, clock.now()), writer);
+    }
+
+    let start_time = output.style.timestamp(&task.start_time, tz, true);
+    let end_time = output.style.timestamp(&task.end_time.unwrap_or(task.start_time), tz, true);
+
+    writeln!(
+        writer,
+        "taskmao: logged '{}' from '{}' to '{}'\n    Duration: {}\n    Task Id: {}",
+        output.style.highlight(&task.description),
+        start_time,
+        end_time,
+        output.style.duration(task.duration(clock.now())),
+        task.unique_id
+    )?;
+
+    Ok(())
+}
+
+fn project_event_json(kind: &'static str, project: &Project, tasks: Option<&[Task]>, now: DateTime<Utc>) -> ProjectEventJson {
+    ProjectEventJson {
+        kind,
+        project: ProjectJson::new(project),
+        tasks: tasks.map(|tasks| tasks.iter().map(|task| TaskJson::new(task, now)).collect()),
+    }
+}
+
+pub fn project_add(project: &Project, clock: &dyn Clock, output: &Output, writer: impl std::io::Write) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_json(&project_event_json("project_added", project, None, clock.now()), writer);
+    }
+
+    custom_message(
+        &format!("added project '{}'", output.style.project(&project.name, project.color.as_deref())),
+        output,
+        writer,
+    )
+}
+
+pub fn project_archive(project: &Project, clock: &dyn Clock, output: &Output, writer: impl std::io::Write) -> Result<(), anyhow::Error> {
+    let (kind, verb) = match project.archived {
+        true => ("project_archived", "archived"),
+        false => ("project_unarchived", "unarchived"),
+    };
+    if output.format != OutputFormat::Text {
+        return write_json(&project_event_json(kind, project, None, clock.now()), writer);
+    }
+
+    custom_message(&format!("{} project '{}'", verb, project.name), output, writer)
+}
+
+// `project rename` and `project merge` both move tasks into `project`
+pub fn project_moved(
+    from: &str,
+    project: &Project,
+    tasks: &[Task],
+    merged: bool,
+    clock: &dyn Clock,
+    output: &Output,
+    writer: impl std::io::Write,
+) -> Result<(), anyhow::Error> {
+    let (kind, verb) = match merged {
+        true => ("projects_merged", "merged project"),
+        false => ("project_renamed", "renamed project"),
+    };
+    if output.format != OutputFormat::Text {
+        return write_json(&project_event_json(kind, project, Some(tasks), clock.now()), writer);
+    }
+
+    let task_str = if tasks.len() == 1 { "task" } else { "tasks" };
+    custom_message(
+        &format!(
+            "{} '{}' into '{}', moving {} {}",
+            verb,
+            from,
+            output.style.project(&project.name, project.color.as_deref()),
+            tasks.len(),
+            task_str
+        ),
+        output,
+        writer,
+    )
+}
+
+// lists the projects, leaving out archived ones unless `all`, with how many tasks and how much
+// time each has.  projects that tasks use without having been added are listed after them
+pub fn project_list(
+    projects: &[Project],
+    tasks: &[Task],
+    all: bool,
+    clock: &dyn Clock,
+    output: &Output,
+    mut writer: impl std::io::Write,
+) -> Result<(), anyhow::Error> {
+    let now = clock.now();
+    let usage = |name: &str| {
+        let in_project = tasks.iter().filter(|task| task.project_name == name);
+        ProjectUsageJson {
+            tasks: in_project.clone().count(),
+            duration_seconds: in_project.map(|task| task.duration(now).num_seconds()).sum(),
+        }
+    };
+    let listed: Vec<&Project> = projects.iter().filter(|project| all || !project.archived).collect();
+    let mut unknown: Vec<&str> = tasks
+        .iter()
+        .map(|task| task.project_name.as_str())
+        .filter(|name| projects.iter().all(|project| project.name != *name))
+        .collect();
+    unknown.sort_unstable();
+    unknown.dedup();
+
+    if output.format != OutputFormat::Text {
+        let projects_json = ProjectsJson {
+            kind: "projects",
+            projects: listed
+                .iter()
+                .map(|project| ProjectListEntryJson {
+                    project: ProjectJson::new(project),
+                    usage: usage(&project.name),
+                })
+                .collect(),
+            unknown: unknown
+                .iter()
+                .map(|name| UnknownProjectJson {
+                    name: name.to_string(),
+                    usage: usage(name),
+                })
+                .collect(),
+        };
+        return write_json(&projects_json, writer);
+    }
+
+    if listed.is_empty() && unknown.is_empty() {
+        return custom_message("there are no projects yet.  run 'taskmao project add NAME' to add one", output, writer);
+    }
+
+    let write_usage = |name: &str, writer: &mut dyn std::io::Write| -> Result<(), anyhow::Error> {
+        let usage = usage(name);
+        let task_str = if usage.tasks == 1 { "task" } else { "tasks" };
+        write!(
+            writer,
+            "{} {}, {}",
+            usage.tasks,
+            task_str,
+            output.style.duration(Duration::seconds(usage.duration_seconds))
+        )?;
+        Ok(())
+    };
+
+    let project_str = if listed.len() == 1 { "project" } else { "projects" };
+    writeln!(writer, "\nYou have {} {}\n---\n", listed.len(), project_str)?;
+    for project in &listed {
+        let archived = if project.archived { " (archived)" } else { "" };
+        write!(writer, "    {}{}: ", output.style.project(&project.name, project.color.as_deref()), archived)?;
+        write_usage(&project.name, &mut writer)?;
+        writeln!(writer)?;
+        if !project.description.is_empty() {
+            writeln!(writer, "        {}", project.description)?;
+        }
+    }
 
-        if task.running == "true" {
-            writeln!(
-                writer,
-                "Current Task: {}\n    Project: {}\n    Start Time: {}\n    Task Id: {}\n",
-                task.description,
-                task.project_name,
-                start_time,
-                task.unique_id
-            )?;
-        } else {
-            writeln!(
-                writer,
-                "    Task: {}\n    Project: {}\n    Start Time: {}\n    End Time: {}\n    Duration: {}\n    Task Id: {}\n---\n",
-                task.description,
-                task.project_name,
-                start_time,
-                end_time,
-                create_duration_str(duration),
-                task.unique_id
-            )?;
+    if !unknown.is_empty() {
+        writeln!(
+            writer,
+            "\nUsed by tasks without being added.  add them with 'taskmao project add NAME', or move their tasks with 'taskmao project merge NAME INTO':\n"
+        )?;
+        for name in &unknown {
+            write!(writer, "    {}: ", name)?;
+            write_usage(name, &mut writer)?;
+            writeln!(writer)?;
         }
     }
 
     Ok(())
 }
 
+pub fn task_note(task: &Task, clock: &dyn Clock, output: &Output, mut writer: impl std::io::Write) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_json(&task_event_json("note_added", task, None, clock.now()), writer);
+    }
+
+    writeln!(writer, "taskmao: added a note to '{}'", output.style.highlight(&task.description))?;
+
+    Ok(())
+}
+
+pub fn task_pause(
+    task: &Task,
+    clock: &dyn Clock,
+    tz: &impl TimeZone,
+    output: &Output,
+    mut writer: impl std::io::Write,
+) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_json(&task_event_json("task_paused", task, None, clock.now()), writer);
+    }
+
+    let time = match task.breaks.last() {
+        Some(task_break) => output.style.timestamp(&task_break.start_time, tz, false),
+        None => output.style.timestamp(&clock.now(), tz, false),
+    };
+
+    writeln!(writer, "taskmao: paused '{}' at {}", output.style.highlight(&task.description), time)?;
+
+    Ok(())
+}
+
+pub fn task_resume(
+    task: &Task,
+    clock: &dyn Clock,
+    tz: &impl TimeZone,
+    output: &Output,
+    mut writer: impl std::io::Write,
+) -> Result<(), anyhow::Error> {
+    if output.format != OutputFormat::Text {
+        return write_json(&task_event_json("task_resumed", task, None, clock.now()), writer);
+    }
+
+    let time = match task.breaks.last().and_then(|b| b.end_time) {
+        Some(end_time) => output.style.timestamp(&end_time, tz, false),
+        None => output.style.timestamp(&clock.now(), tz, false),
+    };
+
+    writeln!(
+        writer,
+        "taskmao: resumed '{}' at {}\n    Duration: {}",
+        output.style.highlight(&task.description),
+        time,
+        output.style.duration(task.duration(clock.now()))
+    )?;
+
+    Ok(())
+}
+
+// `ended_task` is the task that was running until the new one started, if there was one
 pub fn task_start(
-    task_start_timestamp: &str,
-    task_desc: &str,
+    task: &Task,
+    ended_task: Option<&Task>,
+    clock: &dyn Clock,
+    tz: &impl TimeZone,
+    output: &Output,
     mut writer: impl std::io::Write,
 ) -> Result<(), anyhow::Error> {
-    let time = convert_to_local_timestamp(task_start_timestamp, false)?;
+    if output.format != OutputFormat::Text {
+        return write_json(&task_event_json("task_started", task, ended_task, clock.now()), writer);
+    }
+
+    if let Some(ended_task) = ended_task {
+        task_end(ended_task, clock, tz, output, &mut writer)?;
+    }
+
+    let time = output.style.timestamp(&task.start_time, tz, false);
 
     writeln!(
         writer,
         "taskmao: started running task '{}' at {}",
-        task_desc, time
+        output.style.highlight(&task.description),
+        time
     )?;
 
     Ok(())
@@ -172,25 +1346,66 @@ pub fn task_start(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::Duration;
+    use crate::data::TaskBreak;
+    use crate::time::FixedClock;
+    use chrono::{Duration, FixedOffset};
+
+    fn clock() -> FixedClock {
+        FixedClock(Utc.ymd(2021, 3, 18).and_hms(12, 0, 0))
+    }
+
+    // an hour ahead of utc
+    fn tz() -> FixedOffset {
+        FixedOffset::east(3600)
+    }
 
     #[test]
     fn test_custom_message_printout() -> Result<(), anyhow::Error> {
         let mut result = Vec::new();
         let input = "this is my custom message";
-        custom_message(input, &mut result)?;
+        custom_message(input, &Output::new(OutputFormat::Text), &mut result)?;
         assert_eq!(result, b"taskmao: this is my custom message\n");
         Ok(())
     }
 
     #[test]
-    fn test_task_start_errors_when_time_is_invalid() {
-        let result = Vec::new();
-        let input = "101010101010:10:10";
-        let desc = "this is a test task";
-        let res = task_start(input, desc, result);
-        assert_eq!(res.is_err(), true);
+    fn test_custom_message_json() -> Result<(), anyhow::Error> {
+        let mut result = Vec::new();
+        let input = "this is my custom message";
+        custom_message(input, &Output::new(OutputFormat::Json), &mut result)?;
+        assert_eq!(result, b"{\"type\":\"message\",\"message\":\"this is my custom message\"}\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_task_start_printout() -> Result<(), anyhow::Error> {
+        let mut result = Vec::new();
+        let task = Task::new("this is a test task", "default", Utc.ymd(2021, 3, 18).and_hms(10, 15, 0));
+        task_start(&task, None, &clock(), &tz(), &Output::new(OutputFormat::Text), &mut result)?;
+        assert_eq!(
+            String::from_utf8(result)?,
+            "taskmao: started running task 'this is a test task' at 11:15:00\n"
+        );
+        Ok(())
+    }
+    #[test]
+    fn test_task_resume_uses_the_duration_style() -> Result<(), anyhow::Error> {
+        let mut result = Vec::new();
+        let mut task = Task::new("this is a test task", "default", Utc.ymd(2021, 3, 18).and_hms(10, 0, 0));
+        task.breaks = vec![TaskBreak {
+            start_time: Utc.ymd(2021, 3, 18).and_hms(10, 30, 0),
+            end_time: Some(Utc.ymd(2021, 3, 18).and_hms(11, 0, 0)),
+        }];
+        let mut output = Output::new(OutputFormat::Text);
+        output.style.duration_style = DurationStyle::Decimal;
+        task_resume(&task, &clock(), &tz(), &output, &mut result)?;
+        assert_eq!(
+            String::from_utf8(result)?,
+            "taskmao: resumed 'this is a test task' at 12:00:00\n    Duration: 1.50h\n"
+        );
+        Ok(())
     }
+
     #[test]
     fn test_create_duration_str() {
         let secs_duration = Duration::seconds(59);
@@ -215,13 +1430,114 @@ mod tests {
             format!("{} days, {} hours, {} minutes and {} seconds", 1, 0, 10, 0)
         );
     }
+
+    #[test]
+    fn test_duration_styles_and_rounding() {
+        let mut style = Style::default();
+        let duration = Duration::seconds(3790);
+
+        style.duration_style = DurationStyle::Short;
+        assert_eq!(style.duration(duration), "1h 03m 10s");
+        style.duration_style = DurationStyle::Decimal;
+        assert_eq!(style.duration(duration), "1.05h");
+
+        style.rounding = Rounding {
+            minutes: 15,
+            mode: RoundingMode::Nearest,
+        };
+        assert_eq!(style.duration(duration), "1.00h");
+        style.rounding.mode = RoundingMode::Up;
+        assert_eq!(style.duration(duration), "1.25h");
+        assert_eq!(style.duration(Duration::minutes(30)), "0.50h");
+        style.rounding.mode = RoundingMode::Down;
+        assert_eq!(style.duration(Duration::seconds(899)), "0.00h");
+    }
+
+    #[test]
+    fn test_timestamp_styles() {
+        let mut style = Style::default();
+        let time = Utc.ymd(2021, 3, 18).and_hms(16, 30, 5);
+        assert_eq!(style.timestamp(&time, &tz(), true), "2021-03-18 17:30:05");
+
+        style.date_format = "%d/%m/%Y".to_string();
+        style.clock = ClockFormat::TwelveHour;
+        assert_eq!(style.timestamp(&time, &tz(), true), "18/03/2021 5:30:05 PM");
+        assert_eq!(style.timestamp(&time, &tz(), false), "5:30:05 PM");
+        assert!(check_date_format("%d/%m/%Y").is_ok());
+        assert!(check_date_format("%Q").is_err());
+    }
+
     #[test]
     fn test_unfound_task() {
         let mut result = Vec::new();
-        let tasks = <Vec<TaskDto>>::new();
+        let tasks = <Vec<Task>>::new();
         let input = "2394890naerisntenuylunetanrsten";
-        let _res = task_find(tasks, input, &mut result);
+        let _res = task_find(tasks, input, &ShortIds::default(), &clock(), &tz(), &Output::new(OutputFormat::Text), &mut result);
         let str_output = String::from_utf8(result).unwrap();
-        assert_eq!(str_output, "taskmao: there were no tasks found with the string '2394890naerisntenuylunetanrsten' in their id\nlook up another id and try again\n")
+        assert_eq!(str_output, "taskmao: there were no tasks found whose id starts with '2394890naerisntenuylunetanrsten'\nlook up another id and try again\n")
+    }
+
+    #[test]
+    fn test_task_list_jsonl_writes_a_task_per_line() -> Result<(), anyhow::Error> {
+        let mut result = Vec::new();
+        let tasks = vec![
+            Task::new("first", "default", Utc.ymd(2021, 3, 18).and_hms(10, 0, 0)),
+            Task::new("second", "default", Utc.ymd(2021, 3, 18).and_hms(11, 0, 0)),
+        ];
+        task_list(tasks, ListOptions::default(), &ShortIds::default(), &clock(), &tz(), &Output::new(OutputFormat::Jsonl), &mut result)?;
+
+        let lines: Vec<serde_json::Value> = String::from_utf8(result)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["description"], "second");
+        Ok(())
+    }
+
+    #[test]
+    fn test_overlap_error_json_lists_conflicting_tasks() -> Result<(), anyhow::Error> {
+        let mut result = Vec::new();
+        let task = Task::new("first", "default", Utc.ymd(2021, 3, 18).and_hms(10, 0, 0));
+        let err = anyhow::Error::new(Error::Overlap(vec![task]));
+        error(&err, &clock(), &tz(), &Output::new(OutputFormat::Json), &mut result)?;
+
+        let json: serde_json::Value = serde_json::from_slice(&result)?;
+        assert_eq!(json["type"], "error");
+        assert_eq!(json["conflicting_tasks"][0]["description"], "first");
+        assert_eq!(json["conflicting_tasks"][0]["duration_seconds"], 7200);
+        Ok(())
+    }
+
+    #[test]
+    fn test_profiles_marks_the_active_database() -> Result<(), anyhow::Error> {
+        let profiles = vec![
+            Profile {
+                name: "default".to_string(),
+                path: "/data/base.sql3".into(),
+            },
+            Profile {
+                name: "work".to_string(),
+                path: "/data/profiles/work.sql3".into(),
+            },
+        ];
+
+        let mut output = Vec::new();
+        super::profiles(&profiles, Path::new("/data/profiles/work.sql3"), &Output::new(OutputFormat::Text), &mut output)?;
+        assert_eq!(
+            String::from_utf8(output)?,
+            "  default: /data/base.sql3\n* work: /data/profiles/work.sql3\n"
+        );
+
+        let mut output = Vec::new();
+        super::profiles(&profiles, Path::new("/tmp/other.sql3"), &Output::new(OutputFormat::Text), &mut output)?;
+        assert!(String::from_utf8(output)?.ends_with("* /tmp/other.sql3\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_format_from_str() {
+        assert_eq!("json".parse::<OutputFormat>().unwrap(), OutputFormat::Json);
+        assert!("yaml".parse::<OutputFormat>().is_err());
     }
 }
@@ -0,0 +1,85 @@
+use crate::data::Task;
+use std::fmt;
+
+// the errors returned by `Tracker`.  anything that goes wrong within the data store is a
+// `Storage` error, everything else is something the caller can act on
+#[derive(Debug)]
+pub enum Error {
+    AmbiguousId { id: String, candidates: Vec<Task> },
+    EmptyRange,
+    EndBeforeStart,
+    Invalid(String),
+    InvalidQuery { query: String, reason: String },
+    NoEndedTask,
+    NoRunningTask,
+    NothingToRedo,
+    NothingToUndo,
+    Overlap(Vec<Task>),
+    ProjectArchived(String),
+    ProjectExists(String),
+    ProjectNotFound(String),
+    Storage(anyhow::Error),
+    TaskNotFound(String),
+    TaskRunning(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::AmbiguousId { id, candidates } => write!(
+                f,
+                "{} tasks were found for the id '{}'.  enter more of the id and try again",
+                candidates.len(),
+                id
+            ),
+            Error::EmptyRange => write!(f, "the last day of a range can't be before its first day"),
+            Error::EndBeforeStart => write!(f, "the end time of a task can't be before its start time"),
+            Error::Invalid(message) => write!(f, "{}", message),
+            Error::InvalidQuery { query, reason } => write!(
+                f,
+                "'{}' isn't a search taskmao understands, as {}.  put phrases and words with punctuation in double quotes, such as '\"client-x\"'",
+                query, reason
+            ),
+            Error::NoEndedTask => write!(f, "you have no ended tasks"),
+            Error::NoRunningTask => write!(f, "you currently have no task running"),
+            Error::NothingToRedo => write!(f, "there's nothing to redo"),
+            Error::NothingToUndo => write!(f, "there's nothing to undo"),
+            Error::Overlap(_) => write!(
+                f,
+                "the times entered overlap with the following tasks.  adjust the times and try again"
+            ),
+            Error::ProjectArchived(name) => write!(
+                f,
+                "the project '{}' is archived.  run 'taskmao project unarchive {}' to use it again",
+                name, name
+            ),
+            Error::ProjectExists(name) => write!(
+                f,
+                "there's already a project named '{}'.  run 'taskmao project merge' to move tasks into it instead",
+                name
+            ),
+            Error::ProjectNotFound(name) => write!(
+                f,
+                "there's no project named '{}'.  run 'taskmao project add {}' to add it",
+                name, name
+            ),
+            Error::Storage(err) => write!(f, "{}", err),
+            Error::TaskNotFound(id) => write!(f, "no tasks were found for the id '{}'.  check the id and try again", id),
+            Error::TaskRunning(id) => write!(f, "the task '{}' is currently running.  end the task and try again", id),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<anyhow::Error> for Error {
+    fn from(err: anyhow::Error) -> Error {
+        Error::Storage(err)
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(err: rusqlite::Error) -> Error {
+        Error::Storage(err.into())
+    }
+}
@@ -0,0 +1,157 @@
+use crate::data::Task;
+use crate::error::Error;
+use crate::store::TaskStore;
+use std::collections::HashMap;
+
+// short ids are never shorter than this, so that they don't all change as more tasks are added
+pub const MIN_SHORT_ID_LEN: usize = 4;
+
+// finds the task that `id` refers to.  an exact id always wins, otherwise `id` has to be the
+// start of exactly one task's id.  tasks in the trash are never found
+pub fn resolve_id(store: &dyn TaskStore, id: &str) -> Result<Task, Error> {
+    check_id(id)?;
+
+    if let Some(task) = store.find_by_id(id)?.filter(|task| task.deleted_at.is_none()) {
+        return Ok(task);
+    }
+
+    pick_candidate(id, store.search_by_id_prefix(id)?)
+}
+
+// like `resolve_id`, but only looks through `tasks`
+pub fn resolve_id_among(tasks: Vec<Task>, id: &str) -> Result<Task, Error> {
+    check_id(id)?;
+
+    if let Some(task) = tasks.iter().find(|task| task.unique_id == id) {
+        return Ok(task.clone());
+    }
+
+    pick_candidate(id, tasks.into_iter().filter(|task| task.unique_id.starts_with(id)).collect())
+}
+
+fn check_id(id: &str) -> Result<(), Error> {
+    if id.is_empty() {
+        return Err(Error::Invalid("a task id can't be empty".to_string()));
+    }
+
+    Ok(())
+}
+
+fn pick_candidate(id: &str, mut candidates: Vec<Task>) -> Result<Task, Error> {
+    match candidates.len() {
+        0 => Err(Error::TaskNotFound(id.to_string())),
+        1 => Ok(candidates.remove(0)),
+        _ => Err(Error::AmbiguousId {
+            id: id.to_string(),
+            candidates,
+        }),
+    }
+}
+
+// the shortest start of each id that no other id starts with, like git's short commit hashes
+#[derive(Debug, Default)]
+pub struct ShortIds {
+    lengths: HashMap<String, usize>,
+}
+
+impl ShortIds {
+    pub fn new(sorted_ids: &[String]) -> ShortIds {
+        let mut lengths = HashMap::new();
+        for (idx, id) in sorted_ids.iter().enumerate() {
+            // in sorted order the ids sharing the longest start with `id` are its neighbours
+            let before = idx.checked_sub(1).map_or(0, |prev| common_prefix_len(id, &sorted_ids[prev]));
+            let after = sorted_ids.get(idx + 1).map_or(0, |next| common_prefix_len(id, next));
+            let len = (before.max(after) + 1).max(MIN_SHORT_ID_LEN);
+
+            lengths.insert(id.clone(), len.min(id.chars().count()));
+        }
+
+        ShortIds { lengths }
+    }
+
+    // the short form of `id`, or all of it if `id` wasn't known when these were worked out
+    pub fn get<'a>(&self, id: &'a str) -> &'a str {
+        match self.lengths.get(id) {
+            Some(len) => match id.char_indices().nth(*len) {
+                Some((end, _)) => &id[..end],
+                None => id,
+            },
+            None => id,
+        }
+    }
+}
+
+fn common_prefix_len(a: &str, b: &str) -> usize {
+    a.chars().zip(b.chars()).take_while(|(a, b)| a == b).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+    use chrono::{TimeZone, Utc};
+
+    fn store_with_ids(ids: &[&str]) -> Result<MemoryStore, anyhow::Error> {
+        let store = MemoryStore::new();
+        for (hour, id) in ids.iter().enumerate() {
+            let mut task = Task::new(id, "work", Utc.ymd(2021, 3, 18).and_hms(hour as u32, 0, 0));
+            task.unique_id = id.to_string();
+            store.save(&task)?;
+        }
+
+        Ok(store)
+    }
+
+    #[test]
+    fn test_resolve_id_by_prefix() -> Result<(), anyhow::Error> {
+        let store = store_with_ids(&["abc", "abc-1", "abd-2", "xyz-3"])?;
+
+        assert_eq!(resolve_id(&store, "abc")?.unique_id, "abc");
+        assert_eq!(resolve_id(&store, "abd")?.unique_id, "abd-2");
+        assert_eq!(resolve_id(&store, "x")?.unique_id, "xyz-3");
+        assert!(matches!(resolve_id(&store, "bc"), Err(Error::TaskNotFound(id)) if id == "bc"));
+        assert!(matches!(resolve_id(&store, ""), Err(Error::Invalid(_))));
+
+        match resolve_id(&store, "ab") {
+            Err(Error::AmbiguousId { id, candidates }) => {
+                assert_eq!(id, "ab");
+                let ids: Vec<&str> = candidates.iter().map(|task| task.unique_id.as_str()).collect();
+                assert_eq!(ids, vec!["abc", "abc-1", "abd-2"]);
+            }
+            other => panic!("expected an ambiguous id, got {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_trashed_tasks_are_resolved_separately() -> Result<(), anyhow::Error> {
+        let store = store_with_ids(&["abc-1", "abd-2"])?;
+        let mut trashed = store.find_by_id("abc-1")?.unwrap();
+        trashed.deleted_at = Some(Utc.ymd(2021, 3, 18).and_hms(12, 0, 0));
+        store.save(&trashed)?;
+
+        assert!(matches!(resolve_id(&store, "abc-1"), Err(Error::TaskNotFound(_))));
+        assert_eq!(resolve_id(&store, "ab")?.unique_id, "abd-2");
+        assert_eq!(resolve_id_among(store.trashed()?, "ab")?, trashed);
+        assert!(matches!(resolve_id_among(store.trashed()?, "abd"), Err(Error::TaskNotFound(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_short_ids_are_the_shortest_unique_prefix() {
+        let ids: Vec<String> = ["0123456-a", "0123457-b", "abcdef", "abc", "f"]
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<String>>();
+        let mut sorted_ids = ids.clone();
+        sorted_ids.sort();
+        let short_ids = ShortIds::new(&sorted_ids);
+
+        assert_eq!(short_ids.get("0123456-a"), "0123456");
+        assert_eq!(short_ids.get("0123457-b"), "0123457");
+        assert_eq!(short_ids.get("abcdef"), "abcd");
+        assert_eq!(short_ids.get("abc"), "abc");
+        assert_eq!(short_ids.get("f"), "f");
+        assert_eq!(short_ids.get("unknown-id"), "unknown-id");
+    }
+}
@@ -0,0 +1,307 @@
+extern crate rusqlite;
+
+use crate::data::{Project, Task, TaskBreak, TaskNote, TaskStatus};
+use anyhow::{anyhow, Error};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+// a single task being added, changed or removed.  `before` is empty for a task that was added and
+// `after` is empty for one that was removed
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    pub task_unique_id: String,
+    pub before: Option<Task>,
+    pub after: Option<Task>,
+}
+
+// a single project being added, changed or removed, like `Change` is for tasks
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProjectChange {
+    pub name: String,
+    pub before: Option<Project>,
+    pub after: Option<Project>,
+}
+
+// the changes made by one command, which are undone and redone together
+#[derive(Clone, Debug, PartialEq)]
+pub struct Operation {
+    pub id: i64,
+    pub command: String,
+    pub time: DateTime<Utc>,
+    pub changes: Vec<Change>,
+    pub project_changes: Vec<ProjectChange>,
+    pub undone: bool,
+}
+
+// how a task is written into the journal.  every field is kept, unlike in the json output,
+// so that the task can be restored exactly
+#[derive(Debug, Deserialize, Serialize)]
+struct TaskImage {
+    unique_id: String,
+    description: String,
+    project_name: String,
+    start_time: DateTime<Utc>,
+    end_time: Option<DateTime<Utc>>,
+    running: bool,
+    tags: Vec<String>,
+    breaks: Vec<(DateTime<Utc>, Option<DateTime<Utc>>)>,
+    // missing from entries written before there was a trash
+    #[serde(default)]
+    deleted_at: Option<DateTime<Utc>>,
+    // and before there were notes
+    #[serde(default)]
+    notes: Vec<(DateTime<Utc>, String)>,
+}
+
+fn task_to_image(task: &Task) -> Result<String, Error> {
+    let image = TaskImage {
+        unique_id: task.unique_id.clone(),
+        description: task.description.clone(),
+        project_name: task.project_name.clone(),
+        start_time: task.start_time,
+        end_time: task.end_time,
+        running: task.status == TaskStatus::Running,
+        tags: task.tags.clone(),
+        breaks: task.breaks.iter().map(|b| (b.start_time, b.end_time)).collect(),
+        deleted_at: task.deleted_at,
+        notes: task.notes.iter().map(|n| (n.time, n.text.clone())).collect(),
+    };
+
+    Ok(serde_json::to_string(&image)?)
+}
+
+fn task_from_image(image: &str) -> Result<Task, Error> {
+    let image: TaskImage =
+        serde_json::from_str(image).map_err(|err| anyhow!("a journal entry couldn't be read: {}", err))?;
+
+    Ok(Task {
+        breaks: image
+            .breaks
+            .into_iter()
+            .map(|(start_time, end_time)| TaskBreak { start_time, end_time })
+            .collect(),
+        description: image.description,
+        end_time: image.end_time,
+        notes: image.notes.into_iter().map(|(time, text)| TaskNote { time, text }).collect(),
+        project_name: image.project_name,
+        start_time: image.start_time,
+        status: if image.running { TaskStatus::Running } else { TaskStatus::Ended },
+        tags: image.tags,
+        unique_id: image.unique_id,
+        deleted_at: image.deleted_at,
+    })
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct ProjectImage {
+    name: String,
+    description: String,
+    color: Option<String>,
+    archived: bool,
+    created_at: DateTime<Utc>,
+}
+
+fn project_to_image(project: &Project) -> Result<String, Error> {
+    let image = ProjectImage {
+        name: project.name.clone(),
+        description: project.description.clone(),
+        color: project.color.clone(),
+        archived: project.archived,
+        created_at: project.created_at,
+    };
+
+    Ok(serde_json::to_string(&image)?)
+}
+
+fn project_from_image(image: &str) -> Result<Project, Error> {
+    let image: ProjectImage =
+        serde_json::from_str(image).map_err(|err| anyhow!("a journal entry couldn't be read: {}", err))?;
+
+    Ok(Project {
+        name: image.name,
+        description: image.description,
+        color: image.color,
+        archived: image.archived,
+        created_at: image.created_at,
+    })
+}
+
+fn optional_image(task: &Option<Task>) -> Result<Option<String>, Error> {
+    task.as_ref().map(task_to_image).transpose()
+}
+
+fn optional_task(image: Option<String>) -> Result<Option<Task>, Error> {
+    image.as_deref().map(task_from_image).transpose()
+}
+
+// operations that were undone can't be redone once something else has changed, so they're dropped
+pub fn record_operation(
+    conn: &Connection,
+    command: &str,
+    time: DateTime<Utc>,
+    changes: &[Change],
+    project_changes: &[ProjectChange],
+) -> Result<i64, Error> {
+    conn.execute("DELETE FROM journal WHERE undone = 1;", [])?;
+    let id: i64 = conn.query_row("SELECT COALESCE(MAX(operation), 0) + 1 FROM journal", [], |r| r.get(0))?;
+
+    for change in changes {
+        conn.execute(
+            "INSERT INTO journal (operation, command, time, task_unique_id, before, after, undone) VALUES (?1, ?2, ?3, ?4, ?5, ?6, 0);",
+            params![
+                id,
+                command,
+                time.to_rfc3339(),
+                change.task_unique_id,
+                optional_image(&change.before)?,
+                optional_image(&change.after)?
+            ],
+        )?;
+    }
+    for change in project_changes {
+        conn.execute(
+            "INSERT INTO journal (operation, command, time, task_unique_id, before, after, undone, kind) VALUES (?1, ?2, ?3, ?4, ?5, ?6, 0, 'project');",
+            params![
+                id,
+                command,
+                time.to_rfc3339(),
+                change.name,
+                change.before.as_ref().map(project_to_image).transpose()?,
+                change.after.as_ref().map(project_to_image).transpose()?
+            ],
+        )?;
+    }
+
+    Ok(id)
+}
+
+// removes all but the newest `keep` operations, returning how many were removed
+pub fn prune_operations(conn: &Connection, keep: usize) -> Result<usize, Error> {
+    let newest_removed: Option<i64> = conn
+        .query_row(
+            "SELECT operation FROM (SELECT DISTINCT operation FROM journal ORDER BY operation DESC) LIMIT 1 OFFSET ?1",
+            params![keep as i64],
+            |r| r.get(0),
+        )
+        .optional()?;
+
+    let newest_removed = match newest_removed {
+        Some(operation) => operation,
+        None => return Ok(0),
+    };
+    let removed: i64 = conn.query_row(
+        "SELECT COUNT(DISTINCT operation) FROM journal WHERE operation <= ?1",
+        params![newest_removed],
+        |r| r.get(0),
+    )?;
+    conn.execute("DELETE FROM journal WHERE operation <= ?1;", params![newest_removed])?;
+
+    Ok(removed as usize)
+}
+
+// returns every operation in the journal, oldest first
+pub fn get_operations(conn: &Connection) -> Result<Vec<Operation>, Error> {
+    let mut stmt = conn.prepare(
+        "SELECT operation, command, time, task_unique_id, before, after, undone, kind FROM journal ORDER BY operation, id",
+    )?;
+    let mut rows = stmt.query([])?;
+
+    let mut operations: Vec<Operation> = Vec::new();
+    while let Some(row) = rows.next()? {
+        let id: i64 = row.get(0)?;
+        if operations.last().is_none_or(|operation| operation.id != id) {
+            let time: String = row.get(2)?;
+            operations.push(Operation {
+                id,
+                command: row.get(1)?,
+                time: DateTime::parse_from_rfc3339(&time)?.with_timezone(&Utc),
+                changes: Vec::new(),
+                project_changes: Vec::new(),
+                undone: row.get(6)?,
+            });
+        }
+        let operation = operations.last_mut().ok_or(anyhow!("a journal entry couldn't be read"))?;
+
+        let before: Option<String> = row.get(4)?;
+        let after: Option<String> = row.get(5)?;
+        match row.get::<_, String>(7)?.as_str() {
+            "project" => operation.project_changes.push(ProjectChange {
+                name: row.get(3)?,
+                before: before.as_deref().map(project_from_image).transpose()?,
+                after: after.as_deref().map(project_from_image).transpose()?,
+            }),
+            _ => operation.changes.push(Change {
+                task_unique_id: row.get(3)?,
+                before: optional_task(before)?,
+                after: optional_task(after)?,
+            }),
+        }
+    }
+
+    Ok(operations)
+}
+
+pub fn set_operation_undone(conn: &Connection, id: i64, undone: bool) -> Result<(), Error> {
+    conn.execute("UPDATE journal SET undone = ?1 WHERE operation = ?2;", params![undone, id])?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_task_images_round_trip() -> Result<(), Error> {
+        let mut task = Task::new("writing", "work", Utc.ymd(2021, 3, 18).and_hms_nano(9, 0, 0, 5));
+        task.tags = vec!["a".to_string(), "b".to_string()];
+        task.pause(Utc.ymd(2021, 3, 18).and_hms(10, 0, 0))?;
+        assert_eq!(task_from_image(&task_to_image(&task)?)?, task);
+
+        task.end_task(Utc.ymd(2021, 3, 18).and_hms(11, 0, 0));
+        assert_eq!(task_from_image(&task_to_image(&task)?)?, task);
+
+        task.deleted_at = Some(Utc.ymd(2021, 3, 18).and_hms(12, 0, 0));
+        task.notes = vec![TaskNote {
+            time: Utc.ymd(2021, 3, 18).and_hms(10, 30, 0),
+            text: "a note\nover two lines".to_string(),
+        }];
+        assert_eq!(task_from_image(&task_to_image(&task)?)?, task);
+        Ok(())
+    }
+
+    #[test]
+    fn test_operations_keep_task_and_project_changes() -> Result<(), Error> {
+        let conn = Connection::open_in_memory()?;
+        crate::data::set_up_sqlite(&conn)?;
+
+        let mut task = Task::new("writing", "work", Utc.ymd(2021, 3, 18).and_hms(9, 0, 0));
+        let before = task.clone();
+        task.project_name = "client".to_string();
+        let mut project = Project::new("client", Utc.ymd(2021, 3, 18).and_hms(9, 0, 0));
+        project.color = Some("red".to_string());
+        let changes = vec![Change {
+            task_unique_id: task.unique_id.clone(),
+            before: Some(before),
+            after: Some(task),
+        }];
+        let project_changes = vec![ProjectChange {
+            name: "client".to_string(),
+            before: None,
+            after: Some(project),
+        }];
+        let time = Utc.ymd(2021, 3, 18).and_hms(10, 0, 0);
+        record_operation(&conn, "project rename", time, &changes, &project_changes)?;
+        record_operation(&conn, "project add", time, &[], &project_changes)?;
+
+        let operations = get_operations(&conn)?;
+        assert_eq!(operations.len(), 2);
+        assert_eq!(operations[0].changes, changes);
+        assert_eq!(operations[0].project_changes, project_changes);
+        assert!(operations[1].changes.is_empty());
+        assert_eq!(operations[1].project_changes, project_changes);
+        Ok(())
+    }
+}
@@ -0,0 +1,480 @@
+extern crate serde_json;
+
+use crate::data::{Project, Task, TaskBreak, TaskNote, TaskStatus};
+use crate::journal::Operation;
+use crate::profiles::Profile;
+use crate::report::Report;
+use crate::search::{SearchHit, MATCH_END, MATCH_START};
+use crate::transfer::ImportSummary;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// the objects emitted by `--format json` / `--format jsonl`.  these are part of taskmao's
+// interface, so fields should only ever be added to them, never renamed or removed.
+// timestamps are ISO-8601 in utc and durations are whole seconds
+
+fn iso_timestamp(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BreakJson {
+    pub start_time: String,
+    pub end_time: Option<String>,
+}
+
+impl BreakJson {
+    pub fn new(task_break: &TaskBreak) -> BreakJson {
+        BreakJson {
+            start_time: iso_timestamp(&task_break.start_time),
+            end_time: task_break.end_time.as_ref().map(iso_timestamp),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct NoteJson {
+    pub time: String,
+    pub text: String,
+}
+
+impl NoteJson {
+    pub fn new(note: &TaskNote) -> NoteJson {
+        NoteJson {
+            time: iso_timestamp(&note.time),
+            text: note.text.clone(),
+        }
+    }
+}
+
+// `duration_seconds` excludes the time spent on breaks
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TaskJson {
+    pub id: String,
+    pub description: String,
+    pub project: String,
+    pub status: &'static str,
+    pub start_time: String,
+    pub end_time: Option<String>,
+    pub duration_seconds: i64,
+    pub tags: Vec<String>,
+    pub paused: bool,
+    pub breaks: Vec<BreakJson>,
+    pub notes: Vec<NoteJson>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<String>,
+}
+
+impl TaskJson {
+    pub fn new(task: &Task, now: DateTime<Utc>) -> TaskJson {
+        TaskJson {
+            id: task.unique_id.clone(),
+            description: task.description.clone(),
+            project: task.project_name.clone(),
+            status: match task.status {
+                TaskStatus::Running => "running",
+                TaskStatus::Ended => "ended",
+            },
+            start_time: iso_timestamp(&task.start_time),
+            end_time: task.end_time.as_ref().map(iso_timestamp),
+            duration_seconds: task.duration(now).num_seconds(),
+            tags: task.tags.clone(),
+            paused: task.is_paused(),
+            breaks: task.breaks.iter().map(BreakJson::new).collect(),
+            notes: task.notes.iter().map(NoteJson::new).collect(),
+            deleted_at: task.deleted_at.as_ref().map(iso_timestamp),
+        }
+    }
+}
+
+// a single task, tagged with what happened to it
+#[derive(Debug, Serialize)]
+pub struct TaskEventJson {
+    #[serde(rename = "type")]
+    pub event: &'static str,
+    pub task: TaskJson,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ended_task: Option<TaskJson>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TasksJson {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub tasks: Vec<TaskJson>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MessageJson<'a> {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub message: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorJson {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub conflicting_tasks: Vec<TaskJson>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub candidates: Vec<TaskJson>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RowErrorJson {
+    pub line: u64,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportJson {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub imported: Vec<String>,
+    pub duplicates: Vec<String>,
+    pub errors: Vec<RowErrorJson>,
+}
+
+impl ImportJson {
+    pub fn new(summary: &ImportSummary) -> ImportJson {
+        ImportJson {
+            kind: "import",
+            imported: summary.imported.clone(),
+            duplicates: summary.duplicates.clone(),
+            errors: summary
+                .errors
+                .iter()
+                .map(|e| RowErrorJson {
+                    line: e.line,
+                    message: e.message.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfigJson {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub settings: BTreeMap<String, String>,
+}
+
+impl ConfigJson {
+    pub fn new(settings: &[(&str, String)]) -> ConfigJson {
+        ConfigJson {
+            kind: "config",
+            settings: settings.iter().map(|(key, value)| (key.to_string(), value.clone())).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChangeJson {
+    pub task_id: String,
+    pub before: Option<TaskJson>,
+    pub after: Option<TaskJson>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProjectChangeJson {
+    pub name: String,
+    pub before: Option<ProjectJson>,
+    pub after: Option<ProjectJson>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OperationJson {
+    pub id: i64,
+    pub command: String,
+    pub time: String,
+    pub undone: bool,
+    pub changes: Vec<ChangeJson>,
+    pub project_changes: Vec<ProjectChangeJson>,
+}
+
+impl OperationJson {
+    pub fn new(operation: &Operation, now: DateTime<Utc>) -> OperationJson {
+        OperationJson {
+            id: operation.id,
+            command: operation.command.clone(),
+            time: iso_timestamp(&operation.time),
+            undone: operation.undone,
+            changes: operation
+                .changes
+                .iter()
+                .map(|change| ChangeJson {
+                    task_id: change.task_unique_id.clone(),
+                    before: change.before.as_ref().map(|t| TaskJson::new(t, now)),
+                    after: change.after.as_ref().map(|t| TaskJson::new(t, now)),
+                })
+                .collect(),
+            project_changes: operation
+                .project_changes
+                .iter()
+                .map(|change| ProjectChangeJson {
+                    name: change.name.clone(),
+                    before: change.before.as_ref().map(ProjectJson::new),
+                    after: change.after.as_ref().map(ProjectJson::new),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ProjectJson {
+    pub name: String,
+    pub description: String,
+    pub color: Option<String>,
+    pub archived: bool,
+    pub created_at: String,
+}
+
+impl ProjectJson {
+    pub fn new(project: &Project) -> ProjectJson {
+        ProjectJson {
+            name: project.name.clone(),
+            description: project.description.clone(),
+            color: project.color.clone(),
+            archived: project.archived,
+            created_at: iso_timestamp(&project.created_at),
+        }
+    }
+}
+
+// a project, tagged with what happened to it.  `tasks` lists the tasks that were moved by a
+// rename or merge
+#[derive(Debug, Serialize)]
+pub struct ProjectEventJson {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub project: ProjectJson,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tasks: Option<Vec<TaskJson>>,
+}
+
+// how much a project has been used, leaving out tasks in the trash
+#[derive(Debug, Serialize)]
+pub struct ProjectUsageJson {
+    pub tasks: usize,
+    pub duration_seconds: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProjectListEntryJson {
+    #[serde(flatten)]
+    pub project: ProjectJson,
+    #[serde(flatten)]
+    pub usage: ProjectUsageJson,
+}
+
+// projects used by tasks without having been added
+#[derive(Debug, Serialize)]
+pub struct UnknownProjectJson {
+    pub name: String,
+    #[serde(flatten)]
+    pub usage: ProjectUsageJson,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProjectsJson {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub projects: Vec<ProjectListEntryJson>,
+    pub unknown: Vec<UnknownProjectJson>,
+}
+
+// a task found by `search`.  the matches in `description` and `project` are wrapped in
+// <em> and </em>
+#[derive(Debug, Serialize)]
+pub struct SearchHitJson {
+    pub score: f64,
+    pub description: String,
+    pub project: String,
+    pub notes: Vec<String>,
+    pub task: TaskJson,
+}
+
+impl SearchHitJson {
+    pub fn new(hit: &SearchHit, now: DateTime<Utc>) -> SearchHitJson {
+        let marked = |text: &str| text.replace(MATCH_START, "<em>").replace(MATCH_END, "</em>");
+
+        SearchHitJson {
+            score: hit.score,
+            description: marked(&hit.description),
+            project: marked(&hit.project),
+            notes: hit.notes.iter().map(|note| marked(note)).collect(),
+            task: TaskJson::new(&hit.task, now),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchJson {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub query: String,
+    pub hits: Vec<SearchHitJson>,
+}
+
+// the output of `history`, `undo` and `redo`, told apart by `type`
+#[derive(Debug, Serialize)]
+pub struct OperationsJson {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub operations: Vec<OperationJson>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProfileJson {
+    pub name: String,
+    pub path: String,
+    pub active: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProfilesJson {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub db: String,
+    pub profiles: Vec<ProfileJson>,
+}
+
+impl ProfilesJson {
+    pub fn new(profiles: &[Profile], active_path: &Path) -> ProfilesJson {
+        ProfilesJson {
+            kind: "profiles",
+            db: active_path.display().to_string(),
+            profiles: profiles
+                .iter()
+                .map(|profile| ProfileJson {
+                    name: profile.name.clone(),
+                    path: profile.path.display().to_string(),
+                    active: profile.path == active_path,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProjectTotalJson {
+    pub project: String,
+    pub duration_seconds: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagTotalJson {
+    pub tag: String,
+    pub duration_seconds: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DayTotalJson {
+    pub date: String,
+    pub duration_seconds: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DescriptionTotalJson {
+    pub project: String,
+    pub description: String,
+    pub duration_seconds: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReportJson {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub from: String,
+    pub to: String,
+    pub total_seconds: i64,
+    pub by_project: Vec<ProjectTotalJson>,
+    pub by_day: Vec<DayTotalJson>,
+    pub by_description: Vec<DescriptionTotalJson>,
+    pub by_tag: Vec<TagTotalJson>,
+}
+
+impl ReportJson {
+    pub fn new(report: &Report) -> ReportJson {
+        ReportJson {
+            kind: "report",
+            from: iso_timestamp(&report.from),
+            to: iso_timestamp(&report.to),
+            total_seconds: report.total.num_seconds(),
+            by_project: report
+                .by_project
+                .iter()
+                .map(|(project, duration)| ProjectTotalJson {
+                    project: project.clone(),
+                    duration_seconds: duration.num_seconds(),
+                })
+                .collect(),
+            by_day: report
+                .by_day
+                .iter()
+                .map(|(day, duration)| DayTotalJson {
+                    date: day.format("%Y-%m-%d").to_string(),
+                    duration_seconds: duration.num_seconds(),
+                })
+                .collect(),
+            by_description: report
+                .by_description
+                .iter()
+                .map(|(project, description, duration)| DescriptionTotalJson {
+                    project: project.clone(),
+                    description: description.clone(),
+                    duration_seconds: duration.num_seconds(),
+                })
+                .collect(),
+            by_tag: report
+                .by_tag
+                .iter()
+                .map(|(tag, duration)| TagTotalJson {
+                    tag: tag.clone(),
+                    duration_seconds: duration.num_seconds(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_task_json_fields() -> Result<(), anyhow::Error> {
+        let mut task = Task::new("write tests", "taskmao", Utc.ymd(2021, 3, 18).and_hms(23, 36, 24));
+        task.end_task(Utc.ymd(2021, 3, 19).and_hms(1, 38, 59));
+        task.unique_id = "abc".to_string();
+        task.notes.push(TaskNote {
+            time: Utc.ymd(2021, 3, 19).and_hms(1, 0, 0),
+            text: "a note".to_string(),
+        });
+
+        let json = serde_json::to_string(&TaskJson::new(&task, Utc.ymd(2021, 3, 20).and_hms(0, 0, 0)))?;
+        assert_eq!(
+            json,
+            r#"{"id":"abc","description":"write tests","project":"taskmao","status":"ended","start_time":"2021-03-18T23:36:24Z","end_time":"2021-03-19T01:38:59Z","duration_seconds":7355,"tags":[],"paused":false,"breaks":[],"notes":[{"time":"2021-03-19T01:00:00Z","text":"a note"}]}"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_running_task_json_has_null_end_time() -> Result<(), anyhow::Error> {
+        let task = Task::new("write tests", "taskmao", Utc.ymd(2021, 3, 18).and_hms(23, 36, 24));
+        let json = serde_json::to_value(TaskJson::new(&task, Utc.ymd(2021, 3, 18).and_hms(23, 37, 24)))?;
+
+        assert_eq!(json["status"], "running");
+        assert!(json["end_time"].is_null());
+        assert_eq!(json["duration_seconds"], 60);
+        Ok(())
+    }
+}
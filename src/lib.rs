@@ -0,0 +1,26 @@
+extern crate chrono;
+#[macro_use]
+extern crate lazy_static;
+extern crate regex;
+extern crate rusqlite;
+pub mod config;
+pub mod data;
+pub mod display;
+pub mod error;
+pub mod ids;
+pub mod journal;
+pub mod json;
+mod migrations;
+pub mod profiles;
+pub mod report;
+pub mod search;
+pub mod store;
+pub mod tags;
+pub mod time;
+pub mod time_expr;
+pub mod tracker;
+pub mod transfer;
+
+pub use data::{Project, Task, TaskBreak, TaskStatus};
+pub use error::Error;
+pub use tracker::{BulkEdit, BulkPlan, TaskFilter, Tracker};
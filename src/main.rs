@@ -1,33 +1,60 @@
 extern crate chrono;
 extern crate clap;
-extern crate dirs;
-#[macro_use]
-extern crate lazy_static;
-extern crate regex;
-extern crate rusqlite;
-extern crate uuid;
-mod data;
-mod display;
-mod time;
+mod prompt;
 
 use anyhow::{anyhow};
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
 use clap::{arg, Arg, ArgAction, ArgMatches, Command};
-use data::*;
-use rusqlite::{Connection, Result};
-use std::path::PathBuf;
+use regex::Regex;
+use std::io::IsTerminal;
 use std::{fs, io};
-use time::{convert_to_utc_timestr, get_current_utc_string};
-use uuid::Uuid;
+use taskmao::config::{self, Config, ProjectCheck};
+use taskmao::display::{self, ListOptions, Output, OutputFormat};
+use taskmao::ids::ShortIds;
+use taskmao::profiles::{db_path, ensure_parent_dir, DataDirs};
+use taskmao::report;
+use taskmao::tags;
+use taskmao::time::{parse_date, AmbiguousTimePolicy, Clock, SystemClock};
+use taskmao::time_expr::parse_duration;
+use taskmao::transfer;
+use taskmao::{BulkEdit, Project, TaskFilter, Task, Tracker};
 
+const AMBIGUOUS_TIME_TEXT: &str = "ambiguous-time";
+const BULK_DELETE_TEXT: &str = "bulk-delete";
+const BULK_EDIT_TEXT: &str = "bulk-edit";
 const CANCEL_TEXT: &str = "cancel";
+const CONFIG_TEXT: &str = "config";
+const DB_TEXT: &str = "db";
 const DELETE_TEXT: &str = "delete";
 const DESCRIPTION_TEXT: &str = "DESC";
+const DRY_RUN_TEXT: &str = "dry-run";
+const EDIT_TEXT: &str = "edit";
 const END_TEXT: &str = "end";
+const EXPORT_TEXT: &str = "export";
 const FIND_TEXT: &str = "find";
+const FORMAT_TEXT: &str = "format";
+const HISTORY_TEXT: &str = "history";
+const IMPORT_TEXT: &str = "import";
 const INFO_TEXT: &str = "info";
 const LIST_TEXT: &str = "list";
+const LOG_TEXT: &str = "log";
+const NOTE_TEXT: &str = "note";
+const PAUSE_TEXT: &str = "pause";
+const PROFILE_TEXT: &str = "profile";
+const PROFILES_TEXT: &str = "profiles";
 const START_TIME_TEXT: &str = "START_TIME";
 const PROJECT_TEXT: &str = "project";
+const PROJECT_COMMAND_TEXT: &str = "project";
+const REDO_TEXT: &str = "redo";
+const REPORT_TEXT: &str = "report";
+const RESTART_TEXT: &str = "restart";
+const RESUME_TEXT: &str = "resume";
+const SEARCH_TEXT: &str = "search";
+const TAG_TEXT: &str = "tag";
+const TRASH_TEXT: &str = "trash";
+const UNDO_TEXT: &str = "undo";
+const VERBOSE_TEXT: &str = "verbose";
+const YES_TEXT: &str = "yes";
 
 fn parse_args() -> ArgMatches {
     let cli = Command::new("taskmao")
@@ -43,25 +70,178 @@ fn parse_args() -> ArgMatches {
             Arg::new(PROJECT_TEXT)
                 .short('p')
                 .long("project")
-                .default_value("default")
-                .help("sets the project of a task")
+                .help("sets the project of a task;  defaults to the default_project setting")
                 .action(ArgAction::Set)
         )
+        .arg(
+            Arg::new(FORMAT_TEXT)
+                .long("format")
+                .global(true)
+                .value_parser(["text", "json", "jsonl"])
+                .default_value("text")
+                .help("sets the output format;  json and jsonl output is documented in the readme")
+                .action(ArgAction::Set)
+        )
+        .arg(
+            Arg::new(AMBIGUOUS_TIME_TEXT)
+                .long("ambiguous-time")
+                .global(true)
+                .value_parser(["earliest", "latest", "error"])
+                .default_value("earliest")
+                .help("sets which time to use when a local time happens twice because the clocks went back")
+                .action(ArgAction::Set)
+        )
+        .arg(
+            Arg::new(DB_TEXT)
+                .long("db")
+                .global(true)
+                .value_name("PATH")
+                .help("sets the database file to keep tasks in;  defaults to TASKMAO_DB or the default profile")
+                .action(ArgAction::Set)
+        )
+        .arg(
+            Arg::new(PROFILE_TEXT)
+                .long("profile")
+                .global(true)
+                .value_name("NAME")
+                .conflicts_with(DB_TEXT)
+                .help("keeps tasks in the database of the named profile, such as 'work' or 'personal'")
+                .action(ArgAction::Set)
+        )
+        .arg(
+            Arg::new(YES_TEXT)
+                .short('y')
+                .long("yes")
+                .global(true)
+                .help("skips asking for confirmation before deleting or cancelling tasks;  needed when not running in a terminal")
+                .action(ArgAction::SetTrue)
+        )
         .arg(
             Arg::new(START_TIME_TEXT)
                 .short('t')
                 .long("time")
-                .help("manually set a start time for new task other than now")
+                .allow_hyphen_values(true)
+                .help("manually set a start time for new task other than now, such as '17:30', '15m ago' or 'yesterday 5pm'")
                 .action(ArgAction::Set)
         )
+        .arg(
+            Arg::new(TAG_TEXT)
+                .long("tag")
+                .help("adds a tag to a new task;  can be repeated, and `+tag` words in the description are also tags")
+                .action(ArgAction::Append)
+        )
+        .subcommand(
+            filter_args(Command::new(BULK_DELETE_TEXT))
+                .about("moves every task matching the filter to the trash, after showing which tasks match")
+                .arg(
+                    Arg::new(DRY_RUN_TEXT)
+                    .long("dry-run")
+                    .help("only shows the tasks that would be deleted")
+                    .action(ArgAction::SetTrue)
+                )
+        )
+        .subcommand(
+            filter_args(Command::new(BULK_EDIT_TEXT))
+                .about("changes the project or tags of every task matching the filter, after showing which tasks match")
+                .arg(
+                    Arg::new("SET_PROJECT")
+                    .long("set-project")
+                    .value_name("PROJECT")
+                    .help("moves the tasks to the given project")
+                )
+                .arg(
+                    Arg::new("ADD_TAG")
+                    .long("add-tag")
+                    .value_name("TAG")
+                    .action(ArgAction::Append)
+                    .help("adds a tag to the tasks;  can be repeated")
+                )
+                .arg(
+                    Arg::new("REMOVE_TAG")
+                    .long("remove-tag")
+                    .value_name("TAG")
+                    .action(ArgAction::Append)
+                    .help("removes a tag from the tasks;  can be repeated")
+                )
+                .arg(
+                    Arg::new(DRY_RUN_TEXT)
+                    .long("dry-run")
+                    .help("only shows the tasks as they would be after the change")
+                    .action(ArgAction::SetTrue)
+                )
+        )
         .subcommand(
             Command::new(CANCEL_TEXT)
-                .about("cancels the currently running task")
+                .about("cancels the currently running task, moving it to the trash")
+        )
+        .subcommand(
+            Command::new(CONFIG_TEXT)
+                .about("shows or changes the settings in taskmao's config file")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("get")
+                        .about("shows the value of a setting")
+                        .arg(arg!(<KEY> "sets the setting to show"))
+                )
+                .subcommand(
+                    Command::new("list")
+                        .about("lists every setting and its value")
+                )
+                .subcommand(
+                    Command::new("set")
+                        .about("changes a setting in the config file")
+                        .arg(arg!(<KEY> "sets the setting to change"))
+                        .arg(arg!(<VALUE> "sets the new value of the setting"))
+                )
         )
         .subcommand(
             Command::new(DELETE_TEXT)
-                .about("deletes a task by its unique id")
-                .arg(arg!(<TASK_ID> "sets the id of the task that is to be deleted"))
+                .about("deletes a task by its unique id, moving it to the trash")
+                .arg(arg!(<TASK_ID> "sets the id, or the start of the id, of the task that is to be deleted"))
+                .arg_required_else_help(true)
+        )
+        .subcommand(
+            Command::new(EDIT_TEXT)
+                .about("edits a task by its unique id;  prompts for each field if no flags are given")
+                .arg(arg!(<TASK_ID> "sets the id, or the start of the id, of the task that is to be edited"))
+                .arg(
+                    Arg::new("DESCRIPTION")
+                    .short('d')
+                    .long("description")
+                    .help("sets the new description of the task")
+                )
+                .arg(
+                    Arg::new(PROJECT_TEXT)
+                    .short('p')
+                    .long("project")
+                    .help("sets the new project of the task")
+                )
+                .arg(
+                    Arg::new("START_TIME")
+                    .short('s')
+                    .long("start")
+                    .allow_hyphen_values(true)
+                    .help("sets the new start time of the task, such as '17:30', '2021-03-18 5:30pm' or '2h ago'")
+                )
+                .arg(
+                    Arg::new("END_TIME")
+                    .short('e')
+                    .long("end")
+                    .allow_hyphen_values(true)
+                    .help("sets the new end time of the task, such as '17:30', '2021-03-18 5:30pm' or '2h ago'")
+                )
+                .arg(
+                    Arg::new(TAG_TEXT)
+                    .long("tag")
+                    .action(ArgAction::Append)
+                    .help("adds a tag to the task;  can be repeated")
+                )
+                .arg(
+                    Arg::new("UNTAG")
+                    .long("untag")
+                    .action(ArgAction::Append)
+                    .help("removes a tag from the task;  can be repeated")
+                )
                 .arg_required_else_help(true)
         )
         .subcommand(
@@ -71,247 +251,904 @@ fn parse_args() -> ArgMatches {
                     Arg::new("END_TIME")
                     .short('t')
                     .long("time")
+                    .allow_hyphen_values(true)
                     .help("manually set the end time of the current task")
                 )
         )
+        .subcommand(
+            Command::new(EXPORT_TEXT)
+                .about("exports tasks, optionally limited to a range of days")
+                .arg(
+                    Arg::new("CSV")
+                    .long("csv")
+                    .num_args(0..=1)
+                    .value_name("FILE")
+                    .required(true)
+                    .help("exports as csv to the given file, or to stdout if no file is given")
+                )
+                .arg(
+                    Arg::new("FROM")
+                    .long("from")
+                    .help("sets the first day to export, as 'YYYY-MM-DD'")
+                )
+                .arg(
+                    Arg::new("TO")
+                    .long("to")
+                    .help("sets the last day to export, as 'YYYY-MM-DD'")
+                )
+        )
         .subcommand(
             Command::new(FIND_TEXT)
                 .about("finds a previously executed task by id")
-                .arg(arg!(<TASK_ID> "sets the id of the task that is to be found"))
+                .arg(arg!(<TASK_ID> "sets the start of the id of the tasks that are to be found"))
+                .arg(
+                    Arg::new(TAG_TEXT)
+                    .long("tag")
+                    .action(ArgAction::Append)
+                    .help("only finds tasks with the given tag;  can be repeated")
+                )
                 .arg_required_else_help(true)
         )
+        .subcommand(
+            Command::new(HISTORY_TEXT)
+                .about("lists the changes made to tasks, newest first, which can be undone and redone")
+                .arg(
+                    Arg::new("LIMIT")
+                    .short('n')
+                    .long("limit")
+                    .value_parser(clap::value_parser!(usize))
+                    .default_value("10")
+                    .help("sets the number of changes to list")
+                )
+                .arg(
+                    Arg::new("PRUNE")
+                    .long("prune")
+                    .value_name("KEEP")
+                    .value_parser(clap::value_parser!(usize))
+                    .help("removes all but the newest KEEP changes, which can then no longer be undone")
+                )
+        )
+        .subcommand(
+            Command::new(IMPORT_TEXT)
+                .about("imports completed tasks, skipping any whose id already exists")
+                .arg(
+                    Arg::new("CSV")
+                    .long("csv")
+                    .value_name("FILE")
+                    .required(true)
+                    .help("imports from the given csv file, or from stdin if the file is '-'")
+                )
+        )
         .subcommand(
             Command::new(INFO_TEXT)
                 .about("returns info on the currently running task")
         )
         .subcommand(
-            Command::new(LIST_TEXT)
-                .about("lists tasks completed / worked on today")
+            filter_args(Command::new(LIST_TEXT))
+                .about("lists tasks completed / worked on today, or on the days given with --from and --to")
+                .arg(
+                    Arg::new(VERBOSE_TEXT)
+                    .short('v')
+                    .long("verbose")
+                    .action(ArgAction::SetTrue)
+                    .help("shows the notes of each task as well")
+                )
+        )
+        .subcommand(
+            Command::new(LOG_TEXT)
+                .about("logs a completed task with an explicit start and end time")
+                .arg(arg!(<DESC> "sets the description of the task to log"))
+                .arg(
+                    Arg::new("START_TIME")
+                    .short('s')
+                    .long("start")
+                    .allow_hyphen_values(true)
+                    .required(true)
+                    .help("sets the start time of the task, such as '17:30', '2021-03-18 5:30pm' or '2h ago'")
+                )
+                .arg(
+                    Arg::new("END_TIME")
+                    .short('e')
+                    .long("end")
+                    .allow_hyphen_values(true)
+                    .required(true)
+                    .help("sets the end time of the task, such as '17:30', '2021-03-18 5:30pm' or '2h ago'")
+                )
+                .arg(
+                    Arg::new(PROJECT_TEXT)
+                    .short('p')
+                    .long("project")
+                    .help("sets the project of the task;  defaults to the default_project setting")
+                )
+                .arg(
+                    Arg::new(TAG_TEXT)
+                    .long("tag")
+                    .action(ArgAction::Append)
+                    .help("adds a tag to the task;  can be repeated, and `+tag` words in the description are also tags")
+                )
+                .arg_required_else_help(true)
+        )
+        .subcommand(
+            Command::new(NOTE_TEXT)
+                .about("adds a timestamped note to a task;  opens $EDITOR for the note if no text is given")
+                .arg(arg!(<TASK_ID> "sets the id, or the start of the id, of the task to add the note to;  'current' for the running task"))
+                .arg(
+                    Arg::new("TEXT")
+                    .num_args(1..)
+                    .trailing_var_arg(true)
+                    .help("sets the text of the note")
+                )
+                .arg_required_else_help(true)
+        )
+        .subcommand(
+            Command::new(PAUSE_TEXT)
+                .about("pauses the currently running task;  paused time isn't counted towards the task")
+                .arg(
+                    Arg::new("PAUSE_TIME")
+                    .short('t')
+                    .long("time")
+                    .allow_hyphen_values(true)
+                    .help("manually set the time the current task was paused")
+                )
+        )
+        .subcommand(
+            Command::new(PROFILES_TEXT)
+                .about("lists the profiles that have been used and the database each keeps its tasks in")
+        )
+        .subcommand(
+            Command::new(PROJECT_COMMAND_TEXT)
+                .about("adds, lists, renames, archives or merges projects")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("add")
+                        .about("adds a project that tasks can be started in")
+                        .arg(arg!(<NAME> "sets the name of the project"))
+                        .arg(arg!(-d --description <DESCRIPTION> "sets a description of the project"))
+                        .arg(arg!(--color <COLOR> "sets the color the project is shown in, such as red or cyan"))
+                )
+                .subcommand(
+                    Command::new("archive")
+                        .about("archives a project, so that starting tasks in it warns or errors")
+                        .arg(arg!(<NAME> "sets the name of the project to archive"))
+                )
+                .subcommand(
+                    Command::new("list")
+                        .about("lists the projects, with how many tasks and how much time each has")
+                        .arg(arg!(-a --all "includes archived projects"))
+                )
+                .subcommand(
+                    Command::new("merge")
+                        .about("moves every task of a project into another project, removing the first")
+                        .arg(arg!(<NAME> "sets the project to merge away"))
+                        .arg(arg!(<INTO> "sets the project that takes its tasks"))
+                )
+                .subcommand(
+                    Command::new("rename")
+                        .about("renames a project, along with every task in it")
+                        .arg(arg!(<NAME> "sets the current name of the project"))
+                        .arg(arg!(<NEW_NAME> "sets the new name of the project"))
+                )
+                .subcommand(
+                    Command::new("unarchive")
+                        .about("takes a project back out of the archive")
+                        .arg(arg!(<NAME> "sets the name of the project to unarchive"))
+                )
+        )
+        .subcommand(
+            Command::new(REDO_TEXT)
+                .about("makes the last undone changes again")
+                .arg(arg!([COUNT] "sets the number of changes to redo").value_parser(clap::value_parser!(usize)).default_value("1"))
+        )
+        .subcommand(
+            Command::new(REPORT_TEXT)
+                .about("reports the time spent per project, day and description;  defaults to today")
+                .arg(
+                    Arg::new("FROM")
+                    .long("from")
+                    .help("sets the first day of the report, as 'YYYY-MM-DD'")
+                )
+                .arg(
+                    Arg::new("TO")
+                    .long("to")
+                    .requires("FROM")
+                    .help("sets the last day of the report, as 'YYYY-MM-DD';  defaults to today")
+                )
+                .arg(
+                    Arg::new("WEEK")
+                    .long("week")
+                    .action(ArgAction::SetTrue)
+                    .conflicts_with_all(["FROM", "TO", "MONTH"])
+                    .help("reports on the current week")
+                )
+                .arg(
+                    Arg::new("MONTH")
+                    .long("month")
+                    .action(ArgAction::SetTrue)
+                    .conflicts_with_all(["FROM", "TO"])
+                    .help("reports on the current month")
+                )
+                .arg(
+                    Arg::new(PROJECT_TEXT)
+                    .short('p')
+                    .long("project")
+                    .help("only reports on tasks from the given project")
+                )
+                .arg(
+                    Arg::new(TAG_TEXT)
+                    .long("tag")
+                    .action(ArgAction::Append)
+                    .help("only reports on tasks with the given tag;  can be repeated")
+                )
+        )
+        .subcommand(
+            Command::new(RESTART_TEXT)
+                .visible_alias("continue")
+                .about("starts a new task with the description, project and tags of a previous task;  defaults to the last ended task")
+                .arg(arg!([TASK_ID] "sets the id, or the start of the id, of the task to restart"))
+                .arg(
+                    Arg::new(START_TIME_TEXT)
+                    .short('t')
+                    .long("time")
+                    .allow_hyphen_values(true)
+                    .help("manually set a start time for the new task other than now")
+                )
+        )
+        .subcommand(
+            Command::new(RESUME_TEXT)
+                .about("resumes the currently paused task")
+                .arg(
+                    Arg::new("RESUME_TIME")
+                    .short('t')
+                    .long("time")
+                    .allow_hyphen_values(true)
+                    .help("manually set the time the current task was resumed")
+                )
+        )
+        .subcommand(
+            filter_args(Command::new(SEARCH_TEXT))
+                .about("searches task descriptions, projects and notes, best matches first")
+                .arg(arg!(<QUERY> "sets the words to search for;  \"quoted phrases\", prefix* words, OR, NOT, (brackets) and project:word are understood"))
+                .arg(
+                    Arg::new("LIMIT")
+                    .short('n')
+                    .long("limit")
+                    .value_parser(clap::value_parser!(usize))
+                    .default_value("20")
+                    .help("sets the number of matches to list")
+                )
+        )
+        .subcommand(
+            Command::new(TRASH_TEXT)
+                .about("lists, restores or permanently removes deleted and cancelled tasks")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("empty")
+                        .about("permanently removes the tasks in the trash")
+                        .arg(
+                            Arg::new("OLDER_THAN")
+                            .long("older-than")
+                            .help("only removes tasks deleted longer ago than this, such as 30d or 2w")
+                        )
+                )
+                .subcommand(
+                    Command::new("list")
+                        .about("lists the tasks in the trash")
+                )
+                .subcommand(
+                    Command::new("restore")
+                        .about("takes a task back out of the trash")
+                        .arg(arg!(<TASK_ID> "sets the id, or the start of the id, of the task that is to be restored"))
+                )
+        )
+        .subcommand(
+            Command::new(UNDO_TEXT)
+                .about("undoes the last changes made to tasks, such as a start, delete or edit")
+                .arg(arg!([COUNT] "sets the number of changes to undo").value_parser(clap::value_parser!(usize)).default_value("1"))
         );
 
 
-    return cli.get_matches()
+    cli.get_matches()
 }
 
-fn run(args: ArgMatches) -> Result<(), anyhow::Error> {
-    let mut path: PathBuf = match dirs::home_dir() {
-        Some(path) => path,
-        None => PathBuf::from(""),
+// the flags shared by the commands that work on the tasks matching a filter
+fn filter_args(command: Command) -> Command {
+    command
+        .arg(
+            Arg::new(PROJECT_TEXT)
+            .short('p')
+            .long("project")
+            .help("only matches tasks from the given project")
+        )
+        .arg(
+            Arg::new(TAG_TEXT)
+            .long("tag")
+            .action(ArgAction::Append)
+            .help("only matches tasks with the given tag;  can be repeated")
+        )
+        .arg(
+            Arg::new("FROM")
+            .long("from")
+            .help("only matches tasks worked on from this day on, as 'YYYY-MM-DD'")
+        )
+        .arg(
+            Arg::new("TO")
+            .long("to")
+            .help("only matches tasks worked on up to and including this day, as 'YYYY-MM-DD';  defaults to today when --from is given")
+        )
+        .arg(
+            Arg::new("MATCH")
+            .long("match")
+            .value_name("REGEX")
+            .help("only matches tasks whose description the regular expression matches, such as 'oauth|login' or '(?i)review'")
+        )
+        .arg(
+            Arg::new("ID")
+            .long("id")
+            .value_name("PREFIX")
+            .help("only matches tasks whose id starts with the prefix")
+        )
+}
+
+fn get_filter(matches: &ArgMatches) -> Result<TaskFilter, anyhow::Error> {
+    let description = match matches.get_one::<String>("MATCH") {
+        Some(pattern) => Some(
+            Regex::new(pattern).map_err(|err| anyhow!("'{}' isn't a valid regular expression: {}", pattern, err))?,
+        ),
+        None => None,
     };
 
-    path.push(".config");
-    path.push("taskmao");
+    Ok(TaskFilter {
+        project: matches.get_one::<String>(PROJECT_TEXT).cloned(),
+        tags: get_tags(matches, TAG_TEXT)?,
+        from: matches.get_one::<String>("FROM").map(|from| parse_date(from)).transpose()?,
+        to: matches.get_one::<String>("TO").map(|to| parse_date(to)).transpose()?,
+        description,
+        id_prefix: matches.get_one::<String>("ID").cloned(),
+    })
+}
 
-    // create regardless in order to ensure that the dir exists
-    fs::create_dir_all(path.as_path())?;
+// the filter for a bulk command, which has to narrow the tasks down somehow
+fn get_bulk_filter(matches: &ArgMatches, command: &str) -> Result<TaskFilter, anyhow::Error> {
+    let filter = get_filter(matches)?;
+    if filter.is_empty() {
+        return Err(anyhow!(
+            "'{}' needs a filter, such as --project, --tag, --from, --to, --match or --id, so that it doesn't change every task",
+            command
+        ));
+    }
 
-    let conn = match Connection::open(path.join("base.sql3")) {
-        Ok(conn) => conn,
-        Err(e) => panic!(
-            "The sqlite connection couldn't be opened with the following error: {}",
-            e
-        ),
+    Ok(filter)
+}
+
+fn get_tags(matches: &ArgMatches, id: &str) -> Result<Vec<String>, anyhow::Error> {
+    let tags: Vec<String> = match matches.get_many::<String>(id) {
+        Some(tags) => tags.cloned().collect(),
+        None => Vec::new(),
     };
 
-    match set_up_sqlite(&conn) {
-        Ok(_val) => {}
-        Err(err) => {
-            display::custom_message(
-                &format!(
-                    "The data store for tasks could not be set up with the following error: {}",
-                    err
-                ),
-                &mut io::stdout(),
-            )?;
+    tags::normalize_tags(&tags)
+}
+
+// splits `+tag` words out of the description and merges them with any `--tag` flags
+fn get_description_and_tags(desc: &str, matches: &ArgMatches) -> Result<(String, Vec<String>), anyhow::Error> {
+    let (description, mut tags) = tags::parse_description_tags(desc);
+    if description.is_empty() {
+        return Err(anyhow!("a task's description can't be made up of only tags"));
+    }
+
+    tags.extend(get_tags(matches, TAG_TEXT)?);
+    Ok((description, tags::normalize_tags(&tags)?))
+}
+
+// parses an optional time expression, defaulting to now
+fn get_time(tracker: &Tracker<impl TimeZone>, matches: &ArgMatches, id: &str) -> Result<DateTime<Utc>, anyhow::Error> {
+    match matches.get_one::<String>(id) {
+        Some(time) => Ok(tracker.parse_time(time)?),
+        None => Ok(tracker.now()),
+    }
+}
+
+// warns when a task is given a project that hasn't been added or is archived, or errors if the
+// project_check setting is 'error'.  the default project doesn't need to have been added
+fn check_project(tracker: &Tracker<impl TimeZone>, name: &str, config: &Config, output: &Output) -> Result<(), anyhow::Error> {
+    match tracker.check_project(name) {
+        Err(taskmao::Error::ProjectNotFound(_)) if name == config.default_project => Ok(()),
+        Err(err @ (taskmao::Error::ProjectNotFound(_) | taskmao::Error::ProjectArchived(_))) => match config.project_check {
+            ProjectCheck::Warn => display::warning(&err, output, &mut io::stderr()),
+            ProjectCheck::Error => Err(err.into()),
+        },
+        other => Ok(other?),
+    }
+}
+
+// the config command reads the config file leniently, so that it can fix a file that every
+// other command refuses to run with
+fn run_config(args: &ArgMatches, output: &mut Output) -> Result<(), anyhow::Error> {
+    let path = config::config_path()
+        .ok_or(anyhow!("a directory for the config file couldn't be found"))?;
+    let (config, errors) = Config::load_lenient(&path)?;
+    output.style = config.style(io::stdout().is_terminal());
+    for err in &errors {
+        display::warning(err, output, &mut io::stderr())?;
+    }
+
+    match args.subcommand() {
+        Some(("get", get_matches)) => {
+            let key = get_matches.get_one::<String>("KEY").map_or("", String::as_str);
+            display::config_get(key, &config.get(key)?, output, &mut io::stdout())?;
         }
-    };
+        Some(("set", set_matches)) => {
+            let key = set_matches.get_one::<String>("KEY").map_or("", String::as_str);
+            let value = set_matches.get_one::<String>("VALUE").map_or("", String::as_str);
+            let value = config::set_value(&path, key, value)?;
+            display::config_set(key, &value, output, &mut io::stdout())?;
+        }
+        _ => display::config_list(&config.entries(), output, &mut io::stdout())?,
+    }
+
+    Ok(())
+}
+
+fn run(args: &ArgMatches, config: &Config, output: &Output, clock: Box<dyn Clock>, tz: impl TimeZone) -> Result<(), anyhow::Error> {
+    let data_dirs = DataDirs::from_env();
+    let path = db_path(
+        args.get_one::<String>(DB_TEXT).map(String::as_str),
+        args.get_one::<String>(PROFILE_TEXT).map(String::as_str),
+        std::env::var("TASKMAO_DB").ok().as_deref(),
+        &data_dirs,
+    )?;
+
+    if let Some((PROFILES_TEXT, _)) = args.subcommand() {
+        let profiles = data_dirs.profiles()?;
+        display::profiles(&profiles, &path, output, &mut io::stdout())?;
+        return Ok(());
+    }
+
+    // create regardless in order to ensure that the dir exists
+    ensure_parent_dir(&path)?;
+
+    let mut tracker = Tracker::open(&path, clock, tz)?;
+    if let Some(policy) = args.get_one::<String>(AMBIGUOUS_TIME_TEXT) {
+        tracker.set_ambiguous_time_policy(policy.parse::<AmbiguousTimePolicy>()?);
+    }
+    tracker.set_history_limit(match config.history_limit {
+        0 => None,
+        limit => Some(limit),
+    });
+    let (clock, tz) = (tracker.clock(), tracker.timezone());
 
     match args.subcommand() {
-        Some((CANCEL_TEXT, _)) => match get_most_recent_task(&conn) {
-            Ok(current_task) => {
-                match delete_task_by_id(&conn, &current_task.unique_id) {
-                    Ok(()) => {
-                        display::custom_message("cancelled current task.  it will not persist within the task data store", &mut io::stdout())?;
-                        return Ok(());
-                    },
-                    Err(error) => {
-                        display::custom_message(&(format!("your task was not able to be cancelled with the following error message: {}", error.to_string())), &mut io::stdout())?;
-                        return Ok(());
-                    }
+        Some((BULK_DELETE_TEXT, sub_matches)) => {
+            let filter = get_bulk_filter(sub_matches, BULK_DELETE_TEXT)?;
+            let plan = tracker.plan_bulk_delete(&filter)?;
+            if plan.is_empty() {
+                return display::custom_message("there are no tasks matching the filter", output, &mut io::stdout());
+            }
+
+            let tasks = plan.tasks();
+            let short_ids = tracker.short_ids()?;
+            if sub_matches.get_flag(DRY_RUN_TEXT) {
+                return display::bulk_preview(BULK_DELETE_TEXT, &tasks, &short_ids, clock, tz, output, &mut io::stdout());
+            }
+            if prompt::needs_confirmation(BULK_DELETE_TEXT, args.get_flag(YES_TEXT))? {
+                display::bulk_preview(BULK_DELETE_TEXT, &tasks, &short_ids, clock, tz, output, &mut io::stderr())?;
+                if !prompt::confirm(&format!("delete these {} tasks?", tasks.len()))? {
+                    return display::custom_message("the tasks were left as they were", output, &mut io::stdout());
                 }
             }
-            Err(_err) => {
-                display::custom_message(
-                    "you currently have no task running",
-                    &mut io::stdout(),
-                )?;
+
+            let deleted_tasks = tracker.apply_bulk(&plan)?;
+            display::bulk_delete(&deleted_tasks, clock, output, &mut io::stdout())?;
+        }
+        Some((BULK_EDIT_TEXT, sub_matches)) => {
+            let filter = get_bulk_filter(sub_matches, BULK_EDIT_TEXT)?;
+            let edit = BulkEdit {
+                project: sub_matches.get_one::<String>("SET_PROJECT").cloned(),
+                add_tags: get_tags(sub_matches, "ADD_TAG")?,
+                remove_tags: get_tags(sub_matches, "REMOVE_TAG")?,
+            };
+            if edit.is_empty() {
+                return Err(anyhow!("'bulk-edit' needs a change to make, such as --set-project, --add-tag or --remove-tag"));
+            }
+            if let Some(project) = &edit.project {
+                check_project(&tracker, project, config, output)?;
+            }
+
+            let plan = tracker.plan_bulk_edit(&filter, &edit)?;
+            if plan.is_empty() {
+                return display::custom_message("there are no tasks matching the filter", output, &mut io::stdout());
+            }
+
+            let tasks = plan.tasks();
+            let short_ids = tracker.short_ids()?;
+            if sub_matches.get_flag(DRY_RUN_TEXT) {
+                return display::bulk_preview(BULK_EDIT_TEXT, &tasks, &short_ids, clock, tz, output, &mut io::stdout());
+            }
+            if prompt::needs_confirmation(BULK_EDIT_TEXT, args.get_flag(YES_TEXT))? {
+                display::bulk_preview(BULK_EDIT_TEXT, &tasks, &short_ids, clock, tz, output, &mut io::stderr())?;
+                if !prompt::confirm(&format!("update these {} tasks?", tasks.len()))? {
+                    return display::custom_message("the tasks were left as they were", output, &mut io::stdout());
+                }
             }
+
+            let edited_tasks = tracker.apply_bulk(&plan)?;
+            display::bulk_edit(&edited_tasks, clock, output, &mut io::stdout())?;
+        }
+        Some((CANCEL_TEXT, _)) => {
+            let task = tracker.current()?;
+            if prompt::needs_confirmation(CANCEL_TEXT, args.get_flag(YES_TEXT))? {
+                display::task_summary(&task, tracker.short_ids()?.get(&task.unique_id), clock, tz, output, &mut io::stderr())?;
+                if !prompt::confirm("cancel this task?")? {
+                    return display::custom_message("the task was left running", output, &mut io::stdout());
+                }
+            }
+
+            let cancelled_task = tracker.cancel()?;
+            display::task_cancel(&cancelled_task, clock, output, &mut io::stdout())?;
         }
         Some((DELETE_TEXT, sub_matches)) => {
             let task_id = sub_matches.get_one::<String>("TASK_ID")
                 .ok_or(anyhow!("A task id was not entered for the delete command.  Enter a valid task id and try again."))?;
 
-            let task_to_delete = match find_task_by_id(&conn, task_id) {
-                Ok(task) => task,
-                Err(error) if error.to_string().contains("returned no rows") => {
-                    display::custom_message(
-                        "there was no tasks found with your id. check your id and try again",
-                        &mut io::stdout(),
-                    )?;
-                    return Ok(());
-                },
-                Err(error) => {
-                    display::custom_message(&(format!("encountered the following sqlite error while trying to delete your task: {}", error.to_string())), &mut io::stdout())?;
-                    return Ok(());
+            // a running task can't be deleted, so there's no point asking about it
+            let task = tracker.find(task_id)?;
+            if task.is_running() {
+                return Err(taskmao::Error::TaskRunning(task.unique_id).into());
+            }
+            if prompt::needs_confirmation(DELETE_TEXT, args.get_flag(YES_TEXT))? {
+                display::task_summary(&task, tracker.short_ids()?.get(&task.unique_id), clock, tz, output, &mut io::stderr())?;
+                if !prompt::confirm("delete this task?")? {
+                    return display::custom_message("the task was left as it was", output, &mut io::stdout());
                 }
-            };
+            }
 
-            // handle early return when the task to delete is already running
-            if task_to_delete.running == "true" {
-                display::custom_message(
-                        "this task is currently running.  if you want to delete, end the task and try again",
-                        &mut io::stdout(),
-                    )?;
+            let deleted_task = tracker.delete(&task.unique_id)?;
+            display::task_delete(&deleted_task, clock, output, &mut io::stdout())?;
+        }
+        Some((EDIT_TEXT, sub_matches)) => {
+            let task_id = sub_matches.get_one::<String>("TASK_ID")
+                .ok_or(anyhow!("A task id was not entered for the edit command.  Enter a valid task id and try again."))?;
 
-                return Ok(());
-            }
+            let mut task = tracker.find(task_id)?;
 
-            match delete_task_by_id(&conn, &task_to_delete.unique_id) {
-                Ok(()) => {
-                    display::custom_message(&(format!("deleted task with id '{}'", task_id)), &mut io::stdout())?;
+            let mut edits = prompt::TaskEdits {
+                description: sub_matches.get_one::<String>("DESCRIPTION").cloned(),
+                project_name: sub_matches.get_one::<String>(PROJECT_TEXT).cloned(),
+                start_time: sub_matches.get_one::<String>("START_TIME").cloned(),
+                end_time: sub_matches.get_one::<String>("END_TIME").cloned(),
+            };
+            let added_tags = get_tags(sub_matches, TAG_TEXT)?;
+            let removed_tags = get_tags(sub_matches, "UNTAG")?;
+            if edits.is_empty() && added_tags.is_empty() && removed_tags.is_empty() {
+                edits = prompt::task_edits(&task, tz)?;
+                if edits.is_empty() {
+                    return display::custom_message("the task was left as it was", output, &mut io::stdout());
+                }
+            }
 
-                    return Ok(());
-                },
-                Err(error) => {
-                    display::custom_message(&(format!("encountered the following sqlite error while trying to delete your task: {}", error.to_string())), &mut io::stdout())?;
-                    return Ok(());
+            if let Some(description) = edits.description {
+                task.description = description;
+            }
+            if let Some(project_name) = edits.project_name {
+                if project_name != task.project_name {
+                    check_project(&tracker, &project_name, config, output)?;
                 }
+                task.project_name = project_name;
+            }
+            if let Some(start_time) = edits.start_time {
+                task.start_time = tracker.parse_time(&start_time)?;
+            }
+            if let Some(end_time) = edits.end_time {
+                task.end_time = Some(tracker.parse_time(&end_time)?);
             }
+            task.tags.extend(added_tags);
+            task.tags.retain(|tag| !removed_tags.contains(tag));
+            task.tags = tags::normalize_tags(&task.tags)?;
+
+            tracker.update(&task)?;
+            display::task_edit(&task, clock, tz, output, &mut io::stdout())?;
         }
         Some((END_TEXT, sub_matches)) => {
-            let end_time: String = match sub_matches.get_one::<String>("END_TIME") {
-                Some(end_time) => convert_to_utc_timestr(end_time)?,
-                None => get_current_utc_string(),
+            let end_time = get_time(&tracker, sub_matches, "END_TIME")?;
+            let ended_task = tracker.end(end_time)?;
+            display::task_end(&ended_task, clock, tz, output, &mut io::stdout())?;
+        }
+        Some((EXPORT_TEXT, sub_matches)) => {
+            let tasks = match (sub_matches.get_one::<String>("FROM"), sub_matches.get_one::<String>("TO")) {
+                (None, None) => tracker.all_tasks()?,
+                (from, to) => {
+                    let from_date = match from {
+                        Some(from) => parse_date(from)?,
+                        None => NaiveDate::from_ymd(1970, 1, 1),
+                    };
+                    // the last day is inclusive, so the export runs up until the start of the next day
+                    let to_date = match to {
+                        Some(to) => parse_date(to)?.succ(),
+                        None => tracker.today().succ(),
+                    };
+                    tracker.tasks_between(from_date, to_date)?
+                }
             };
 
-            match get_most_recent_task(&conn) {
-                Ok(mut prev_task) => {
-                    prev_task.end_task(end_time.to_string());
-                    prev_task.save_to_db(&conn)?;
-                    display::task_end(&prev_task.end_time, &prev_task.description)?;
-                }
-                Err(_err) => {
-                    display::custom_message(
-                        "you currently have no task running",
-                        &mut io::stdout(),
-                    )?;
-                }
+            match sub_matches.get_one::<String>("CSV") {
+                Some(path) => transfer::export_csv(&tasks, tracker.now(), fs::File::create(path)?)?,
+                None => transfer::export_csv(&tasks, tracker.now(), io::stdout())?,
+            }
+        }
+        Some((HISTORY_TEXT, sub_matches)) => {
+            if let Some(keep) = sub_matches.get_one::<usize>("PRUNE") {
+                let removed = tracker.prune_history(*keep)?;
+                let message = format!("removed {} changes from the history", removed);
+                return display::custom_message(&message, output, &mut io::stdout());
+            }
+
+            let limit = sub_matches.get_one::<usize>("LIMIT").copied().unwrap_or(10);
+            let operations = tracker.history(limit)?;
+            display::history(&operations, clock, tz, output, &mut io::stdout())?;
+        }
+        Some((IMPORT_TEXT, sub_matches)) => {
+            let path = sub_matches.get_one::<String>("CSV")
+                .ok_or(anyhow!("A file was not entered for the import command.  Enter a csv file and try again."))?;
+
+            let summary = match path.as_str() {
+                "-" => tracker.import_csv(io::stdin(), &config.default_project, config.project_check)?,
+                path => tracker.import_csv(fs::File::open(path)?, &config.default_project, config.project_check)?,
             };
+            display::import_summary(&summary, output, &mut io::stdout())?;
+            for project in &summary.projects {
+                check_project(&tracker, project, config, output)?;
+            }
         }
         Some((FIND_TEXT, sub_matches)) => {
             let id = sub_matches.get_one::<String>("TASK_ID")
                 .ok_or(anyhow!("A search id was not entered for the find command.  Enter a valid search id and try again."))?;
 
-            match get_tasks_start_with(&conn, id) {
-                Ok(tasks) => {
-                    display::task_find(tasks, id, &mut io::stdout())?;
-                },
-                Err(_err) => {
-                    display::custom_message(&(format!("no tasks were found for the id: {}", id)), &mut io::stdout())?;
+            let filter = TaskFilter {
+                tags: get_tags(sub_matches, TAG_TEXT)?,
+                ..TaskFilter::default()
+            };
+            let tasks = tracker.find_by_prefix(id, &filter)?;
+            display::task_find(tasks, id, &tracker.short_ids()?, clock, tz, output, &mut io::stdout())?;
+        }
+        Some((INFO_TEXT, _)) => {
+            let current_task = tracker.current()?;
+            display::task_info(current_task, clock, tz, output, &mut io::stdout())?;
+        }
+        Some((LIST_TEXT, sub_matches)) => {
+            let filter = get_filter(sub_matches)?;
+            let days = if filter.has_date_range() {
+                Some((
+                    filter.from.unwrap_or_else(|| NaiveDate::from_ymd(1970, 1, 1)),
+                    filter.to.unwrap_or_else(|| tracker.today()),
+                ))
+            } else {
+                None
+            };
+            let tasks = tracker.list(&filter)?;
+            let options = ListOptions {
+                days,
+                verbose: sub_matches.get_flag(VERBOSE_TEXT),
+            };
+            display::task_list(tasks, options, &tracker.short_ids()?, clock, tz, output, &mut io::stdout())?;
+        }
+        Some((LOG_TEXT, sub_matches)) => {
+            let desc = sub_matches.get_one::<String>("DESC")
+                .ok_or(anyhow!("A description was not entered for the log command.  Enter a description and try again."))?;
+            let project = match sub_matches.get_one::<String>(PROJECT_TEXT) {
+                Some(p) => p,
+                None => &config.default_project
+            };
+            let start_time = match sub_matches.get_one::<String>("START_TIME") {
+                Some(start_time) => tracker.parse_time(start_time)?,
+                None => return Err(anyhow!("A start time was not entered for the log command.  Enter a start time and try again.")),
+            };
+            let end_time = match sub_matches.get_one::<String>("END_TIME") {
+                Some(end_time) => tracker.parse_time(end_time)?,
+                None => return Err(anyhow!("An end time was not entered for the log command.  Enter an end time and try again.")),
+            };
+
+            check_project(&tracker, project, config, output)?;
+
+            let (desc, tags) = get_description_and_tags(desc, sub_matches)?;
+            let mut task = Task::new(&desc, project, start_time);
+            task.tags = tags;
+            task.end_task(end_time);
+
+            tracker.log(&task)?;
+            display::task_log(&task, clock, tz, output, &mut io::stdout())?;
+        }
+        Some((NOTE_TEXT, sub_matches)) => {
+            let task_id = sub_matches.get_one::<String>("TASK_ID")
+                .ok_or(anyhow!("A task id was not entered for the note command.  Enter a valid task id and try again."))?;
+            let id = if task_id == "current" { None } else { Some(task_id.as_str()) };
+
+            let text = match sub_matches.get_many::<String>("TEXT") {
+                Some(words) => words.cloned().collect::<Vec<String>>().join(" "),
+                None => {
+                    let task = match id {
+                        Some(id) => tracker.find(id)?,
+                        None => tracker.current()?,
+                    };
+                    match prompt::note_text(&task)? {
+                        Some(text) => text,
+                        None => return display::custom_message("the note was empty, so it wasn't added", output, &mut io::stdout()),
+                    }
                 }
             };
+
+            let noted_task = tracker.add_note(id, &text)?;
+            display::task_note(&noted_task, clock, output, &mut io::stdout())?;
         }
-        Some((INFO_TEXT, _)) => match get_most_recent_task(&conn) {
-            Ok(current_task) => {
-                display::task_info(current_task, &mut io::stdout())?;
+        Some((PAUSE_TEXT, sub_matches)) => {
+            let pause_time = get_time(&tracker, sub_matches, "PAUSE_TIME")?;
+            let paused_task = tracker.pause(pause_time)?;
+            display::task_pause(&paused_task, clock, tz, output, &mut io::stdout())?;
+        }
+        Some((PROJECT_COMMAND_TEXT, sub_matches)) => match sub_matches.subcommand() {
+            Some(("add", add_matches)) => {
+                let name = add_matches.get_one::<String>("NAME").map_or("", String::as_str);
+                let mut project = Project::new(name, tracker.now());
+                project.description = add_matches.get_one::<String>("description").cloned().unwrap_or_default();
+                project.color = add_matches.get_one::<String>("color").cloned();
+                tracker.add_project(&project)?;
+                display::project_add(&project, clock, output, &mut io::stdout())?;
+            }
+            Some(("archive", archive_matches)) => {
+                let name = archive_matches.get_one::<String>("NAME").map_or("", String::as_str);
+                let project = tracker.archive_project(name, true)?;
+                display::project_archive(&project, clock, output, &mut io::stdout())?;
             }
-            Err(_err) => {
-                display::custom_message(
-                    "you currently have no task running",
-                    &mut io::stdout(),
-                )?;
+            Some(("unarchive", unarchive_matches)) => {
+                let name = unarchive_matches.get_one::<String>("NAME").map_or("", String::as_str);
+                let project = tracker.archive_project(name, false)?;
+                display::project_archive(&project, clock, output, &mut io::stdout())?;
+            }
+            Some((command @ ("merge" | "rename"), move_matches)) => {
+                let name = move_matches.get_one::<String>("NAME").map_or("", String::as_str);
+                let (into, tasks) = match command {
+                    "merge" => {
+                        let into = move_matches.get_one::<String>("INTO").map_or("", String::as_str);
+                        (into, tracker.merge_projects(name, into)?)
+                    }
+                    _ => {
+                        let new_name = move_matches.get_one::<String>("NEW_NAME").map_or("", String::as_str);
+                        (new_name, tracker.rename_project(name, new_name)?)
+                    }
+                };
+                let project = tracker
+                    .projects()?
+                    .into_iter()
+                    .find(|project| project.name == into)
+                    .ok_or_else(|| taskmao::Error::ProjectNotFound(into.to_string()))?;
+                display::project_moved(name, &project, &tasks, command == "merge", clock, output, &mut io::stdout())?;
+            }
+            _ => {
+                let list_matches = sub_matches.subcommand_matches("list");
+                let all = list_matches.is_some_and(|matches| matches.get_flag("all"));
+                display::project_list(&tracker.projects()?, &tracker.all_tasks()?, all, clock, output, &mut io::stdout())?;
             }
         },
-        Some((LIST_TEXT, _)) => match get_todays_tasks(&conn) {
-            Ok(tasks) => {
-                display::task_list(tasks, &mut io::stdout())?;
+        Some((REDO_TEXT, sub_matches)) => {
+            let count = sub_matches.get_one::<usize>("COUNT").copied().unwrap_or(1);
+            let operations = tracker.redo(count)?;
+            display::task_redo(&operations, clock, tz, output, &mut io::stdout())?;
+        }
+        Some((REPORT_TEXT, sub_matches)) => {
+            let today = tracker.today();
+            let (from_date, to_date) = if sub_matches.get_flag("WEEK") {
+                report::week_dates(today, config.week_start)
+            } else if sub_matches.get_flag("MONTH") {
+                report::month_dates(today)
+            } else {
+                let from_date = match sub_matches.get_one::<String>("FROM") {
+                    Some(from) => parse_date(from)?,
+                    None => today,
+                };
+                // the last day is inclusive, so the report runs up until the start of the next day
+                let to_date = match sub_matches.get_one::<String>("TO") {
+                    Some(to) => parse_date(to)?.succ(),
+                    None => today.succ(),
+                };
+                (from_date, to_date)
+            };
+
+            let filter = TaskFilter {
+                project: sub_matches.get_one::<String>(PROJECT_TEXT).cloned(),
+                tags: get_tags(sub_matches, TAG_TEXT)?,
+                ..TaskFilter::default()
+            };
+            let report = tracker.report(from_date, to_date, &filter)?;
+            display::report(&report, tz, output, &mut io::stdout())?;
+        }
+        Some((RESTART_TEXT, sub_matches)) => {
+            let start_time = get_time(&tracker, sub_matches, START_TIME_TEXT)?;
+            let task_id = sub_matches.get_one::<String>("TASK_ID").map(String::as_str);
+            check_project(&tracker, &tracker.restart_source(task_id)?.project_name, config, output)?;
+
+            let (new_task, ended_task) = tracker.restart(task_id, start_time)?;
+            display::task_start(&new_task, ended_task.as_ref(), clock, tz, output, &mut io::stdout())?;
+        }
+        Some((RESUME_TEXT, sub_matches)) => {
+            let resume_time = get_time(&tracker, sub_matches, "RESUME_TIME")?;
+            let resumed_task = tracker.resume(resume_time)?;
+            display::task_resume(&resumed_task, clock, tz, output, &mut io::stdout())?;
+        }
+        Some((SEARCH_TEXT, sub_matches)) => {
+            let query = sub_matches.get_one::<String>("QUERY").map_or("", String::as_str);
+            let limit = sub_matches.get_one::<usize>("LIMIT").copied().unwrap_or(20);
+            let hits = tracker.search(query, &get_filter(sub_matches)?, limit)?;
+            display::search(&hits, query, &tracker.short_ids()?, clock, tz, output, &mut io::stdout())?;
+        }
+        Some((TRASH_TEXT, sub_matches)) => match sub_matches.subcommand() {
+            Some(("empty", empty_matches)) => {
+                let older_than = empty_matches.get_one::<String>("OLDER_THAN").map(|d| parse_duration(d)).transpose()?;
+                let emptied = tracker.empty_trash(older_than)?;
+                display::trash_empty(&emptied, clock, output, &mut io::stdout())?;
+            }
+            Some(("restore", restore_matches)) => {
+                let task_id = restore_matches.get_one::<String>("TASK_ID").map_or("", String::as_str);
+                let restored_task = tracker.restore_from_trash(task_id)?;
+                display::trash_restore(&restored_task, clock, output, &mut io::stdout())?;
             }
-            Err(_err) => {
-                display::custom_message("you have no tasks from today", &mut io::stdout())?;
+            _ => {
+                let tasks = tracker.trash()?;
+                let mut ids: Vec<String> = tasks.iter().map(|task| task.unique_id.clone()).collect();
+                ids.sort();
+                display::trash_list(tasks, &ShortIds::new(&ids), clock, tz, output, &mut io::stdout())?;
             }
         },
+        Some((UNDO_TEXT, sub_matches)) => {
+            let count = sub_matches.get_one::<usize>("COUNT").copied().unwrap_or(1);
+            let operations = tracker.undo(count)?;
+            display::task_undo(&operations, clock, tz, output, &mut io::stdout())?;
+        }
         None => {
             let project = match args.get_one::<String>(PROJECT_TEXT) {
                 Some(p) => p,
-                None => "default"
-            };
-            // convert from local datetime to utc string here
-            // need to convert start time input to local datetime, then local datetime to utc
-            // string
-            let start_time: String = match args.get_one::<String>(START_TIME_TEXT) {
-                Some(start_time) => convert_to_utc_timestr(start_time)?,
-                None => get_current_utc_string(),
+                None => &config.default_project
             };
+            let start_time = get_time(&tracker, args, START_TIME_TEXT)?;
 
-            match args.get_one::<String>(DESCRIPTION_TEXT) {
-                Some(desc) => {
-                    // grab most recent entry from sqlite
-                    let new_task = TaskDto {
-                        end_time: start_time.clone(),
-                        description: desc.to_string(),
-                        project_name: project.to_string(),
-                        running: "true".to_string(),
-                        start_time: start_time.clone(),
-                        unique_id: Uuid::new_v4().to_string(),
-                    };
+            let desc = args.get_one::<String>(DESCRIPTION_TEXT).ok_or(anyhow!(
+                "a description wasn't entered for your task.  For more help, try '--help'"
+            ))?;
+            check_project(&tracker, project, config, output)?;
 
-                    let mut prev_task = match get_most_recent_task(&conn) {
-                        Ok(task) => task,
-                        Err(_err) => TaskDto {
-                            end_time: start_time.clone(),
-                            description: desc.to_string(),
-                            project_name: project.to_string(),
-                            running: "false".to_string(),
-                            start_time: start_time.clone(),
-                            unique_id: Uuid::new_v4().to_string(),
-                        },
-                    };
-                    match &prev_task.running[..] {
-                        "true" => {
-                            prev_task.end_task(start_time);
-                            prev_task.save_to_db(&conn)?;
-                            new_task.save_to_db(&conn)?;
-
-                            display::task_end(&prev_task.end_time, &prev_task.description)?;
-                        }
-                        _ => {
-                            new_task.save_to_db(&conn)?;
-                        }
-                    }
+            let (desc, tags) = get_description_and_tags(desc, args)?;
+            let mut new_task = Task::new(&desc, project, start_time);
+            new_task.tags = tags;
 
-                    display::task_start(
-                        &new_task.start_time,
-                        &new_task.description,
-                        &mut io::stdout(),
-                    )?;
-                }
-                None => display::custom_message(
-                    "a description wasn't entered for your task.  For more help, try '--help'",
-                    &mut io::stdout(),
-                )?,
-            }
+            let ended_task = tracker.start(&new_task)?;
+
+            display::task_start(&new_task, ended_task.as_ref(), clock, tz, output, &mut io::stdout())?;
         }
-        _ => display::custom_message(
-            "try 'taskmao --help' for more information",
-            &mut io::stdout(),
-        )?,
+        _ => return Err(anyhow!("try 'taskmao --help' for more information")),
     };
 
     Ok(())
 }
 
-fn main() -> Result<()> {
+fn main() {
     let args = parse_args();
-    run(args).map_err(|e| format!("error code: {}", e)).unwrap();
-    std::process::exit(0);
+    let format = match args.get_one::<String>(FORMAT_TEXT) {
+        Some(format) => format.parse().unwrap_or(OutputFormat::Text),
+        None => OutputFormat::Text,
+    };
+
+    let mut output = Output::new(format);
+    let result = match args.subcommand() {
+        Some((CONFIG_TEXT, sub_matches)) => run_config(sub_matches, &mut output),
+        _ => config::config_path()
+            .map_or(Ok(Config::default()), |path| Config::load(&path))
+            .and_then(|config| {
+                output.style = config.style(io::stdout().is_terminal());
+                run(&args, &config, &output, Box::new(SystemClock), Local)
+            }),
+    };
+
+    if let Err(err) = result {
+        // if even the error can't be written there's nowhere left to report it
+        let _ = display::error(&err, &SystemClock, &Local, &output, &mut io::stderr());
+        std::process::exit(1);
+    }
 }
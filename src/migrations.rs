@@ -0,0 +1,239 @@
+extern crate rusqlite;
+
+use anyhow::{anyhow, Error};
+use rusqlite::Connection;
+
+// ordered list of schema migrations.  the schema version stored in the
+// database's `user_version` pragma is the number of migrations that have been
+// applied, so new migrations must only ever be appended to the end of this list
+const MIGRATIONS: &[&str] = &[
+    // 1: initial tasks table.  uses IF NOT EXISTS so that databases created
+    // before versioning was introduced are adopted without being modified
+    r"
+    CREATE TABLE IF NOT EXISTS tasks (id INTEGER PRIMARY KEY, start_time TEXT UNIQUE, end_time TEXT, project_name TEXT, running TEXT, description TEXT, unique_id TEXT UNIQUE);
+    ",
+    // 2: running tasks no longer store a placeholder end time equal to their start
+    r"
+    UPDATE tasks SET end_time = NULL WHERE running = 'true';
+    ",
+    // 3: tags, which a task can have any number of
+    r"
+    CREATE TABLE task_tags (task_unique_id TEXT NOT NULL, tag TEXT NOT NULL, PRIMARY KEY (task_unique_id, tag));
+    CREATE INDEX task_tags_tag ON task_tags (tag);
+    ",
+    // 4: breaks taken while a task was running.  a break without an end time is still ongoing
+    r"
+    CREATE TABLE task_breaks (task_unique_id TEXT NOT NULL, start_time TEXT NOT NULL, end_time TEXT, PRIMARY KEY (task_unique_id, start_time));
+    ",
+    // 5: the journal of changes made to tasks, for undo and redo.  each row is one task changed by
+    // an operation, with json images of the task from before and after the change
+    r"
+    CREATE TABLE journal (id INTEGER PRIMARY KEY, operation INTEGER NOT NULL, command TEXT NOT NULL, time TEXT NOT NULL, task_unique_id TEXT NOT NULL, before TEXT, after TEXT, undone INTEGER NOT NULL DEFAULT 0);
+    CREATE INDEX journal_operation ON journal (operation);
+    ",
+    // 6: deleted tasks are kept in a trash until it's emptied.  start times only have to be unique
+    // among tasks that aren't in the trash, which sqlite can only express by rebuilding the table
+    r"
+    CREATE TABLE tasks_with_trash (id INTEGER PRIMARY KEY, start_time TEXT, end_time TEXT, project_name TEXT, running TEXT, description TEXT, unique_id TEXT UNIQUE, deleted_at TEXT);
+    INSERT INTO tasks_with_trash (id, start_time, end_time, project_name, running, description, unique_id)
+        SELECT id, start_time, end_time, project_name, running, description, unique_id FROM tasks;
+    DROP TABLE tasks;
+    ALTER TABLE tasks_with_trash RENAME TO tasks;
+    CREATE UNIQUE INDEX tasks_start_time ON tasks (start_time) WHERE deleted_at IS NULL;
+    ",
+    // 7: a full-text index of task descriptions and projects, kept in step with tasks by triggers.
+    // each row's rowid is the id of its task
+    r"
+    CREATE VIRTUAL TABLE task_search USING fts5(description, project);
+    INSERT INTO task_search (rowid, description, project) SELECT id, description, project_name FROM tasks;
+    CREATE TRIGGER task_search_insert AFTER INSERT ON tasks BEGIN
+        INSERT INTO task_search (rowid, description, project) VALUES (new.id, new.description, new.project_name);
+    END;
+    CREATE TRIGGER task_search_update AFTER UPDATE OF description, project_name ON tasks BEGIN
+        UPDATE task_search SET description = new.description, project = new.project_name WHERE rowid = new.id;
+    END;
+    CREATE TRIGGER task_search_delete AFTER DELETE ON tasks BEGIN
+        DELETE FROM task_search WHERE rowid = old.id;
+    END;
+    ",
+    // 8: notes added to tasks, which the full-text index now covers as well.  fts5 tables can't
+    // gain columns, so the index is rebuilt.  a task's notes are indexed as one text, separated by
+    // char(30), so that the matches found in each note can be told apart
+    r"
+    CREATE TABLE task_notes (id INTEGER PRIMARY KEY, task_unique_id TEXT NOT NULL, time TEXT NOT NULL, text TEXT NOT NULL);
+    CREATE INDEX task_notes_task ON task_notes (task_unique_id);
+    DROP TRIGGER task_search_insert;
+    DROP TRIGGER task_search_update;
+    DROP TRIGGER task_search_delete;
+    DROP TABLE task_search;
+    CREATE VIRTUAL TABLE task_search USING fts5(description, project, notes);
+    INSERT INTO task_search (rowid, description, project, notes) SELECT id, description, project_name, '' FROM tasks;
+    CREATE TRIGGER task_search_insert AFTER INSERT ON tasks BEGIN
+        INSERT INTO task_search (rowid, description, project, notes) VALUES (new.id, new.description, new.project_name,
+            (SELECT COALESCE(GROUP_CONCAT(text, char(30)), '') FROM (SELECT text FROM task_notes WHERE task_unique_id = new.unique_id ORDER BY time, id)));
+    END;
+    CREATE TRIGGER task_search_update AFTER UPDATE OF description, project_name ON tasks BEGIN
+        UPDATE task_search SET description = new.description, project = new.project_name WHERE rowid = new.id;
+    END;
+    CREATE TRIGGER task_search_delete AFTER DELETE ON tasks BEGIN
+        DELETE FROM task_search WHERE rowid = old.id;
+    END;
+    CREATE TRIGGER task_search_note_insert AFTER INSERT ON task_notes BEGIN
+        UPDATE task_search SET notes = (SELECT COALESCE(GROUP_CONCAT(text, char(30)), '') FROM (SELECT text FROM task_notes WHERE task_unique_id = new.task_unique_id ORDER BY time, id))
+            WHERE rowid = (SELECT id FROM tasks WHERE unique_id = new.task_unique_id);
+    END;
+    CREATE TRIGGER task_search_note_delete AFTER DELETE ON task_notes BEGIN
+        UPDATE task_search SET notes = (SELECT COALESCE(GROUP_CONCAT(text, char(30)), '') FROM (SELECT text FROM task_notes WHERE task_unique_id = old.task_unique_id ORDER BY time, id))
+            WHERE rowid = (SELECT id FROM tasks WHERE unique_id = old.task_unique_id);
+    END;
+    ",
+    // 9: projects, with the projects tasks already use being added as they were first used.  the
+    // journal also records changes to projects, in rows with a kind of 'project' that keep the
+    // project's name in task_unique_id
+    r"
+    CREATE TABLE projects (name TEXT PRIMARY KEY, description TEXT NOT NULL DEFAULT '', color TEXT, archived INTEGER NOT NULL DEFAULT 0, created_at TEXT NOT NULL);
+    INSERT INTO projects (name, created_at)
+        SELECT project_name, MIN(start_time) FROM tasks WHERE project_name IS NOT NULL GROUP BY project_name;
+    ALTER TABLE journal ADD COLUMN kind TEXT NOT NULL DEFAULT 'task';
+    ",
+];
+
+pub fn latest_version() -> u32 {
+    MIGRATIONS.len() as u32
+}
+
+pub fn get_schema_version(conn: &Connection) -> Result<u32, Error> {
+    let version: u32 = conn.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+
+    Ok(version)
+}
+
+pub fn run_migrations(conn: &Connection) -> Result<(), Error> {
+    let current_version = get_schema_version(conn)?;
+
+    if current_version > latest_version() {
+        return Err(anyhow!(
+            "the task data store is at schema version {}, but this version of taskmao only supports up to version {}.  upgrade taskmao and try again",
+            current_version,
+            latest_version()
+        ));
+    }
+
+    for (idx, migration) in MIGRATIONS.iter().enumerate().skip(current_version as usize) {
+        let version = idx + 1;
+        let tx = conn.unchecked_transaction()?;
+
+        tx.execute_batch(migration).map_err(|err| {
+            anyhow!(
+                "migrating the task data store to schema version {} failed with the following error: {}",
+                version,
+                err
+            )
+        })?;
+        // pragmas can't be bound as parameters, so the version is formatted in
+        tx.execute_batch(&format!("PRAGMA user_version = {};", version))?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fresh_database_is_migrated_to_latest() -> Result<(), Error> {
+        let conn = Connection::open_in_memory()?;
+        run_migrations(&conn)?;
+        assert_eq!(get_schema_version(&conn)?, latest_version());
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrations_are_idempotent() -> Result<(), Error> {
+        let conn = Connection::open_in_memory()?;
+        run_migrations(&conn)?;
+        run_migrations(&conn)?;
+        assert_eq!(get_schema_version(&conn)?, latest_version());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unversioned_database_keeps_existing_tasks() -> Result<(), Error> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch(
+            "CREATE TABLE tasks (id INTEGER PRIMARY KEY, start_time TEXT UNIQUE, end_time TEXT, project_name TEXT, running TEXT, description TEXT, unique_id TEXT UNIQUE);
+             INSERT INTO tasks (start_time, end_time, project_name, running, description, unique_id) VALUES ('2021-03-18 23:36:24', '2021-03-18 23:38:59', 'default', 'false', 'old task', 'abc');",
+        )?;
+
+        run_migrations(&conn)?;
+
+        let count: u32 = conn.query_row("SELECT COUNT(*) FROM tasks", [], |r| r.get(0))?;
+        assert_eq!(count, 1);
+        assert_eq!(get_schema_version(&conn)?, latest_version());
+        Ok(())
+    }
+
+    #[test]
+    fn test_running_tasks_lose_placeholder_end_time() -> Result<(), Error> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch(
+            "CREATE TABLE tasks (id INTEGER PRIMARY KEY, start_time TEXT UNIQUE, end_time TEXT, project_name TEXT, running TEXT, description TEXT, unique_id TEXT UNIQUE);
+             INSERT INTO tasks (start_time, end_time, project_name, running, description, unique_id) VALUES ('2021-03-18 23:36:24', '2021-03-18 23:36:24', 'default', 'true', 'running task', 'abc');",
+        )?;
+
+        run_migrations(&conn)?;
+
+        let end_time: Option<String> = conn.query_row("SELECT end_time FROM tasks WHERE unique_id = 'abc'", [], |r| r.get(0))?;
+        assert_eq!(end_time, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_start_times_are_only_unique_outside_the_trash() -> Result<(), Error> {
+        let conn = Connection::open_in_memory()?;
+        run_migrations(&conn)?;
+
+        let insert = "INSERT INTO tasks (start_time, running, description, unique_id, deleted_at) VALUES ('2021-03-18 23:36:24', 'false', 'task', ?1, ?2);";
+        conn.execute(insert, rusqlite::params!["a", "2021-03-19 09:00:00"])?;
+        conn.execute(insert, rusqlite::params!["b", "2021-03-19 10:00:00"])?;
+        conn.execute(insert, rusqlite::params!["c", None::<String>])?;
+        assert!(conn.execute(insert, rusqlite::params!["d", None::<String>]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_projects_in_use_are_added() -> Result<(), Error> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch(
+            "CREATE TABLE tasks (id INTEGER PRIMARY KEY, start_time TEXT UNIQUE, end_time TEXT, project_name TEXT, running TEXT, description TEXT, unique_id TEXT UNIQUE);
+             INSERT INTO tasks (start_time, end_time, project_name, running, description, unique_id) VALUES ('2021-03-18 09:00:00', '2021-03-18 10:00:00', 'work', 'false', 'later', 'a');
+             INSERT INTO tasks (start_time, end_time, project_name, running, description, unique_id) VALUES ('2021-03-17 09:00:00', '2021-03-17 10:00:00', 'work', 'false', 'first', 'b');
+             INSERT INTO tasks (start_time, end_time, project_name, running, description, unique_id) VALUES ('2021-03-18 11:00:00', '2021-03-18 12:00:00', 'client', 'false', 'other', 'c');",
+        )?;
+
+        run_migrations(&conn)?;
+
+        let mut stmt = conn.prepare("SELECT name, created_at, archived FROM projects ORDER BY name")?;
+        let projects = stmt
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?
+            .collect::<rusqlite::Result<Vec<(String, String, bool)>>>()?;
+        assert_eq!(
+            projects,
+            vec![
+                ("client".to_string(), "2021-03-18 11:00:00".to_string(), false),
+                ("work".to_string(), "2021-03-17 09:00:00".to_string(), false),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_newer_schema_is_refused() -> Result<(), Error> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch(&format!("PRAGMA user_version = {};", latest_version() + 1))?;
+        assert!(run_migrations(&conn).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,204 @@
+use anyhow::{anyhow, Error};
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+// tasks were kept here before profiles existed, so the default profile keeps using it if it's there
+const LEGACY_DB_FILE: &str = "base.sql3";
+const DB_EXTENSION: &str = "sql3";
+
+lazy_static! {
+    static ref PROFILE_NAME_REGEX: Regex = Regex::new(r"^[a-z0-9][a-z0-9_-]*$").unwrap();
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+// the directories taskmao keeps its data in.  there's no data directory when there's no home
+// directory, in which case a database has to be given explicitly
+#[derive(Debug)]
+pub struct DataDirs {
+    pub data_dir: Option<PathBuf>,
+    pub legacy_dir: Option<PathBuf>,
+}
+
+impl DataDirs {
+    // uses $XDG_DATA_HOME/taskmao, falling back to the platform's data directory
+    pub fn from_env() -> DataDirs {
+        let xdg_data_home = std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute());
+
+        DataDirs {
+            data_dir: xdg_data_home.or_else(dirs::data_dir).map(|data_home| data_home.join("taskmao")),
+            legacy_dir: dirs::home_dir().map(|home| home.join(".config").join("taskmao")),
+        }
+    }
+
+    fn data_dir(&self) -> Result<&Path, Error> {
+        self.data_dir.as_deref().ok_or(anyhow!(
+            "a directory to keep tasks in couldn't be found.  set a database with --db or TASKMAO_DB"
+        ))
+    }
+
+    fn profiles_dir(&self) -> Result<PathBuf, Error> {
+        Ok(self.data_dir()?.join("profiles"))
+    }
+
+    pub fn profile_path(&self, name: &str) -> Result<PathBuf, Error> {
+        if !PROFILE_NAME_REGEX.is_match(name) {
+            return Err(anyhow!(
+                "'{}' is an illegal profile name;  use lowercase letters, numbers, '-' and '_'",
+                name
+            ));
+        }
+
+        if name == DEFAULT_PROFILE {
+            if let Some(legacy_path) = self.legacy_dir.as_ref().map(|dir| dir.join(LEGACY_DB_FILE)) {
+                if legacy_path.exists() {
+                    return Ok(legacy_path);
+                }
+            }
+            return Ok(self.data_dir()?.join(LEGACY_DB_FILE));
+        }
+
+        Ok(self.profiles_dir()?.join(name).with_extension(DB_EXTENSION))
+    }
+
+    // the default profile always exists, followed by every other profile that has been used
+    pub fn profiles(&self) -> Result<Vec<Profile>, Error> {
+        let profiles_dir = self.profiles_dir()?;
+        let mut names: Vec<String> = Vec::new();
+        if profiles_dir.is_dir() {
+            for entry in fs::read_dir(profiles_dir)? {
+                let path = entry?.path();
+                if path.extension() == Some(std::ffi::OsStr::new(DB_EXTENSION)) {
+                    if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                        names.push(name.to_string());
+                    }
+                }
+            }
+        }
+        names.retain(|name| name != DEFAULT_PROFILE && PROFILE_NAME_REGEX.is_match(name));
+        names.sort();
+        names.insert(0, DEFAULT_PROFILE.to_string());
+
+        names
+            .into_iter()
+            .map(|name| {
+                Ok(Profile {
+                    path: self.profile_path(&name)?,
+                    name,
+                })
+            })
+            .collect()
+    }
+}
+
+// works out which database to use.  an explicit `--db` wins, then `--profile`, then the
+// TASKMAO_DB environment variable, and otherwise the default profile
+pub fn db_path(db: Option<&str>, profile: Option<&str>, env_db: Option<&str>, data_dirs: &DataDirs) -> Result<PathBuf, Error> {
+    match (db, profile, env_db) {
+        (Some(db), _, _) => Ok(PathBuf::from(db)),
+        (None, Some(profile), _) => data_dirs.profile_path(profile),
+        (None, None, Some(env_db)) if !env_db.is_empty() => Ok(PathBuf::from(env_db)),
+        _ => data_dirs.profile_path(DEFAULT_PROFILE),
+    }
+}
+
+// creates the directory the database is kept in, if it doesn't exist yet
+pub fn ensure_parent_dir(path: &Path) -> Result<(), Error> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn temp_dirs() -> DataDirs {
+        let root = std::env::temp_dir().join(format!("taskmao-test-{}", Uuid::new_v4()));
+
+        DataDirs {
+            data_dir: Some(root.join("data")),
+            legacy_dir: Some(root.join("config")),
+        }
+    }
+
+    #[test]
+    fn test_db_path_precedence() -> Result<(), Error> {
+        let dirs = temp_dirs();
+        let data_dir = dirs.data_dir.clone().unwrap();
+
+        assert_eq!(db_path(Some("a.sql3"), Some("work"), Some("b.sql3"), &dirs)?, PathBuf::from("a.sql3"));
+        assert_eq!(
+            db_path(None, Some("work"), Some("b.sql3"), &dirs)?,
+            data_dir.join("profiles").join("work.sql3")
+        );
+        assert_eq!(db_path(None, None, Some("b.sql3"), &dirs)?, PathBuf::from("b.sql3"));
+        assert_eq!(db_path(None, None, Some(""), &dirs)?, data_dir.join("base.sql3"));
+        assert_eq!(db_path(None, None, None, &dirs)?, data_dir.join("base.sql3"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_profile_keeps_the_legacy_database() -> Result<(), Error> {
+        let dirs = temp_dirs();
+        let legacy_dir = dirs.legacy_dir.clone().unwrap();
+        fs::create_dir_all(&legacy_dir)?;
+        fs::write(legacy_dir.join("base.sql3"), "")?;
+
+        assert_eq!(dirs.profile_path(DEFAULT_PROFILE)?, legacy_dir.join("base.sql3"));
+
+        fs::remove_dir_all(legacy_dir.parent().unwrap())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_illegal_profile_names_are_rejected() {
+        let dirs = temp_dirs();
+
+        for name in &["", "../work", "Work", "work/home", "-work"] {
+            assert!(dirs.profile_path(name).is_err(), "'{}' should be rejected", name);
+        }
+    }
+
+    #[test]
+    fn test_no_data_dir_needs_an_explicit_db() -> Result<(), Error> {
+        let dirs = DataDirs {
+            data_dir: None,
+            legacy_dir: None,
+        };
+
+        assert_eq!(db_path(None, None, Some("b.sql3"), &dirs)?, PathBuf::from("b.sql3"));
+        assert!(db_path(None, None, None, &dirs).is_err());
+        assert!(db_path(None, Some("work"), None, &dirs).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_profiles_lists_default_first() -> Result<(), Error> {
+        let dirs = temp_dirs();
+        let data_dir = dirs.data_dir.clone().unwrap();
+        let profiles_dir = data_dir.join("profiles");
+        fs::create_dir_all(&profiles_dir)?;
+        for file in &["work.sql3", "personal.sql3", "notes.txt"] {
+            fs::write(profiles_dir.join(file), "")?;
+        }
+
+        let names: Vec<String> = dirs.profiles()?.into_iter().map(|profile| profile.name).collect();
+        assert_eq!(names, vec!["default", "personal", "work"]);
+
+        fs::remove_dir_all(data_dir.parent().unwrap())?;
+        Ok(())
+    }
+}
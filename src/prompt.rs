@@ -0,0 +1,107 @@
+extern crate dialoguer;
+
+use anyhow::anyhow;
+use chrono::TimeZone;
+use dialoguer::{Confirm, Editor, Input};
+use std::io::{self, IsTerminal};
+use taskmao::time::convert_to_local_timestamp;
+use taskmao::Task;
+
+// raw, unparsed changes to a task, whether they came from flags or from prompts
+#[derive(Debug, Default)]
+pub struct TaskEdits {
+    pub description: Option<String>,
+    pub project_name: Option<String>,
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+}
+
+impl TaskEdits {
+    pub fn is_empty(&self) -> bool {
+        self.description.is_none()
+            && self.project_name.is_none()
+            && self.start_time.is_none()
+            && self.end_time.is_none()
+    }
+}
+
+// whether to ask before `command` goes ahead, which it always does with `--yes`.  without a
+// terminal to ask on the command is refused instead, so that scripts never hang waiting for an answer
+pub fn needs_confirmation(command: &str, yes: bool) -> Result<bool, anyhow::Error> {
+    if yes {
+        return Ok(false);
+    }
+
+    if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        return Err(anyhow!(
+            "'{}' asks for confirmation, but taskmao isn't running in a terminal.  pass --yes to {} without being asked",
+            command,
+            command
+        ));
+    }
+
+    Ok(true)
+}
+
+// asks a yes or no question, defaulting to no
+pub fn confirm(question: &str) -> Result<bool, anyhow::Error> {
+    Ok(Confirm::new().with_prompt(question).default(false).interact()?)
+}
+
+// opens $VISUAL or $EDITOR for a note on `task`.  lines starting with '#' are left out, and a note
+// that's empty or wasn't saved isn't added
+pub fn note_text(task: &Task) -> Result<Option<String>, anyhow::Error> {
+    if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        return Err(anyhow!(
+            "'note' opens an editor when no text is given, but taskmao isn't running in a terminal.  pass the note's text after the task id"
+        ));
+    }
+
+    let template = format!(
+        "\n# write the note for '{}' above.  lines starting with '#' are left out, and an empty note isn't added\n",
+        task.description
+    );
+    let text = Editor::new()
+        .edit(&template)
+        .map_err(|err| anyhow!("the editor couldn't be opened ({}).  set $EDITOR to the editor to use, or pass the note's text after the task id", err))?
+        .unwrap_or_default();
+    let text: Vec<&str> = text.lines().filter(|line| !line.starts_with('#')).collect();
+
+    Ok(Some(text.join("\n").trim().to_string()).filter(|text| !text.is_empty()))
+}
+
+// asks for a field with its current value as the default.  an unchanged value is `None`, so that
+// it's left exactly as it was rather than read back in
+fn edited_field(prompt: &str, current: String) -> Result<Option<String>, anyhow::Error> {
+    let value = Input::<String>::new()
+        .with_prompt(prompt)
+        .default(current.clone())
+        .interact_text()?;
+
+    Ok(Some(value).filter(|value| *value != current))
+}
+
+// prompts for every editable field of the task, using its current values as defaults
+pub fn task_edits(task: &Task, tz: &impl TimeZone) -> Result<TaskEdits, anyhow::Error> {
+    if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        return Err(anyhow!(
+            "'edit' asks for each field when no changes are given, but taskmao isn't running in a terminal.  pass the changes as flags, such as --description or --project"
+        ));
+    }
+
+    let description = edited_field("description", task.description.clone())?;
+    let project_name = edited_field("project", task.project_name.clone())?;
+    let start_time = edited_field("start time", convert_to_local_timestamp(&task.start_time, tz, true))?;
+    // the end time of a running task is set by ending it
+    let end_time = match task.end_time {
+        Some(end_time) => edited_field("end time", convert_to_local_timestamp(&end_time, tz, true))?,
+        None => None,
+    };
+
+    Ok(TaskEdits {
+        description,
+        project_name,
+        start_time,
+        end_time,
+    })
+}
@@ -0,0 +1,260 @@
+extern crate chrono;
+
+use crate::data::Task;
+use crate::time::get_local_day_start;
+use chrono::prelude::*;
+use chrono::Duration;
+use std::collections::BTreeMap;
+
+#[derive(Debug, PartialEq)]
+pub struct Report {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub total: Duration,
+    pub by_project: Vec<(String, Duration)>,
+    pub by_day: Vec<(NaiveDate, Duration)>,
+    pub by_description: Vec<(String, String, Duration)>,
+    pub by_tag: Vec<(String, Duration)>,
+}
+
+// returns the first day of the week containing `today` and the first day of the following week,
+// where weeks start on `week_start`
+pub fn week_dates(today: NaiveDate, week_start: Weekday) -> (NaiveDate, NaiveDate) {
+    let days_since_start = (7 + today.weekday().num_days_from_monday() - week_start.num_days_from_monday()) % 7;
+    let start = today - Duration::days(days_since_start as i64);
+
+    (start, start + Duration::days(7))
+}
+
+// returns the first day of the month containing `today` and the first day of the following month
+pub fn month_dates(today: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let start = NaiveDate::from_ymd(today.year(), today.month(), 1);
+    let end = match today.month() {
+        12 => NaiveDate::from_ymd(today.year() + 1, 1, 1),
+        month => NaiveDate::from_ymd(today.year(), month + 1, 1),
+    };
+
+    (start, end)
+}
+
+fn add_duration<K: Ord>(totals: &mut BTreeMap<K, Duration>, key: K, duration: Duration) {
+    let total = totals.entry(key).or_insert_with(Duration::zero);
+    *total = *total + duration;
+}
+
+// sorts durations largest first, falling back to the key so that output is stable
+fn sorted_by_duration<K: Ord>(totals: BTreeMap<K, Duration>) -> Vec<(K, Duration)> {
+    let mut sorted: Vec<(K, Duration)> = totals.into_iter().collect();
+    sorted.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    sorted
+}
+
+// totals the time spent on `tasks` between `from` and `to`, less breaks.  running tasks count up until `now`,
+// and time is attributed to the local day it was spent on, so tasks crossing midnight are split
+pub fn build_report(
+    tasks: &[Task],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    now: DateTime<Utc>,
+    tz: &impl TimeZone,
+) -> Result<Report, anyhow::Error> {
+    let mut total = Duration::zero();
+    let mut by_project: BTreeMap<String, Duration> = BTreeMap::new();
+    let mut by_day: BTreeMap<NaiveDate, Duration> = BTreeMap::new();
+    let mut by_description: BTreeMap<(String, String), Duration> = BTreeMap::new();
+    let mut by_tag: BTreeMap<String, Duration> = BTreeMap::new();
+
+    for task in tasks {
+        let start = std::cmp::max(task.start_time, from);
+        let end = std::cmp::min(task.end_time.unwrap_or(now), to);
+
+        if end <= start {
+            continue;
+        }
+
+        let duration = task.duration_between(start, end, now);
+        total = total + duration;
+        add_duration(&mut by_project, task.project_name.clone(), duration);
+        add_duration(
+            &mut by_description,
+            (task.project_name.clone(), task.description.clone()),
+            duration,
+        );
+        for tag in &task.tags {
+            add_duration(&mut by_tag, tag.clone(), duration);
+        }
+
+        let mut cursor = start;
+        while cursor < end {
+            let day = cursor.with_timezone(tz).date().naive_local();
+            let next_day_start = get_local_day_start(day.succ(), tz)?;
+            let segment_end = std::cmp::min(end, next_day_start);
+
+            add_duration(&mut by_day, day, task.duration_between(cursor, segment_end, now));
+            cursor = segment_end;
+        }
+    }
+
+    Ok(Report {
+        from,
+        to,
+        total,
+        by_project: sorted_by_duration(by_project),
+        by_day: by_day.into_iter().collect(),
+        by_description: sorted_by_duration(by_description)
+            .into_iter()
+            .map(|((project, description), duration)| (project, description, duration))
+            .collect(),
+        by_tag: sorted_by_duration(by_tag),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::DstZone;
+
+    // reports are built in a timezone an hour behind utc
+    fn tz() -> FixedOffset {
+        FixedOffset::west(3600)
+    }
+
+    fn local_utc(y: i32, m: u32, d: u32, h: u32) -> DateTime<Utc> {
+        tz().ymd(y, m, d).and_hms(h, 0, 0).with_timezone(&Utc)
+    }
+
+    fn ended_task(description: &str, project: &str, start: DateTime<Utc>, end: DateTime<Utc>) -> Task {
+        let mut task = Task::new(description, project, start);
+        task.end_task(end);
+        task
+    }
+
+    #[test]
+    fn test_week_dates() {
+        let (start, end) = week_dates(NaiveDate::from_ymd(2021, 3, 18), Weekday::Mon);
+        assert_eq!(start, NaiveDate::from_ymd(2021, 3, 15));
+        assert_eq!(end, NaiveDate::from_ymd(2021, 3, 22));
+
+        let (start, _) = week_dates(NaiveDate::from_ymd(2021, 3, 15), Weekday::Mon);
+        assert_eq!(start, NaiveDate::from_ymd(2021, 3, 15));
+
+        let (start, end) = week_dates(NaiveDate::from_ymd(2021, 3, 18), Weekday::Sun);
+        assert_eq!(start, NaiveDate::from_ymd(2021, 3, 14));
+        assert_eq!(end, NaiveDate::from_ymd(2021, 3, 21));
+
+        let (start, _) = week_dates(NaiveDate::from_ymd(2021, 3, 14), Weekday::Sun);
+        assert_eq!(start, NaiveDate::from_ymd(2021, 3, 14));
+    }
+
+    #[test]
+    fn test_month_dates() {
+        assert_eq!(
+            month_dates(NaiveDate::from_ymd(2021, 3, 18)),
+            (NaiveDate::from_ymd(2021, 3, 1), NaiveDate::from_ymd(2021, 4, 1))
+        );
+        assert_eq!(
+            month_dates(NaiveDate::from_ymd(2021, 12, 31)),
+            (NaiveDate::from_ymd(2021, 12, 1), NaiveDate::from_ymd(2022, 1, 1))
+        );
+    }
+
+    #[test]
+    fn test_task_crossing_midnight_is_split_by_day() -> Result<(), anyhow::Error> {
+        let tasks = vec![ended_task("late night", "work", local_utc(2021, 3, 18, 22), local_utc(2021, 3, 19, 2))];
+        let report = build_report(&tasks, local_utc(2021, 3, 18, 0), local_utc(2021, 3, 20, 0), local_utc(2021, 3, 20, 0), &tz())?;
+
+        assert_eq!(report.total, Duration::hours(4));
+        assert_eq!(
+            report.by_day,
+            vec![
+                (NaiveDate::from_ymd(2021, 3, 18), Duration::hours(2)),
+                (NaiveDate::from_ymd(2021, 3, 19), Duration::hours(2)),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_task_across_spring_forward_counts_elapsed_time() -> Result<(), anyhow::Error> {
+        let dst_utc = |d: u32, h: u32| DstZone.ymd(2021, 3, d).and_hms(h, 0, 0).with_timezone(&Utc);
+        let tasks = vec![ended_task("late night", "work", dst_utc(27, 22), dst_utc(28, 4))];
+        let report = build_report(&tasks, dst_utc(27, 0), dst_utc(29, 0), dst_utc(29, 0), &DstZone)?;
+
+        assert_eq!(report.total, Duration::hours(5));
+        assert_eq!(
+            report.by_day,
+            vec![
+                (NaiveDate::from_ymd(2021, 3, 27), Duration::hours(2)),
+                (NaiveDate::from_ymd(2021, 3, 28), Duration::hours(3)),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_tasks_are_clipped_to_range() -> Result<(), anyhow::Error> {
+        let tasks = vec![ended_task("late night", "work", local_utc(2021, 3, 18, 22), local_utc(2021, 3, 19, 2))];
+        let report = build_report(&tasks, local_utc(2021, 3, 19, 0), local_utc(2021, 3, 20, 0), local_utc(2021, 3, 20, 0), &tz())?;
+
+        assert_eq!(report.total, Duration::hours(2));
+        assert_eq!(report.by_day, vec![(NaiveDate::from_ymd(2021, 3, 19), Duration::hours(2))]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_running_tasks_count_until_now() -> Result<(), anyhow::Error> {
+        let tasks = vec![Task::new("still going", "work", local_utc(2021, 3, 19, 9))];
+        let report = build_report(&tasks, local_utc(2021, 3, 19, 0), local_utc(2021, 3, 20, 0), local_utc(2021, 3, 19, 12), &tz())?;
+
+        assert_eq!(report.total, Duration::hours(3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_breaks_are_subtracted() -> Result<(), anyhow::Error> {
+        let mut task = Task::new("late night", "work", local_utc(2021, 3, 18, 22));
+        task.pause(local_utc(2021, 3, 18, 23))?;
+        task.resume(local_utc(2021, 3, 19, 1))?;
+        task.end_task(local_utc(2021, 3, 19, 2));
+        let report = build_report(&[task], local_utc(2021, 3, 18, 0), local_utc(2021, 3, 20, 0), local_utc(2021, 3, 20, 0), &tz())?;
+
+        assert_eq!(report.total, Duration::hours(2));
+        assert_eq!(
+            report.by_day,
+            vec![
+                (NaiveDate::from_ymd(2021, 3, 18), Duration::hours(1)),
+                (NaiveDate::from_ymd(2021, 3, 19), Duration::hours(1)),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_totals_are_grouped_by_project_and_description() -> Result<(), anyhow::Error> {
+        let mut feature = ended_task("feature", "work", local_utc(2021, 3, 19, 9), local_utc(2021, 3, 19, 11));
+        feature.tags = vec!["billable".to_string()];
+        let tasks = vec![
+            ended_task("emails", "admin", local_utc(2021, 3, 19, 8), local_utc(2021, 3, 19, 9)),
+            feature,
+            ended_task("emails", "admin", local_utc(2021, 3, 19, 11), local_utc(2021, 3, 19, 13)),
+            ended_task("review", "work", local_utc(2021, 3, 19, 13), local_utc(2021, 3, 19, 14)),
+        ];
+        let report = build_report(&tasks, local_utc(2021, 3, 19, 0), local_utc(2021, 3, 20, 0), local_utc(2021, 3, 20, 0), &tz())?;
+
+        assert_eq!(report.total, Duration::hours(6));
+        assert_eq!(
+            report.by_project,
+            vec![("admin".to_string(), Duration::hours(3)), ("work".to_string(), Duration::hours(3))]
+        );
+        assert_eq!(report.by_tag, vec![("billable".to_string(), Duration::hours(2))]);
+        assert_eq!(
+            report.by_description,
+            vec![
+                ("admin".to_string(), "emails".to_string(), Duration::hours(3)),
+                ("work".to_string(), "feature".to_string(), Duration::hours(2)),
+                ("work".to_string(), "review".to_string(), Duration::hours(1)),
+            ]
+        );
+        Ok(())
+    }
+}
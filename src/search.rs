@@ -0,0 +1,514 @@
+use crate::data::Task;
+use crate::error::Error;
+
+// matched terms in a hit's description, project and notes are wrapped in these, for the display to
+// replace with however it shows matches
+pub const MATCH_START: char = '\u{2}';
+pub const MATCH_END: char = '\u{3}';
+
+// a task found by a full-text search.  a higher score is a better match
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchHit {
+    pub task: Task,
+    pub score: f64,
+    pub description: String,
+    pub project: String,
+    // the text of each of the task's notes, in order
+    pub notes: Vec<String>,
+}
+
+// a column of the index that a term can be limited to, as in `project:client`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Column {
+    Description,
+    Project,
+    Notes,
+}
+
+impl Column {
+    fn name(&self) -> &'static str {
+        match self {
+            Column::Description => "description",
+            Column::Project => "project",
+            Column::Notes => "notes",
+        }
+    }
+}
+
+// a word, a "quoted phrase" or a prefix*, which matches the words in order
+#[derive(Clone, Debug, PartialEq)]
+pub struct Term {
+    words: Vec<String>,
+    prefix: bool,
+    column: Option<Column>,
+}
+
+// a search parsed from sqlite's fts5 query syntax, which every store understands the same way.
+// `a NOT b` matches `a` unless `b` matches too, and binds tighter than AND, which binds tighter
+// than OR
+#[derive(Clone, Debug, PartialEq)]
+pub enum Query {
+    Term(Term),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>, Box<Query>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Phrase(String),
+    Star,
+    Colon,
+    Open,
+    Close,
+}
+
+// the alphanumeric runs in `text`, with where each starts and ends
+fn tokens(text: &str) -> Vec<(usize, usize, String)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (idx, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+        match (start, c.is_alphanumeric()) {
+            (None, true) => start = Some(idx),
+            (Some(token_start), false) => {
+                tokens.push((token_start, idx, text[token_start..idx].to_lowercase()));
+                start = None;
+            }
+            _ => (),
+        }
+    }
+
+    tokens
+}
+
+// the characters fts5 allows in a word outside of quotes
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || !c.is_ascii()
+}
+
+fn lex(query: &str) -> Result<Vec<Token>, String> {
+    let mut lexed = Vec::new();
+    let mut chars = query.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => lexed.push(Token::Star),
+            ':' => lexed.push(Token::Colon),
+            '(' => lexed.push(Token::Open),
+            ')' => lexed.push(Token::Close),
+            '"' => {
+                let mut phrase = String::new();
+                loop {
+                    match chars.next() {
+                        // a doubled quote is a quote within the phrase
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            phrase.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => phrase.push(c),
+                        None => return Err("a quote isn't closed".to_string()),
+                    }
+                }
+                lexed.push(Token::Phrase(phrase));
+            }
+            c if c.is_whitespace() => (),
+            c if is_word_char(c) => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.peek().copied().filter(|c| is_word_char(*c)) {
+                    word.push(c);
+                    chars.next();
+                }
+                lexed.push(Token::Word(word));
+            }
+            c => return Err(format!("'{}' can only be searched for in double quotes", c)),
+        }
+    }
+
+    Ok(lexed)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn peek_operator(&self, operator: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if word == operator)
+    }
+
+    fn or(&mut self) -> Result<Query, String> {
+        let mut queries = vec![self.and()?];
+        while self.peek_operator("OR") {
+            self.next += 1;
+            queries.push(self.and()?);
+        }
+
+        Ok(if queries.len() == 1 { queries.remove(0) } else { Query::Or(queries) })
+    }
+
+    fn and(&mut self) -> Result<Query, String> {
+        let mut queries = vec![self.not()?];
+        loop {
+            if self.peek_operator("AND") {
+                self.next += 1;
+            } else if !self.starts_term() {
+                break;
+            }
+            queries.push(self.not()?);
+        }
+
+        Ok(if queries.len() == 1 { queries.remove(0) } else { Query::And(queries) })
+    }
+
+    fn not(&mut self) -> Result<Query, String> {
+        let mut query = self.term()?;
+        while self.peek_operator("NOT") {
+            self.next += 1;
+            query = Query::Not(Box::new(query), Box::new(self.term()?));
+        }
+
+        Ok(query)
+    }
+
+    fn starts_term(&self) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) => !["AND", "OR", "NOT"].contains(&word.as_str()),
+            Some(Token::Phrase(_)) | Some(Token::Open) => true,
+            _ => false,
+        }
+    }
+
+    fn term(&mut self) -> Result<Query, String> {
+        if !self.starts_term() {
+            return Err(match self.peek() {
+                Some(Token::Word(operator)) => format!("'{}' needs a word on each side", operator),
+                Some(Token::Star) => "'*' has to follow a word".to_string(),
+                Some(Token::Colon) => "':' has to follow a column, such as 'project'".to_string(),
+                Some(Token::Close) => "a bracket is closed without being opened".to_string(),
+                _ => "it ends too soon".to_string(),
+            });
+        }
+
+        // a word followed by a colon limits what comes after it to that column
+        if let (Some(Token::Word(name)), Some(Token::Colon)) = (self.peek(), self.tokens.get(self.next + 1)) {
+            let column = match name.to_lowercase().as_str() {
+                "description" => Column::Description,
+                "project" => Column::Project,
+                "notes" => Column::Notes,
+                _ => return Err(format!("'{}' isn't a column;  use description, project or notes", name)),
+            };
+            self.next += 2;
+            let mut query = self.term()?;
+            query.limit_to(column);
+            return Ok(query);
+        }
+
+        let text = match self.tokens[self.next].clone() {
+            Token::Open => {
+                self.next += 1;
+                let query = self.or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err("a bracket isn't closed".to_string());
+                }
+                self.next += 1;
+                return Ok(query);
+            }
+            Token::Word(text) | Token::Phrase(text) => text,
+            _ => return Err("it ends too soon".to_string()),
+        };
+        self.next += 1;
+        let prefix = self.peek() == Some(&Token::Star);
+        if prefix {
+            self.next += 1;
+        }
+
+        Ok(Query::Term(Term {
+            words: tokens(&text).into_iter().map(|(_, _, word)| word).collect(),
+            prefix,
+            column: None,
+        }))
+    }
+}
+
+// the spans that a term matched in each part of a task
+#[derive(Clone, Debug, Default)]
+struct Spans {
+    description: Vec<(usize, usize)>,
+    project: Vec<(usize, usize)>,
+    notes: Vec<Vec<(usize, usize)>>,
+}
+
+impl Spans {
+    fn count(&self) -> usize {
+        self.description.len() + self.project.len() + self.notes.iter().map(Vec::len).sum::<usize>()
+    }
+}
+
+// a task's text, split into words
+struct Document {
+    description: Vec<(usize, usize, String)>,
+    project: Vec<(usize, usize, String)>,
+    notes: Vec<Vec<(usize, usize, String)>>,
+}
+
+impl Term {
+    // the spans of `text` that the term matches.  a term without any words matches nothing
+    fn matches_in(&self, text: &[(usize, usize, String)]) -> Vec<(usize, usize)> {
+        if self.words.is_empty() {
+            return Vec::new();
+        }
+
+        let last = self.words.len() - 1;
+        text.windows(self.words.len())
+            .filter(|window| {
+                window.iter().zip(&self.words).enumerate().all(|(idx, ((_, _, token), word))| {
+                    if self.prefix && idx == last {
+                        token.starts_with(word.as_str())
+                    } else {
+                        token == word
+                    }
+                })
+            })
+            .map(|window| (window[0].0, window[last].1))
+            .collect()
+    }
+
+    fn spans(&self, document: &Document) -> Spans {
+        let in_column = |column| self.column.is_none_or(|limit| limit == column);
+        Spans {
+            description: match in_column(Column::Description) {
+                true => self.matches_in(&document.description),
+                false => Vec::new(),
+            },
+            project: match in_column(Column::Project) {
+                true => self.matches_in(&document.project),
+                false => Vec::new(),
+            },
+            notes: document
+                .notes
+                .iter()
+                .map(|note| match in_column(Column::Notes) {
+                    true => self.matches_in(note),
+                    false => Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    fn to_fts5(&self) -> String {
+        let column = self.column.map_or(String::new(), |column| format!("{} : ", column.name()));
+        let star = if self.prefix { " *" } else { "" };
+        format!("{}\"{}\"{}", column, self.words.join(" "), star)
+    }
+}
+
+impl Query {
+    // limits every term without a column to `column`
+    fn limit_to(&mut self, column: Column) {
+        match self {
+            Query::Term(term) => {
+                term.column.get_or_insert(column);
+            }
+            Query::And(queries) | Query::Or(queries) => queries.iter_mut().for_each(|query| query.limit_to(column)),
+            Query::Not(query, excluded) => {
+                query.limit_to(column);
+                excluded.limit_to(column);
+            }
+        }
+    }
+
+    fn matches(&self, document: &Document) -> bool {
+        match self {
+            Query::Term(term) => term.spans(document).count() > 0,
+            Query::And(queries) => queries.iter().all(|query| query.matches(document)),
+            Query::Or(queries) => queries.iter().any(|query| query.matches(document)),
+            Query::Not(query, excluded) => query.matches(document) && !excluded.matches(document),
+        }
+    }
+
+    // the terms that a match is made of, which leaves out those after a NOT
+    fn wanted_terms(&self) -> Vec<&Term> {
+        match self {
+            Query::Term(term) => vec![term],
+            Query::And(queries) | Query::Or(queries) => queries.iter().flat_map(Query::wanted_terms).collect(),
+            Query::Not(query, _) => query.wanted_terms(),
+        }
+    }
+
+    // the query in sqlite's fts5 syntax, with every word quoted so that sqlite reads it the same way
+    pub fn to_fts5(&self) -> String {
+        let join = |queries: &[Query], operator: &str| {
+            queries
+                .iter()
+                .map(|query| format!("({})", query.to_fts5()))
+                .collect::<Vec<String>>()
+                .join(operator)
+        };
+        match self {
+            Query::Term(term) => term.to_fts5(),
+            Query::And(queries) => join(queries, " AND "),
+            Query::Or(queries) => join(queries, " OR "),
+            Query::Not(query, excluded) => format!("({}) NOT ({})", query.to_fts5(), excluded.to_fts5()),
+        }
+    }
+}
+
+// reads a query in sqlite's fts5 syntax:  words, "quoted phrases" and prefix* words, combined
+// with AND, OR, NOT and brackets, and limited to a column with `project:`, `description:` or
+// `notes:`.  words next to each other all have to match
+pub fn parse_query(query: &str) -> Result<Query, Error> {
+    let invalid = |reason: String| Error::InvalidQuery {
+        query: query.to_string(),
+        reason,
+    };
+    let mut parser = Parser {
+        tokens: lex(query).map_err(invalid)?,
+        next: 0,
+    };
+    let parsed = parser.or().map_err(invalid)?;
+    match parser.peek() {
+        None => Ok(parsed),
+        Some(_) => Err(invalid("a bracket is closed without being opened".to_string())),
+    }
+}
+
+fn highlight(text: &str, mut spans: Vec<(usize, usize)>) -> String {
+    spans.sort_unstable();
+    let mut highlighted = String::new();
+    let mut copied = 0;
+    for (start, end) in spans {
+        if start < copied {
+            continue;
+        }
+        highlighted.push_str(&text[copied..start]);
+        highlighted.push(MATCH_START);
+        highlighted.push_str(&text[start..end]);
+        highlighted.push(MATCH_END);
+        copied = end;
+    }
+    highlighted.push_str(&text[copied..]);
+
+    highlighted
+}
+
+// a simple search for stores without a full-text index.  words match case insensitively, and the
+// score is the number of matches
+pub fn search_tasks(tasks: Vec<Task>, query: &Query) -> Vec<SearchHit> {
+    let mut hits: Vec<SearchHit> = tasks
+        .into_iter()
+        .filter_map(|task| {
+            let document = Document {
+                description: tokens(&task.description),
+                project: tokens(&task.project_name),
+                notes: task.notes.iter().map(|note| tokens(&note.text)).collect(),
+            };
+            if !query.matches(&document) {
+                return None;
+            }
+
+            let mut spans = Spans {
+                notes: vec![Vec::new(); task.notes.len()],
+                ..Spans::default()
+            };
+            for term in query.wanted_terms() {
+                let term_spans = term.spans(&document);
+                spans.description.extend(term_spans.description);
+                spans.project.extend(term_spans.project);
+                for (note_spans, in_note) in spans.notes.iter_mut().zip(term_spans.notes) {
+                    note_spans.extend(in_note);
+                }
+            }
+
+            Some(SearchHit {
+                score: spans.count() as f64,
+                description: highlight(&task.description, spans.description),
+                project: highlight(&task.project_name, spans.project),
+                notes: task
+                    .notes
+                    .iter()
+                    .zip(spans.notes)
+                    .map(|(note, spans)| highlight(&note.text, spans))
+                    .collect(),
+                task,
+            })
+        })
+        .collect();
+    hits.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::TaskNote;
+    use chrono::{TimeZone, Utc};
+
+    fn hit_descriptions(query: &str) -> Vec<String> {
+        let mut triage = Task::new("bug triage", "work", Utc.ymd(2021, 3, 18).and_hms(11, 0, 0));
+        triage.notes.push(TaskNote {
+            time: Utc.ymd(2021, 3, 18).and_hms(11, 30, 0),
+            text: "closed the stale tickets".to_string(),
+        });
+        let tasks = vec![
+            Task::new("fix the OAuth bug", "work", Utc.ymd(2021, 3, 18).and_hms(9, 0, 0)),
+            Task::new("oauth: review, then fix oauth docs", "client-x", Utc.ymd(2021, 3, 18).and_hms(10, 0, 0)),
+            triage,
+        ];
+
+        search_tasks(tasks, &parse_query(query).unwrap()).into_iter().map(|hit| hit.description).collect()
+    }
+
+    #[test]
+    fn test_queries_match_words_phrases_and_prefixes() {
+        let oauth_docs = "\u{2}oauth\u{3}: review, then fix \u{2}oauth\u{3} docs";
+        assert_eq!(hit_descriptions("OAUTH"), vec![oauth_docs, "fix the \u{2}OAuth\u{3} bug"]);
+        assert_eq!(hit_descriptions("\"fix the oauth\""), vec!["\u{2}fix the OAuth\u{3} bug"]);
+        assert_eq!(hit_descriptions("tri*"), vec!["bug \u{2}triage\u{3}"]);
+        assert_eq!(hit_descriptions("bug work").len(), 2);
+        assert_eq!(hit_descriptions("client x").len(), 1);
+        assert_eq!(hit_descriptions("bug stale"), vec!["\u{2}bug\u{3} triage"]);
+        assert!(hit_descriptions("tri").is_empty());
+        assert!(hit_descriptions("  \"\" ").is_empty());
+    }
+
+    #[test]
+    fn test_queries_combine_with_operators_and_columns() {
+        assert_eq!(hit_descriptions("docs OR triage").len(), 2);
+        assert_eq!(hit_descriptions("bug NOT oauth"), vec!["\u{2}bug\u{3} triage"]);
+        assert_eq!(hit_descriptions("fix AND (docs OR bug)").len(), 2);
+        assert_eq!(hit_descriptions("project:work").len(), 2);
+        assert_eq!(hit_descriptions("project:(client OR nothing)").len(), 1);
+        assert_eq!(hit_descriptions("notes:stale"), vec!["bug triage"]);
+        assert!(hit_descriptions("description:stale").is_empty());
+        // NOT binds tighter than OR
+        assert_eq!(hit_descriptions("triage OR fix NOT docs"), vec!["\u{2}fix\u{3} the OAuth bug", "bug \u{2}triage\u{3}"]);
+    }
+
+    #[test]
+    fn test_queries_are_written_as_fts5() -> Result<(), Error> {
+        assert_eq!(parse_query("oauth")?.to_fts5(), "\"oauth\"");
+        assert_eq!(
+            parse_query("\"Fix the\" bu* OR project:(a NOT b_c)")?.to_fts5(),
+            "((\"fix the\") AND (\"bu\" *)) OR ((project : \"a\") NOT (project : \"b c\"))"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_malformed_queries_are_invalid() {
+        for query in &["client-x", "\"oauth", "NOT oauth", "oauth OR", "(oauth", "oauth)", "team:oauth", "*", "a : b"] {
+            assert!(
+                matches!(parse_query(query), Err(Error::InvalidQuery { .. })),
+                "'{}' should be invalid",
+                query
+            );
+        }
+    }
+}
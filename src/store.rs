@@ -0,0 +1,826 @@
+use crate::data::*;
+use crate::journal::{get_operations, prune_operations, record_operation, set_operation_undone, Change, Operation, ProjectChange};
+use crate::search::{search_tasks, Query, SearchHit};
+use anyhow::{anyhow, Error};
+use chrono::{DateTime, Utc};
+use rusqlite::Connection;
+use std::cell::RefCell;
+use std::path::Path;
+
+// where tasks are kept.  every implementation has to pass `conformance_tests` below
+pub trait TaskStore {
+    // inserts the task, or updates it if it has already been saved.  a different task sharing
+    // its start time is an error rather than being overwritten, unless one of them is in the trash
+    fn save(&self, task: &Task) -> Result<(), Error>;
+
+    // removes the task for good, whether or not it's in the trash
+    fn delete_by_id(&self, task_unique_id: &str) -> Result<(), Error>;
+
+    // finds a task by its exact id, including tasks in the trash.  every other lookup leaves
+    // tasks in the trash out
+    fn find_by_id(&self, task_unique_id: &str) -> Result<Option<Task>, Error>;
+
+    fn all(&self) -> Result<Vec<Task>, Error>;
+
+    fn most_recent_running(&self) -> Result<Option<Task>, Error>;
+
+    fn most_recent_ended(&self) -> Result<Option<Task>, Error>;
+
+    // returns the tasks whose id starts with `prefix`, ordered by start time.  the prefix is
+    // matched literally, with no wildcards
+    fn search_by_id_prefix(&self, prefix: &str) -> Result<Vec<Task>, Error>;
+
+    // returns the id of every task, sorted
+    fn ids(&self) -> Result<Vec<String>, Error>;
+
+    // searches task descriptions, projects and notes for the tasks that `query` matches, best
+    // matches first, with the matches marked in the hits.  tasks in the trash are left out
+    fn search(&self, query: &Query) -> Result<Vec<SearchHit>, Error>;
+
+    // returns the tasks that were worked on at some point between `from` and `to`, ordered by
+    // start time.  running tasks are treated as running up until `now`
+    fn tasks_in_range(&self, from: DateTime<Utc>, to: DateTime<Utc>, now: DateTime<Utc>) -> Result<Vec<Task>, Error>;
+
+    // like `tasks_in_range`, except that tasks sharing `start_time` always overlap since start
+    // times are unique
+    fn overlapping(&self, start_time: DateTime<Utc>, end_time: DateTime<Utc>, now: DateTime<Utc>) -> Result<Vec<Task>, Error>;
+
+    // adds an operation made up of `changes` and `project_changes` to the journal, returning its
+    // id.  operations that were undone are dropped first, as they can't be redone once something
+    // else has changed
+    fn record_operation(
+        &self,
+        command: &str,
+        time: DateTime<Utc>,
+        changes: &[Change],
+        project_changes: &[ProjectChange],
+    ) -> Result<i64, Error>;
+
+    // returns every operation in the journal, oldest first
+    fn operations(&self) -> Result<Vec<Operation>, Error>;
+
+    fn set_operation_undone(&self, id: i64, undone: bool) -> Result<(), Error>;
+
+    // removes all but the newest `keep` operations from the journal, returning how many were
+    // removed.  they can't be undone any more
+    fn prune_operations(&self, keep: usize) -> Result<usize, Error>;
+
+    // returns the tasks in the trash, in the order they were deleted
+    fn trashed(&self) -> Result<Vec<Task>, Error>;
+
+    // returns every project, ordered by name
+    fn projects(&self) -> Result<Vec<Project>, Error>;
+
+    // inserts the project, or updates the project with the same name
+    fn save_project(&self, project: &Project) -> Result<(), Error>;
+
+    // removes the project, leaving any tasks in it as they are
+    fn delete_project(&self, name: &str) -> Result<(), Error>;
+
+    // runs `f`, keeping none of its changes if it fails.  transactions can be nested, in which
+    // case a failing inner transaction only rolls back its own changes
+    fn transaction(&self, f: &mut dyn FnMut() -> Result<(), Error>) -> Result<(), Error>;
+}
+
+pub struct SqliteStore {
+    conn: Connection,
+}
+
+impl SqliteStore {
+    // opens the data store at `path`, creating it if it doesn't exist yet
+    pub fn open(path: &Path) -> Result<SqliteStore, Error> {
+        let conn = Connection::open(path)
+            .map_err(|e| anyhow!("The sqlite connection couldn't be opened with the following error: {}", e))?;
+
+        SqliteStore::new(conn)
+    }
+
+    // wraps an open connection, migrating its data store to the latest version
+    pub fn new(conn: Connection) -> Result<SqliteStore, Error> {
+        set_up_sqlite(&conn)
+            .map_err(|e| anyhow!("The data store for tasks could not be set up with the following error: {}", e))?;
+
+        Ok(SqliteStore { conn })
+    }
+}
+
+impl TaskStore for SqliteStore {
+    fn save(&self, task: &Task) -> Result<(), Error> {
+        task.save_to_db(&self.conn)
+    }
+
+    fn delete_by_id(&self, task_unique_id: &str) -> Result<(), Error> {
+        delete_task_by_id(&self.conn, task_unique_id)
+    }
+
+    fn find_by_id(&self, task_unique_id: &str) -> Result<Option<Task>, Error> {
+        find_task_by_id(&self.conn, task_unique_id)
+    }
+
+    fn all(&self) -> Result<Vec<Task>, Error> {
+        get_all_tasks(&self.conn)
+    }
+
+    fn most_recent_running(&self) -> Result<Option<Task>, Error> {
+        get_most_recent_task(&self.conn)
+    }
+
+    fn most_recent_ended(&self) -> Result<Option<Task>, Error> {
+        get_most_recent_ended_task(&self.conn)
+    }
+
+    fn search_by_id_prefix(&self, prefix: &str) -> Result<Vec<Task>, Error> {
+        get_tasks_with_id_prefix(&self.conn, prefix)
+    }
+
+    fn ids(&self) -> Result<Vec<String>, Error> {
+        get_task_ids(&self.conn)
+    }
+
+    fn search(&self, query: &Query) -> Result<Vec<SearchHit>, Error> {
+        search_task_index(&self.conn, query)
+    }
+
+    fn tasks_in_range(&self, from: DateTime<Utc>, to: DateTime<Utc>, now: DateTime<Utc>) -> Result<Vec<Task>, Error> {
+        get_tasks_in_range(&self.conn, from, to, now)
+    }
+
+    fn overlapping(&self, start_time: DateTime<Utc>, end_time: DateTime<Utc>, now: DateTime<Utc>) -> Result<Vec<Task>, Error> {
+        get_overlapping_tasks(&self.conn, start_time, end_time, now)
+    }
+
+    fn record_operation(
+        &self,
+        command: &str,
+        time: DateTime<Utc>,
+        changes: &[Change],
+        project_changes: &[ProjectChange],
+    ) -> Result<i64, Error> {
+        record_operation(&self.conn, command, time, changes, project_changes)
+    }
+
+    fn operations(&self) -> Result<Vec<Operation>, Error> {
+        get_operations(&self.conn)
+    }
+
+    fn set_operation_undone(&self, id: i64, undone: bool) -> Result<(), Error> {
+        set_operation_undone(&self.conn, id, undone)
+    }
+
+    fn prune_operations(&self, keep: usize) -> Result<usize, Error> {
+        prune_operations(&self.conn, keep)
+    }
+
+    fn trashed(&self) -> Result<Vec<Task>, Error> {
+        get_trashed_tasks(&self.conn)
+    }
+
+    fn projects(&self) -> Result<Vec<Project>, Error> {
+        get_projects(&self.conn)
+    }
+
+    fn save_project(&self, project: &Project) -> Result<(), Error> {
+        project.save_to_db(&self.conn)
+    }
+
+    fn delete_project(&self, name: &str) -> Result<(), Error> {
+        delete_project_by_name(&self.conn, name)
+    }
+
+    // the savepoint is on the same connection, so everything `f` does through `self` is part of
+    // it.  unlike a transaction, savepoints can be nested
+    fn transaction(&self, f: &mut dyn FnMut() -> Result<(), Error>) -> Result<(), Error> {
+        self.conn.execute_batch("SAVEPOINT store_transaction;")?;
+        match f() {
+            Ok(()) => {
+                self.conn.execute_batch("RELEASE store_transaction;")?;
+                Ok(())
+            }
+            Err(err) => {
+                self.conn.execute_batch("ROLLBACK TO store_transaction; RELEASE store_transaction;")?;
+                Err(err)
+            }
+        }
+    }
+}
+
+// keeps tasks in memory, in the order they were first saved.  tasks in the trash stay in the list
+#[derive(Default)]
+pub struct MemoryStore {
+    tasks: RefCell<Vec<Task>>,
+    journal: RefCell<Vec<Operation>>,
+    projects: RefCell<Vec<Project>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+
+    // the tasks that aren't in the trash and match `predicate`, ordered by start time
+    fn matching(&self, predicate: impl Fn(&Task) -> bool) -> Vec<Task> {
+        let mut tasks: Vec<Task> = self
+            .tasks
+            .borrow()
+            .iter()
+            .filter(|task| task.deleted_at.is_none() && predicate(task))
+            .cloned()
+            .collect();
+        tasks.sort_by_key(|task| task.start_time);
+        tasks
+    }
+}
+
+impl TaskStore for MemoryStore {
+    fn save(&self, task: &Task) -> Result<(), Error> {
+        let mut tasks = self.tasks.borrow_mut();
+
+        if task.deleted_at.is_none()
+            && tasks
+                .iter()
+                .any(|t| t.start_time == task.start_time && t.unique_id != task.unique_id && t.deleted_at.is_none())
+        {
+            return Err(anyhow!("UNIQUE constraint failed: tasks.start_time"));
+        }
+
+        let mut task = task.clone();
+        task.tags.sort();
+        task.breaks.sort_by_key(|b| b.start_time);
+        match tasks.iter_mut().find(|t| t.unique_id == task.unique_id) {
+            Some(saved) => *saved = task,
+            None => tasks.push(task),
+        }
+
+        Ok(())
+    }
+
+    fn delete_by_id(&self, task_unique_id: &str) -> Result<(), Error> {
+        self.tasks.borrow_mut().retain(|task| task.unique_id != task_unique_id);
+        Ok(())
+    }
+
+    fn find_by_id(&self, task_unique_id: &str) -> Result<Option<Task>, Error> {
+        Ok(self.tasks.borrow().iter().find(|task| task.unique_id == task_unique_id).cloned())
+    }
+
+    fn all(&self) -> Result<Vec<Task>, Error> {
+        Ok(self.matching(|_| true))
+    }
+
+    fn most_recent_running(&self) -> Result<Option<Task>, Error> {
+        Ok(self.tasks.borrow().iter().rev().find(|task| task.is_running() && task.deleted_at.is_none()).cloned())
+    }
+
+    // ties on the end time go to the task saved last
+    fn most_recent_ended(&self) -> Result<Option<Task>, Error> {
+        let tasks = self.tasks.borrow();
+        let most_recent = tasks
+            .iter()
+            .enumerate()
+            .filter(|(_, task)| !task.is_running() && task.deleted_at.is_none())
+            .max_by_key(|(idx, task)| (task.end_time, *idx))
+            .map(|(_, task)| task.clone());
+
+        Ok(most_recent)
+    }
+
+    fn search_by_id_prefix(&self, prefix: &str) -> Result<Vec<Task>, Error> {
+        Ok(self.matching(|task| task.unique_id.starts_with(prefix)))
+    }
+
+    fn ids(&self) -> Result<Vec<String>, Error> {
+        let mut ids: Vec<String> = self.matching(|_| true).into_iter().map(|task| task.unique_id).collect();
+        ids.sort();
+
+        Ok(ids)
+    }
+
+    fn search(&self, query: &Query) -> Result<Vec<SearchHit>, Error> {
+        Ok(search_tasks(self.matching(|_| true), query))
+    }
+
+    fn tasks_in_range(&self, from: DateTime<Utc>, to: DateTime<Utc>, now: DateTime<Utc>) -> Result<Vec<Task>, Error> {
+        Ok(self.matching(|task| task.start_time < to && task.end_time.unwrap_or(now) > from))
+    }
+
+    fn overlapping(&self, start_time: DateTime<Utc>, end_time: DateTime<Utc>, now: DateTime<Utc>) -> Result<Vec<Task>, Error> {
+        Ok(self.matching(|task| {
+            (task.start_time < end_time && task.end_time.unwrap_or(now) > start_time) || task.start_time == start_time
+        }))
+    }
+
+    fn record_operation(
+        &self,
+        command: &str,
+        time: DateTime<Utc>,
+        changes: &[Change],
+        project_changes: &[ProjectChange],
+    ) -> Result<i64, Error> {
+        let mut journal = self.journal.borrow_mut();
+        journal.retain(|operation| !operation.undone);
+        let id = journal.last().map_or(1, |operation| operation.id + 1);
+        journal.push(Operation {
+            id,
+            command: command.to_string(),
+            time,
+            changes: changes.to_vec(),
+            project_changes: project_changes.to_vec(),
+            undone: false,
+        });
+
+        Ok(id)
+    }
+
+    fn operations(&self) -> Result<Vec<Operation>, Error> {
+        Ok(self.journal.borrow().clone())
+    }
+
+    fn set_operation_undone(&self, id: i64, undone: bool) -> Result<(), Error> {
+        for operation in self.journal.borrow_mut().iter_mut().filter(|operation| operation.id == id) {
+            operation.undone = undone;
+        }
+
+        Ok(())
+    }
+
+    fn prune_operations(&self, keep: usize) -> Result<usize, Error> {
+        let mut journal = self.journal.borrow_mut();
+        let removed = journal.len().saturating_sub(keep);
+        journal.drain(..removed);
+
+        Ok(removed)
+    }
+
+    fn trashed(&self) -> Result<Vec<Task>, Error> {
+        let mut tasks: Vec<Task> = self.tasks.borrow().iter().filter(|task| task.deleted_at.is_some()).cloned().collect();
+        tasks.sort_by_key(|task| (task.deleted_at, task.start_time));
+
+        Ok(tasks)
+    }
+
+    fn projects(&self) -> Result<Vec<Project>, Error> {
+        let mut projects = self.projects.borrow().clone();
+        projects.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(projects)
+    }
+
+    fn save_project(&self, project: &Project) -> Result<(), Error> {
+        let mut projects = self.projects.borrow_mut();
+        match projects.iter_mut().find(|p| p.name == project.name) {
+            Some(stored) => *stored = project.clone(),
+            None => projects.push(project.clone()),
+        }
+
+        Ok(())
+    }
+
+    fn delete_project(&self, name: &str) -> Result<(), Error> {
+        self.projects.borrow_mut().retain(|project| project.name != name);
+
+        Ok(())
+    }
+
+    fn transaction(&self, f: &mut dyn FnMut() -> Result<(), Error>) -> Result<(), Error> {
+        let snapshot = (self.tasks.borrow().clone(), self.journal.borrow().clone(), self.projects.borrow().clone());
+        let result = f();
+        if result.is_err() {
+            *self.tasks.borrow_mut() = snapshot.0;
+            *self.journal.borrow_mut() = snapshot.1;
+            *self.projects.borrow_mut() = snapshot.2;
+        }
+
+        result
+    }
+}
+
+// the behaviour every `TaskStore` has to share.  each test takes an empty store
+#[cfg(test)]
+mod conformance_tests {
+    use super::*;
+    use crate::search::parse_query;
+    use chrono::{Duration, TimeZone};
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        Utc.ymd(2021, 3, 18).and_hms(hour, 0, 0)
+    }
+
+    fn ended_task(description: &str, start: DateTime<Utc>, end: DateTime<Utc>) -> Task {
+        let mut task = Task::new(description, "work", start);
+        task.end_task(end);
+        task
+    }
+
+    fn search(store: &dyn TaskStore, query: &str) -> Result<Vec<SearchHit>, Error> {
+        store.search(&parse_query(query)?)
+    }
+
+    fn sorted_ids(tasks: Vec<Task>) -> Vec<String> {
+        let mut ids: Vec<String> = tasks.into_iter().map(|task| task.unique_id).collect();
+        ids.sort();
+        ids
+    }
+
+    fn save_round_trips(store: &dyn TaskStore) -> Result<(), Error> {
+        let mut task = ended_task("write tests", at(9), at(11));
+        task.tags = vec!["billable".to_string(), "testing".to_string()];
+        task.breaks = vec![TaskBreak {
+            start_time: at(10),
+            end_time: Some(at(10) + Duration::minutes(15)),
+        }];
+        store.save(&task)?;
+
+        assert_eq!(store.find_by_id(&task.unique_id)?, Some(task.clone()));
+        assert_eq!(store.all()?, vec![task]);
+        assert_eq!(store.find_by_id("missing")?, None);
+        Ok(())
+    }
+
+    fn save_updates_by_id(store: &dyn TaskStore) -> Result<(), Error> {
+        let mut task = ended_task("write tests", at(9), at(11));
+        store.save(&task)?;
+
+        task.start_time = at(8);
+        task.description = "write more tests".to_string();
+        task.tags = vec!["testing".to_string()];
+        store.save(&task)?;
+
+        assert_eq!(store.all()?, vec![task]);
+        Ok(())
+    }
+
+    fn save_refuses_a_shared_start_time(store: &dyn TaskStore) -> Result<(), Error> {
+        let existing = ended_task("existing", at(9), at(10));
+        store.save(&existing)?;
+
+        assert!(store.save(&ended_task("clash", at(9), at(11))).is_err());
+        assert_eq!(store.all()?, vec![existing]);
+        Ok(())
+    }
+
+    fn delete_removes_only_that_task(store: &dyn TaskStore) -> Result<(), Error> {
+        let kept = ended_task("kept", at(9), at(10));
+        let deleted = ended_task("deleted", at(10), at(11));
+        store.save(&kept)?;
+        store.save(&deleted)?;
+
+        store.delete_by_id(&deleted.unique_id)?;
+        store.delete_by_id("missing")?;
+
+        assert_eq!(store.all()?, vec![kept]);
+        Ok(())
+    }
+
+    fn most_recent_running_ignores_later_logged_tasks(store: &dyn TaskStore) -> Result<(), Error> {
+        assert_eq!(store.most_recent_running()?, None);
+
+        let running = Task::new("running", "work", at(12));
+        store.save(&running)?;
+        store.save(&ended_task("logged", at(9), at(10)))?;
+
+        assert_eq!(store.most_recent_running()?, Some(running));
+        Ok(())
+    }
+
+    fn most_recent_ended_goes_by_end_time(store: &dyn TaskStore) -> Result<(), Error> {
+        assert_eq!(store.most_recent_ended()?, None);
+
+        let later = ended_task("later", at(10), at(11));
+        store.save(&later)?;
+        store.save(&ended_task("earlier", at(8), at(9)))?;
+        store.save(&Task::new("running", "work", at(12)))?;
+
+        assert_eq!(store.most_recent_ended()?, Some(later));
+        Ok(())
+    }
+
+    fn search_matches_id_prefixes(store: &dyn TaskStore) -> Result<(), Error> {
+        let mut first = ended_task("first", at(9), at(10));
+        first.unique_id = "abc-1".to_string();
+        let mut second = ended_task("second", at(10), at(11));
+        second.unique_id = "abc-2".to_string();
+        let mut other = ended_task("other", at(11), at(12));
+        other.unique_id = "xyz-1".to_string();
+        for task in &[&first, &second, &other] {
+            store.save(task)?;
+        }
+
+        assert_eq!(sorted_ids(store.search_by_id_prefix("abc")?), vec!["abc-1", "abc-2"]);
+        assert_eq!(sorted_ids(store.search_by_id_prefix("abc-2")?), vec!["abc-2"]);
+        assert!(store.search_by_id_prefix("nothing")?.is_empty());
+        // only the start of an id is matched, and wildcards and quotes are just characters
+        assert!(store.search_by_id_prefix("bc")?.is_empty());
+        for prefix in &["%", "_bc", "abc%", "' OR '1'='1", "abc' --"] {
+            assert!(store.search_by_id_prefix(prefix)?.is_empty(), "'{}' shouldn't match", prefix);
+        }
+        assert_eq!(store.ids()?, vec!["abc-1", "abc-2", "xyz-1"]);
+        Ok(())
+    }
+
+    fn search_matches_words_phrases_and_prefixes(store: &dyn TaskStore) -> Result<(), Error> {
+        let bug = ended_task("fix the OAuth bug", at(9), at(10));
+        let mut docs = ended_task("write oauth docs", at(10), at(11));
+        docs.project_name = "client".to_string();
+        let mut trashed = ended_task("oauth cleanup", at(11), at(12));
+        trashed.deleted_at = Some(at(12));
+        for task in &[&bug, &docs, &trashed] {
+            store.save(task)?;
+        }
+
+        let hits = search(store, "oauth")?;
+        assert_eq!(sorted_ids(hits.iter().map(|hit| hit.task.clone()).collect()), sorted_ids(vec![bug.clone(), docs.clone()]));
+        assert!(hits.iter().all(|hit| hit.score > 0.0));
+
+        let hits = search(store, "\"the oauth\" bu*")?;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].task, bug);
+        assert_eq!(hits[0].description, "fix \u{2}the OAuth\u{3} \u{2}bug\u{3}");
+        assert_eq!(hits[0].project, "work");
+
+        let hits = search(store, "client")?;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].project, "\u{2}client\u{3}");
+        assert!(search(store, "oaut")?.is_empty());
+
+        // the index follows changes to tasks
+        let mut renamed = bug;
+        renamed.description = "fix the login bug".to_string();
+        store.save(&renamed)?;
+        store.delete_by_id(&docs.unique_id)?;
+        assert!(search(store, "oauth")?.is_empty());
+        assert_eq!(search(store, "login")?[0].task, renamed);
+
+        // and to their notes
+        let mut noted = renamed;
+        noted.notes.push(TaskNote {
+            time: at(10),
+            text: "the session cookie expired".to_string(),
+        });
+        store.save(&noted)?;
+        let hits = search(store, "login cookie")?;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].notes, vec!["the session \u{2}cookie\u{3} expired"]);
+        Ok(())
+    }
+
+    fn search_understands_operators_and_columns(store: &dyn TaskStore) -> Result<(), Error> {
+        let mut bug = ended_task("fix the oauth bug", at(9), at(10));
+        bug.notes.push(TaskNote {
+            time: at(10),
+            text: "asked alice about the client".to_string(),
+        });
+        let mut docs = ended_task("write oauth docs", at(10), at(11));
+        docs.project_name = "client".to_string();
+        let lunch = ended_task("lunch", at(11), at(12));
+        for task in &[&bug, &docs, &lunch] {
+            store.save(task)?;
+        }
+
+        let ids = |query: &str| -> Result<Vec<String>, Error> { Ok(sorted_ids(search(store, query)?.into_iter().map(|hit| hit.task).collect())) };
+        assert_eq!(ids("docs OR lunch")?, sorted_ids(vec![docs.clone(), lunch.clone()]));
+        assert_eq!(ids("oauth NOT docs")?, sorted_ids(vec![bug.clone()]));
+        assert_eq!(ids("client")?, sorted_ids(vec![bug.clone(), docs.clone()]));
+        assert_eq!(ids("project:client")?, sorted_ids(vec![docs.clone()]));
+        assert_eq!(ids("notes:(alice OR bob)")?, sorted_ids(vec![bug.clone()]));
+        assert_eq!(ids("lunch OR oauth NOT (docs OR notes:alice)")?, sorted_ids(vec![lunch]));
+        assert_eq!(ids("wri* AND \"oauth docs\"")?, sorted_ids(vec![docs]));
+
+        // words after a NOT aren't marked
+        let hits = search(store, "oauth NOT lunch")?;
+        assert!(hits.iter().all(|hit| !hit.description.contains("lunch")));
+        assert!(hits.iter().any(|hit| hit.description == "fix the \u{2}oauth\u{3} bug"));
+        Ok(())
+    }
+
+    fn tasks_in_range_are_clipped_and_ordered(store: &dyn TaskStore) -> Result<(), Error> {
+        let before = ended_task("before", at(6), at(8));
+        let crossing = ended_task("crossing", at(9), at(11));
+        let inside = ended_task("inside", at(11), at(12));
+        let running = Task::new("running", "work", at(13));
+        for task in &[&running, &inside, &before, &crossing] {
+            store.save(task)?;
+        }
+
+        assert_eq!(store.tasks_in_range(at(10), at(14), at(15))?, vec![crossing, inside, running.clone()]);
+        assert_eq!(store.tasks_in_range(at(8), at(9), at(15))?, vec![]);
+        assert_eq!(store.tasks_in_range(at(14), at(16), at(15))?, vec![running]);
+        Ok(())
+    }
+
+    fn overlapping_includes_shared_start_times(store: &dyn TaskStore) -> Result<(), Error> {
+        let existing = ended_task("existing", at(9), at(10));
+        let instant = ended_task("instant", at(12), at(12));
+        store.save(&existing)?;
+        store.save(&instant)?;
+
+        assert_eq!(store.overlapping(at(9), at(9), at(15))?, vec![existing.clone()]);
+        assert_eq!(store.overlapping(at(8), at(13), at(15))?, vec![existing, instant]);
+        assert!(store.overlapping(at(10), at(11), at(15))?.is_empty());
+        Ok(())
+    }
+
+    fn failed_transactions_are_rolled_back(store: &dyn TaskStore) -> Result<(), Error> {
+        let kept = ended_task("kept", at(9), at(10));
+        store.save(&kept)?;
+
+        let result = store.transaction(&mut || {
+            store.save(&ended_task("rolled back", at(11), at(12)))?;
+            store.delete_by_id(&kept.unique_id)?;
+            Err(anyhow!("failed"))
+        });
+        assert!(result.is_err());
+        assert_eq!(store.all()?, vec![kept.clone()]);
+
+        let committed = ended_task("committed", at(11), at(12));
+        store.transaction(&mut || store.save(&committed))?;
+        assert_eq!(store.all()?, vec![kept, committed.clone()]);
+        Ok(())
+    }
+
+    fn nested_transactions_roll_back_separately(store: &dyn TaskStore) -> Result<(), Error> {
+        let outer = ended_task("outer", at(9), at(10));
+        let inner = ended_task("inner", at(11), at(12));
+
+        store.transaction(&mut || {
+            store.save(&outer)?;
+            let result = store.transaction(&mut || {
+                store.save(&inner)?;
+                Err(anyhow!("failed"))
+            });
+            assert!(result.is_err());
+            Ok(())
+        })?;
+
+        assert_eq!(store.all()?, vec![outer.clone()]);
+        Ok(())
+    }
+
+    fn journal_keeps_operations_in_order(store: &dyn TaskStore) -> Result<(), Error> {
+        let task = ended_task("task", at(9), at(10));
+        let added = Change {
+            task_unique_id: task.unique_id.clone(),
+            before: None,
+            after: Some(task.clone()),
+        };
+        let removed = Change {
+            task_unique_id: task.unique_id.clone(),
+            before: Some(task.clone()),
+            after: None,
+        };
+
+        let project_added = ProjectChange {
+            name: "work".to_string(),
+            before: None,
+            after: Some(Project::new("work", at(9))),
+        };
+
+        let first = store.record_operation("log", at(10), std::slice::from_ref(&added), &[])?;
+        let second = store.record_operation("delete", at(11), &[removed.clone(), added.clone()], &[])?;
+        assert!(second > first);
+
+        let operations = store.operations()?;
+        assert_eq!(operations.len(), 2);
+        assert_eq!(operations[0].command, "log");
+        assert_eq!(operations[0].time, at(10));
+        assert_eq!(operations[1].changes, vec![removed, added.clone()]);
+        assert!(operations[1].project_changes.is_empty());
+        assert!(!operations[1].undone);
+
+        // recording drops the operations that were undone
+        store.set_operation_undone(second, true)?;
+        assert!(store.operations()?[1].undone);
+        let third = store.record_operation("log", at(12), &[added], &[])?;
+        let ids: Vec<i64> = store.operations()?.iter().map(|operation| operation.id).collect();
+        assert_eq!(ids, vec![first, third]);
+
+        // an operation can change projects alone
+        let fourth = store.record_operation("project add", at(13), &[], std::slice::from_ref(&project_added))?;
+        let operations = store.operations()?;
+        assert_eq!(operations[2].id, fourth);
+        assert!(operations[2].changes.is_empty());
+        assert_eq!(operations[2].project_changes, vec![project_added]);
+        Ok(())
+    }
+
+    fn pruning_keeps_the_newest_operations(store: &dyn TaskStore) -> Result<(), Error> {
+        assert_eq!(store.prune_operations(0)?, 0);
+
+        let task = ended_task("task", at(9), at(10));
+        let added = Change {
+            task_unique_id: task.unique_id.clone(),
+            before: None,
+            after: Some(task.clone()),
+        };
+        let mut ids = Vec::new();
+        for hour in 10..14 {
+            ids.push(store.record_operation("log", at(hour), &[added.clone(), added.clone()], &[])?);
+        }
+
+        assert_eq!(store.prune_operations(5)?, 0);
+        assert_eq!(store.prune_operations(2)?, 2);
+        let kept: Vec<i64> = store.operations()?.iter().map(|operation| operation.id).collect();
+        assert_eq!(kept, ids[2..].to_vec());
+        assert_eq!(store.operations()?[0].changes.len(), 2);
+
+        // new operations carry on after the ones that were kept
+        let next = store.record_operation("log", at(14), &[added], &[])?;
+        assert!(next > ids[3]);
+        assert_eq!(store.prune_operations(0)?, 3);
+        assert!(store.operations()?.is_empty());
+        Ok(())
+    }
+
+    fn projects_are_saved_by_name(store: &dyn TaskStore) -> Result<(), Error> {
+        let mut work = Project::new("work", at(9));
+        store.save_project(&work)?;
+        store.save_project(&Project::new("client", at(10)))?;
+
+        work.description = "the day job".to_string();
+        work.color = Some("blue".to_string());
+        work.archived = true;
+        store.save_project(&work)?;
+        let names: Vec<String> = store.projects()?.into_iter().map(|project| project.name).collect();
+        assert_eq!(names, vec!["client", "work"]);
+
+        store.delete_project("client")?;
+        assert_eq!(store.projects()?, vec![work.clone()]);
+
+        // a failed transaction leaves projects as they were
+        let result = store.transaction(&mut || {
+            store.delete_project("work")?;
+            Err(anyhow!("failed"))
+        });
+        assert!(result.is_err());
+        assert_eq!(store.projects()?, vec![work.clone()]);
+        Ok(())
+    }
+
+    fn trashed_tasks_are_only_found_by_id(store: &dyn TaskStore) -> Result<(), Error> {
+        let mut trashed = ended_task("trashed", at(9), at(10));
+        trashed.unique_id = "abc-1".to_string();
+        trashed.deleted_at = Some(at(12));
+        let mut earlier = Task::new("running", "work", at(8));
+        earlier.deleted_at = Some(at(11));
+        store.save(&trashed)?;
+        store.save(&earlier)?;
+
+        assert_eq!(store.find_by_id(&trashed.unique_id)?, Some(trashed.clone()));
+        assert!(store.all()?.is_empty());
+        assert!(store.ids()?.is_empty());
+        assert!(store.search_by_id_prefix("abc")?.is_empty());
+        assert!(store.tasks_in_range(at(0), at(23), at(23))?.is_empty());
+        assert!(store.overlapping(at(9), at(10), at(23))?.is_empty());
+        assert_eq!(store.most_recent_running()?, None);
+        assert_eq!(store.most_recent_ended()?, None);
+        assert_eq!(store.trashed()?, vec![earlier, trashed.clone()]);
+
+        // a task in the trash doesn't hold on to its start time
+        let replacement = ended_task("replacement", at(9), at(10));
+        store.save(&replacement)?;
+        assert_eq!(store.all()?, vec![replacement]);
+
+        trashed.deleted_at = None;
+        assert!(store.save(&trashed).is_err());
+        Ok(())
+    }
+
+    type ConformanceTest = fn(&dyn TaskStore) -> Result<(), Error>;
+
+    fn run_all(new_store: impl Fn() -> Result<Box<dyn TaskStore>, Error>) -> Result<(), Error> {
+        let tests: Vec<(&str, ConformanceTest)> = vec![
+            ("save_round_trips", save_round_trips),
+            ("save_updates_by_id", save_updates_by_id),
+            ("save_refuses_a_shared_start_time", save_refuses_a_shared_start_time),
+            ("delete_removes_only_that_task", delete_removes_only_that_task),
+            ("most_recent_running_ignores_later_logged_tasks", most_recent_running_ignores_later_logged_tasks),
+            ("most_recent_ended_goes_by_end_time", most_recent_ended_goes_by_end_time),
+            ("search_matches_id_prefixes", search_matches_id_prefixes),
+            ("search_matches_words_phrases_and_prefixes", search_matches_words_phrases_and_prefixes),
+            ("search_understands_operators_and_columns", search_understands_operators_and_columns),
+            ("tasks_in_range_are_clipped_and_ordered", tasks_in_range_are_clipped_and_ordered),
+            ("overlapping_includes_shared_start_times", overlapping_includes_shared_start_times),
+            ("failed_transactions_are_rolled_back", failed_transactions_are_rolled_back),
+            ("nested_transactions_roll_back_separately", nested_transactions_roll_back_separately),
+            ("projects_are_saved_by_name", projects_are_saved_by_name),
+            ("journal_keeps_operations_in_order", journal_keeps_operations_in_order),
+            ("pruning_keeps_the_newest_operations", pruning_keeps_the_newest_operations),
+            ("trashed_tasks_are_only_found_by_id", trashed_tasks_are_only_found_by_id),
+        ];
+
+        for (name, test) in tests {
+            test(new_store()?.as_ref()).map_err(|err| anyhow!("{} failed: {}", name, err))?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sqlite_store_conforms() -> Result<(), Error> {
+        run_all(|| Ok(Box::new(SqliteStore::new(Connection::open_in_memory()?)?)))
+    }
+
+    #[test]
+    fn test_memory_store_conforms() -> Result<(), Error> {
+        run_all(|| Ok(Box::new(MemoryStore::new())))
+    }
+}
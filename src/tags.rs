@@ -0,0 +1,74 @@
+use anyhow::{anyhow, Error};
+
+// tags are stored comma separated when they're read back alongside a task, so they can't contain commas
+pub fn normalize_tag(tag: &str) -> Result<String, Error> {
+    let tag = tag.trim().trim_start_matches('+');
+
+    if tag.is_empty() || tag.contains(',') || tag.contains(char::is_whitespace) {
+        return Err(anyhow!(
+            "'{}' is an illegal tag, tags can't be empty or contain commas or spaces",
+            tag
+        ));
+    }
+
+    Ok(tag.to_string())
+}
+
+// sorts and removes duplicate tags so that tasks always carry them in the same order
+pub fn normalize_tags(tags: &[String]) -> Result<Vec<String>, Error> {
+    let mut normalized = tags.iter().map(|t| normalize_tag(t)).collect::<Result<Vec<String>, Error>>()?;
+    normalized.sort();
+    normalized.dedup();
+
+    Ok(normalized)
+}
+
+// splits `+tag` words out of a description, returning the remaining description and the tags
+pub fn parse_description_tags(description: &str) -> (String, Vec<String>) {
+    let mut words = Vec::new();
+    let mut tags = Vec::new();
+
+    for word in description.split_whitespace() {
+        match normalize_tag(word) {
+            Ok(tag) if word.starts_with('+') => tags.push(tag),
+            _ => words.push(word),
+        }
+    }
+
+    (words.join(" "), tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_description_tags() {
+        let (description, tags) = parse_description_tags("weekly sync +meeting +billable");
+        assert_eq!(description, "weekly sync");
+        assert_eq!(tags, vec!["meeting".to_string(), "billable".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_description_tags_ignores_lone_plus() {
+        let (description, tags) = parse_description_tags("1 + 1 = +2");
+        assert_eq!(description, "1 + 1 =");
+        assert_eq!(tags, vec!["2".to_string()]);
+    }
+
+    #[test]
+    fn test_normalize_tags() {
+        let tags = vec!["+meeting".to_string(), "billable".to_string(), "meeting".to_string()];
+        assert_eq!(
+            normalize_tags(&tags).unwrap(),
+            vec!["billable".to_string(), "meeting".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_illegal_tags_are_rejected() {
+        assert!(normalize_tag("+").is_err());
+        assert!(normalize_tag("a,b").is_err());
+        assert!(normalize_tag("a b").is_err());
+    }
+}
@@ -1,241 +1,352 @@
 extern crate chrono;
+use crate::time_expr::parse_time_expression;
 use anyhow::anyhow;
 use chrono::prelude::*;
-use chrono::Duration;
-use regex::Regex;
-
-static LENGTH_OF_FULL_TIMESTAMP: usize = 8;
-
-pub fn convert_to_local_timestamp(
-    utc_date_time: &str,
-    should_display_date: bool,
-) -> Result<String, anyhow::Error> {
-    let parsed_end_time = NaiveDateTime::parse_from_str(utc_date_time, "%Y-%m-%d %H:%M:%S")?;
-    let end_dt = DateTime::<Utc>::from_utc(parsed_end_time, Utc);
-    let converted_date_time = DateTime::<Local>::from(end_dt);
-    let date_format = match should_display_date {
-        true => "%Y-%m-%d %H:%M:%S",
-        false => "%H:%M:%S",
-    };
+use chrono::{Duration, LocalResult};
+use std::str::FromStr;
 
-    Ok(converted_date_time.format(date_format).to_string())
+// the source of the current time.  everything that needs to know the time asks a clock rather
+// than the system, so that tests can fix it
+pub trait Clock {
+    fn now(&self) -> DateTime<Utc>;
 }
 
-pub fn convert_to_utc_timestr(local_date_time: &str) -> Result<String, anyhow::Error> {
-    let modified_ldt = if local_date_time.len() < LENGTH_OF_FULL_TIMESTAMP {
-        local_date_time.to_owned() + ":00"
-    } else {
-        local_date_time.to_string()
-    };
+pub struct SystemClock;
+
+// timestamps are persisted with second precision, so sub-second precision is dropped
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        let now = Utc::now();
+        now.with_nanosecond(0).unwrap_or(now)
+    }
+}
+
+// a clock that is always at the same time
+pub struct FixedClock(pub DateTime<Utc>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
 
-    match is_valid_timestr(&modified_ldt) {
-        true => {
-            let now_str = Local::now().format("%H:%M:%S").to_string();
-            let date_to_add = if is_time_yesterday(&now_str, &modified_ldt) {
-                let dt = Local::now() - Duration::days(1);
-                dt.format("%Y-%m-%d ").to_string()
-            } else {
-                Local::now().format("%Y-%m-%d ").to_string()
-            };
+// a timezone with daylight saving, an hour ahead of utc in winter and two in summer.  in 2021 the
+// clocks go forward from 02:00 to 03:00 on 03-28 and back from 03:00 to 02:00 on 10-31
+#[cfg(test)]
+#[derive(Clone, Copy, Debug)]
+pub struct DstZone;
+
+#[cfg(test)]
+impl TimeZone for DstZone {
+    type Offset = FixedOffset;
 
-            let concat_str: String = date_to_add + &modified_ldt;
-            let parsed_local_time =
-                NaiveDateTime::parse_from_str(&concat_str, "%Y-%m-%d %H:%M:%S")?;
+    fn from_offset(_offset: &FixedOffset) -> DstZone {
+        DstZone
+    }
 
-            let start_dt = Local::from_local_datetime(&Local, &parsed_local_time);
-            let converted_date_time = DateTime::<Utc>::from(start_dt.unwrap());
+    fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+        self.offset_from_local_datetime(&local.and_hms(0, 0, 0))
+    }
 
-            Ok(converted_date_time.format("%Y-%m-%d %H:%M:%S").to_string())
+    // the offsets are ordered so that the earliest instant comes first, as chrono expects
+    fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+        let offsets: Vec<FixedOffset> = vec![FixedOffset::east(7200), FixedOffset::east(3600)]
+            .into_iter()
+            .filter(|offset| self.offset_from_utc_datetime(&(*local - *offset)) == *offset)
+            .collect();
+
+        match offsets.as_slice() {
+            [offset] => LocalResult::Single(*offset),
+            [earliest, latest] => LocalResult::Ambiguous(*earliest, *latest),
+            _ => LocalResult::None,
         }
-        false => return Err(anyhow!("time specified is an illegal timestamp, timestamp should be of the format HH:MM:SS")),
     }
-}
 
-pub fn get_local_datetime() -> String {
-    Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+    fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+        self.offset_from_utc_datetime(&utc.and_hms(0, 0, 0))
+    }
+
+    fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+        let summer_start = NaiveDate::from_ymd(2021, 3, 28).and_hms(1, 0, 0);
+        let summer_end = NaiveDate::from_ymd(2021, 10, 31).and_hms(1, 0, 0);
+
+        if *utc >= summer_start && *utc < summer_end {
+            FixedOffset::east(7200)
+        } else {
+            FixedOffset::east(3600)
+        }
+    }
 }
 
-pub fn get_current_utc_string() -> String {
-    Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()
+// how to resolve a local time that happens twice because the clocks went back
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AmbiguousTimePolicy {
+    Earliest,
+    Latest,
+    Error,
 }
 
-pub fn get_time_between_stamps(begin_stamp: &str, end_stamp: &str) -> Result<Duration, anyhow::Error> {
-    let beg_date_time = NaiveDateTime::parse_from_str(begin_stamp, "%Y-%m-%d %H:%M:%S")?;
-    let end_date_time = NaiveDateTime::parse_from_str(end_stamp, "%Y-%m-%d %H:%M:%S")?;
-    return Ok(end_date_time - beg_date_time);
+impl FromStr for AmbiguousTimePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy {
+            "earliest" => Ok(AmbiguousTimePolicy::Earliest),
+            "latest" => Ok(AmbiguousTimePolicy::Latest),
+            "error" => Ok(AmbiguousTimePolicy::Error),
+            _ => Err(anyhow!("'{}' is not a supported policy for ambiguous times;  use earliest, latest or error", policy)),
+        }
+    }
 }
 
-pub fn get_todays_date() -> String {
-    let now_time = Local::now();
-    now_time.format("%Y-%m-%d").to_string()
+// formats a time as rfc3339 with its local offset, such as 2021-10-31T02:30:00+02:00
+fn with_offset<Tz: TimeZone>(date_time: &DateTime<Tz>) -> String {
+    date_time
+        .with_timezone(&date_time.offset().fix())
+        .to_rfc3339_opts(SecondsFormat::Secs, false)
 }
 
-fn is_time_yesterday(now_time: &str, compared_time: &str) -> bool {
-    let mut now_split: Vec<&str> = now_time.split(':').collect();
-    let mut compared_split: Vec<&str> = compared_time.split(':').collect();
+// finds the times that show `local` on the clock.  chrono's `Local` moves times in a gap and picks
+// one of a repeated time rather than reporting them, so the candidates are worked out from the
+// offsets in force a day either side instead, which covers any one daylight saving change
+fn find_local<Tz: TimeZone>(local: &NaiveDateTime, tz: &Tz) -> LocalResult<DateTime<Tz>> {
+    let mut candidates: Vec<DateTime<Tz>> = Vec::new();
+
+    for around in &[*local - Duration::days(1), *local + Duration::days(1)] {
+        let offset = tz.offset_from_utc_datetime(around).fix();
+        let candidate = tz.from_utc_datetime(&(*local - offset));
+        if candidate.naive_local() == *local && !candidates.contains(&candidate) {
+            candidates.push(candidate);
+        }
+    }
+    candidates.sort_by_key(|candidate| candidate.timestamp());
 
-    if now_split.len() < compared_split.len() {
-        now_split.push("00");
+    match candidates.as_slice() {
+        [single] => LocalResult::Single(single.clone()),
+        [earliest, latest] => LocalResult::Ambiguous(earliest.clone(), latest.clone()),
+        _ => LocalResult::None,
     }
+}
 
-    if compared_split.len() < now_split.len() {
-        compared_split.push("00");
+// converts a local date time to utc.  local times skipped over when the clocks go forward are
+// always an error, and ones repeated when the clocks go back are resolved by `policy`
+pub fn convert_local_to_utc<Tz: TimeZone>(
+    local: &NaiveDateTime,
+    tz: &Tz,
+    policy: AmbiguousTimePolicy,
+) -> Result<DateTime<Utc>, anyhow::Error> {
+    match (find_local(local, tz), policy) {
+        (LocalResult::Single(date_time), _) => Ok(date_time.with_timezone(&Utc)),
+        (LocalResult::Ambiguous(earliest, _), AmbiguousTimePolicy::Earliest) => Ok(earliest.with_timezone(&Utc)),
+        (LocalResult::Ambiguous(_, latest), AmbiguousTimePolicy::Latest) => Ok(latest.with_timezone(&Utc)),
+        (LocalResult::Ambiguous(earliest, latest), AmbiguousTimePolicy::Error) => Err(anyhow!(
+            "{} happens twice in the local timezone as the clocks go back.  give the time with its offset, such as '{}' or '{}', or set --ambiguous-time to earliest or latest",
+            local,
+            with_offset(&earliest),
+            with_offset(&latest)
+        )),
+        (LocalResult::None, _) => Err(anyhow!(
+            "{} doesn't exist in the local timezone as the clocks go forward over it.  give a time outside of the gap",
+            local
+        )),
     }
+}
+
+pub fn convert_to_local_timestamp(utc_date_time: &DateTime<Utc>, tz: &impl TimeZone, should_display_date: bool) -> String {
+    let converted_date_time = utc_date_time.with_timezone(tz).naive_local();
+    let date_format = match should_display_date {
+        true => "%Y-%m-%d %H:%M:%S",
+        false => "%H:%M:%S",
+    };
+
+    converted_date_time.format(date_format).to_string()
+}
+
+// accepts any of the time expressions understood by `parse_time_expression`, relative to now
+pub fn convert_to_utc_datetime(
+    local_date_time: &str,
+    clock: &dyn Clock,
+    tz: &impl TimeZone,
+    policy: AmbiguousTimePolicy,
+) -> Result<DateTime<Utc>, anyhow::Error> {
+    parse_time_expression(local_date_time, clock.now().with_timezone(tz), policy)
+}
 
-    let mut digits_same = true;
-    for time_count in 0..now_split.len() {
-        let now_digit = now_split[time_count].parse::<i32>().unwrap();
-        let compared_digit = compared_split[time_count].parse::<i32>().unwrap();
+// returns the utc time that the given local day begins at.  midnight doesn't exist on days where
+// clocks spring forward at midnight, in which case the day begins at the end of the gap
+pub fn get_local_day_start(date: NaiveDate, tz: &impl TimeZone) -> Result<DateTime<Utc>, anyhow::Error> {
+    let mut day_start = date.and_hms(0, 0, 0);
 
-        if now_digit > compared_digit {
-            digits_same = false;
-            continue;
-        } else if digits_same == true && compared_digit > now_digit {
-            return true;
+    for _ in 0..24 {
+        if let Ok(local_dt) = convert_local_to_utc(&day_start, tz, AmbiguousTimePolicy::Earliest) {
+            return Ok(local_dt);
         }
+        day_start += Duration::hours(1);
     }
 
-    false
+    Err(anyhow!("the start of {} could not be found in the local timezone", date))
 }
 
-fn is_valid_timestr(time_str: &str) -> bool {
-    lazy_static! {
-        // matches all valid inputs on a 24hr clock
-        static ref RE: Regex = Regex::new(r"^(?:(?:([01]?\d|2[0-3]):)([0-5]?\d):)([0-5]\d)$").unwrap();
-    }
+pub fn parse_date(date: &str) -> Result<NaiveDate, anyhow::Error> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| anyhow!("'{}' is an illegal date, dates should be of the format YYYY-MM-DD", date))
+}
 
-    RE.is_match(time_str) && time_str.len() == LENGTH_OF_FULL_TIMESTAMP
+pub fn get_todays_local_date(clock: &dyn Clock, tz: &impl TimeZone) -> NaiveDate {
+    clock.now().with_timezone(tz).date().naive_local()
+}
+
+pub fn get_todays_date(clock: &dyn Clock, tz: &impl TimeZone) -> String {
+    get_todays_local_date(clock, tz).format("%Y-%m-%d").to_string()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    // 2021-03-18 23:36:24 in a timezone an hour ahead of utc
+    fn clock() -> FixedClock {
+        FixedClock(Utc.ymd(2021, 3, 18).and_hms(22, 36, 24))
+    }
+
+    fn tz() -> FixedOffset {
+        FixedOffset::east(3600)
+    }
+
     #[test]
     fn test_convert_local_to_utc() {
-        let timest = Local::now().format("%H:%M:%S").to_string();
-        let utc_time = chrono::offset::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
-
         assert_eq!(
-            convert_to_utc_timestr(&timest).unwrap().contains(&utc_time),
-            true
+            convert_to_utc_datetime("23:36:24", &clock(), &tz(), AmbiguousTimePolicy::Error).unwrap(),
+            Utc.ymd(2021, 3, 18).and_hms(22, 36, 24)
         );
     }
 
     #[test]
     fn test_convert_local_to_utc_no_seconds() {
-        let timest = Local::now().format("%H:%M").to_string();
-        let mut utc_time_with_padd = chrono::offset::Utc::now().format("%H:%M").to_string();
-
-        utc_time_with_padd.push_str(":00");
+        assert_eq!(
+            convert_to_utc_datetime("23:36", &clock(), &tz(), AmbiguousTimePolicy::Error).unwrap(),
+            Utc.ymd(2021, 3, 18).and_hms(22, 36, 0)
+        );
+    }
 
+    #[test]
+    fn test_convert_local_to_utc_later_than_now_is_yesterday() {
         assert_eq!(
-            convert_to_utc_timestr(&timest)
-                .unwrap()
-                .contains(&utc_time_with_padd),
-            true
+            convert_to_utc_datetime("23:37", &clock(), &tz(), AmbiguousTimePolicy::Error).unwrap(),
+            Utc.ymd(2021, 3, 17).and_hms(22, 37, 0)
         );
     }
 
     #[test]
     fn test_convert_invalid_local_to_utc_fails() {
         let timest = "100:10:10";
-        assert_eq!(convert_to_utc_timestr(&timest).is_err(), true);
+        assert!(convert_to_utc_datetime(timest, &clock(), &tz(), AmbiguousTimePolicy::Error).is_err());
     }
 
     #[test]
     fn test_convert_truncated_local_to_utc_fails() {
         let timest = "10";
-        assert_eq!(convert_to_utc_timestr(&timest).is_err(), true);
+        assert!(convert_to_utc_datetime(timest, &clock(), &tz(), AmbiguousTimePolicy::Error).is_err());
     }
 
     #[test]
-    fn test_display_local_timestamp_with_date() {
-        let c_time = chrono::offset::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    fn test_convert_local_date_and_time_to_utc() {
+        let expected = Utc.ymd(2021, 3, 10).and_hms(22, 36, 24);
+
+        assert_eq!(convert_to_utc_datetime("2021-03-10 23:36:24", &clock(), &tz(), AmbiguousTimePolicy::Error).unwrap(), expected);
         assert_eq!(
-            convert_to_local_timestamp(&c_time, true).unwrap(),
-            Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            convert_to_utc_datetime("2021-03-10 23:36", &clock(), &tz(), AmbiguousTimePolicy::Error).unwrap(),
+            expected - Duration::seconds(24)
         );
     }
 
     #[test]
-    fn test_display_local_timestamp_without_date() {
-        let c_time = chrono::offset::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    fn test_convert_invalid_local_date_and_time_to_utc_fails() {
+        assert!(convert_to_utc_datetime("2021-13-18 23:36:24", &clock(), &tz(), AmbiguousTimePolicy::Error).is_err());
+        assert!(convert_to_utc_datetime("2021-03-18 25:36", &clock(), &tz(), AmbiguousTimePolicy::Error).is_err());
+        assert!(convert_to_utc_datetime("18/03/2021 23:36", &clock(), &tz(), AmbiguousTimePolicy::Error).is_err());
+    }
+
+    #[test]
+    fn test_get_local_day_start() {
         assert_eq!(
-            convert_to_local_timestamp(&c_time, false).unwrap(),
-            Local::now().format("%H:%M:%S").to_string(),
+            get_local_day_start(NaiveDate::from_ymd(2021, 3, 18), &tz()).unwrap(),
+            Utc.ymd(2021, 3, 17).and_hms(23, 0, 0)
         );
     }
 
     #[test]
-    fn test_display_local_timestamp_error_hit() {
-        let timest = "arestneasrtn";
-        assert_eq!(convert_to_local_timestamp(&timest, false).is_err(), true);
+    fn test_get_local_day_start_across_dst() {
+        assert_eq!(
+            get_local_day_start(NaiveDate::from_ymd(2021, 3, 29), &DstZone).unwrap(),
+            Utc.ymd(2021, 3, 28).and_hms(22, 0, 0)
+        );
+        assert_eq!(
+            get_local_day_start(NaiveDate::from_ymd(2021, 11, 1), &DstZone).unwrap(),
+            Utc.ymd(2021, 10, 31).and_hms(23, 0, 0)
+        );
     }
 
     #[test]
-    fn test_display_local_timestamp_error_incorrect_format() {
-        let timest = "009:009:009";
-        assert_eq!(convert_to_local_timestamp(&timest, false).is_err(), true);
+    fn test_convert_local_to_utc_in_spring_forward_gap_fails() {
+        let err = convert_local_to_utc(&NaiveDate::from_ymd(2021, 3, 28).and_hms(2, 30, 0), &DstZone, AmbiguousTimePolicy::Earliest)
+            .unwrap_err();
+        assert!(err.to_string().contains("doesn't exist in the local timezone"));
     }
 
     #[test]
-    fn test_get_todays_date() {
+    fn test_convert_ambiguous_local_to_utc_follows_policy() {
+        let fall_back = NaiveDate::from_ymd(2021, 10, 31).and_hms(2, 30, 0);
+
+        assert_eq!(
+            convert_local_to_utc(&fall_back, &DstZone, AmbiguousTimePolicy::Earliest).unwrap(),
+            Utc.ymd(2021, 10, 31).and_hms(0, 30, 0)
+        );
         assert_eq!(
-            get_todays_date(),
-            Local::now().format("%Y-%m-%d").to_string()
+            convert_local_to_utc(&fall_back, &DstZone, AmbiguousTimePolicy::Latest).unwrap(),
+            Utc.ymd(2021, 10, 31).and_hms(1, 30, 0)
         );
+        assert!(convert_local_to_utc(&fall_back, &DstZone, AmbiguousTimePolicy::Error).is_err());
     }
 
     #[test]
-    fn test_is_time_yesterday() {
-        assert_eq!(is_time_yesterday("08:08:08", "09:09:09"), true);
-        assert_eq!(is_time_yesterday("08:08:08", "07:59:59"), false);
-        assert_eq!(is_time_yesterday("00:01:08", "23:09:09"), true);
-        assert_eq!(is_time_yesterday("01:01:08", "01:01:00"), false);
+    fn test_ambiguous_time_policy_from_str() {
+        assert_eq!("latest".parse::<AmbiguousTimePolicy>().unwrap(), AmbiguousTimePolicy::Latest);
+        assert!("middle".parse::<AmbiguousTimePolicy>().is_err());
     }
 
     #[test]
-    fn test_is_valid_timestr() {
-        let valid_strs = vec!["01:01:00", "12:12:59", "23:59:28", "23:59:40"];
-        let invalid_strs = vec!["22:80:00", "21:21:80", "111:12:12", "10:10", "09:09", "09"];
-
-        for str in &valid_strs {
-            assert_eq!(is_valid_timestr(str), true);
-        }
+    fn test_parse_date() {
+        assert_eq!(parse_date("2021-03-18").unwrap(), NaiveDate::from_ymd(2021, 3, 18));
+        assert!(parse_date("2021-03-32").is_err());
+        assert!(parse_date("18/03/2021").is_err());
+    }
 
-        for str in &invalid_strs {
-            assert_eq!(is_valid_timestr(str), false);
-        }
+    #[test]
+    fn test_display_local_timestamp_with_date() {
+        assert_eq!(
+            convert_to_local_timestamp(&clock().now(), &tz(), true),
+            "2021-03-18 23:36:24"
+        );
     }
 
     #[test]
-    fn test_time_since_start() {
-        let begin_time = "2021-03-18 23:36:24";
-        let end_time = "2021-03-18 23:36:59";
-        let prev_time = Utc.ymd(2021, 03, 18).and_hms(23, 36, 24);
-        let future_time = Utc.ymd(2021, 03, 18).and_hms(23, 36, 59);
-        let actual = get_time_between_stamps(begin_time, end_time);
-        assert_eq!(actual.unwrap(), future_time - prev_time);
+    fn test_display_local_timestamp_without_date() {
+        assert_eq!(convert_to_local_timestamp(&clock().now(), &tz(), false), "23:36:24");
     }
 
     #[test]
-    fn test_time_since_start_minutes() {
-        let begin_time = "2021-03-18 23:36:24";
-        let end_time = "2021-03-18 23:38:59";
-        let prev_time = Utc.ymd(2021, 03, 18).and_hms(23, 36, 24);
-        let future_time = Utc.ymd(2021, 03, 18).and_hms(23, 38, 59);
-        let actual = get_time_between_stamps(begin_time, end_time);
-        assert_eq!(actual.unwrap(), future_time - prev_time);
+    fn test_system_clock_drops_subseconds() {
+        assert_eq!(SystemClock.now().nanosecond(), 0);
     }
 
     #[test]
-    fn test_time_since_start_hours() {
-        let begin_time = "2021-03-18 23:36:24";
-        let end_time = "2021-03-19 01:38:59";
-        let prev_time = Utc.ymd(2021, 03, 18).and_hms(23, 36, 24);
-        let future_time = Utc.ymd(2021, 03, 19).and_hms(01, 38, 59);
-        let actual = get_time_between_stamps(begin_time, end_time);
-        assert_eq!(actual.unwrap(), future_time - prev_time);
+    fn test_todays_date_rolls_over_at_local_midnight() {
+        let before_midnight = FixedClock(Utc.ymd(2021, 3, 18).and_hms(22, 59, 59));
+        let after_midnight = FixedClock(Utc.ymd(2021, 3, 18).and_hms(23, 0, 0));
+
+        assert_eq!(get_todays_date(&before_midnight, &tz()), "2021-03-18");
+        assert_eq!(get_todays_date(&after_midnight, &tz()), "2021-03-19");
+        assert_eq!(get_todays_date(&after_midnight, &Utc), "2021-03-18");
     }
-}
\ No newline at end of file
+}
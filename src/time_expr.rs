@@ -0,0 +1,382 @@
+extern crate chrono;
+
+use crate::time::{convert_local_to_utc, AmbiguousTimePolicy};
+use anyhow::{anyhow, Error};
+use chrono::prelude::*;
+use chrono::Duration;
+use regex::Regex;
+
+// parses the time expressions accepted wherever a time can be given, relative to `now`:
+//
+//   now
+//   2021-03-18T17:30:00+01:00        an ISO-8601 date time, in the local timezone if it has no offset
+//   2021-03-18 17:30[:SS]            a local date and time, with any of the times below
+//   17:30[:SS], 5:30pm, 5pm, noon    a local time today, or yesterday if it hasn't happened yet
+//   -15m, 2h ago, 1h 30 minutes ago  an offset back from now
+//   today / yesterday 17:30          a local time on a given day
+//   friday 9am, last friday 9am      the most recent friday on or before today / before today
+//
+// local times that happen twice as the clocks go back are resolved by `policy`
+pub fn parse_time_expression<Tz: TimeZone>(
+    expression: &str,
+    now: DateTime<Tz>,
+    policy: AmbiguousTimePolicy,
+) -> Result<DateTime<Utc>, Error> {
+    let expression = expression.trim().to_lowercase();
+    let words: Vec<&str> = expression.split_whitespace().collect();
+    let now = now.with_nanosecond(0).unwrap_or(now);
+
+    if words.is_empty() {
+        return Err(anyhow!("a time wasn't entered.  {}", EXAMPLES));
+    }
+
+    if expression == "now" {
+        return Ok(now.with_timezone(&Utc));
+    }
+
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(&expression.to_uppercase()) {
+        return Ok(date_time.with_timezone(&Utc));
+    }
+
+    if let Some(offset) = parse_relative_offset(&expression) {
+        return Ok(now.with_timezone(&Utc) - offset?);
+    }
+
+    // a date written with a `T` separator is treated the same as one written with a space
+    let date_str = words[0].split('t').next().unwrap_or(words[0]);
+    if let Ok(date) = NaiveDate::parse_from_str(date_str, "%Y-%m-%d") {
+        let time_str = match words[0].split_once('t') {
+            Some((_, time)) if !time.is_empty() => time.to_string(),
+            _ => words[1..].join(" "),
+        };
+        if time_str.is_empty() {
+            return Err(anyhow!("'{}' needs a time, such as '{} 17:30'", expression, date_str));
+        }
+
+        return resolve_local(&now.timezone(), policy, date.and_time(parse_time_of_day(&time_str)?), &expression);
+    }
+
+    let today = now.date().naive_local();
+    let (day, time_words) = match words.as_slice() {
+        ["today", rest @ ..] => (Some(today), rest),
+        ["yesterday", rest @ ..] => (Some(today.pred()), rest),
+        ["last", weekday, rest @ ..] => match parse_weekday(weekday) {
+            Some(weekday) => (Some(most_recent_weekday(today.pred(), weekday)), rest),
+            None => return Err(unrecognized(&expression)),
+        },
+        [weekday, rest @ ..] => match parse_weekday(weekday) {
+            Some(weekday) => (Some(most_recent_weekday(today, weekday)), rest),
+            None => (None, &words[..]),
+        },
+        [] => (None, &words[..]),
+    };
+
+    // `at` is allowed between the day and the time, as in `yesterday at 5pm`
+    let time_words = match time_words {
+        ["at", rest @ ..] => rest,
+        rest => rest,
+    };
+    let time_str = time_words.join(" ");
+
+    match day {
+        Some(_) if time_str.is_empty() => Err(anyhow!("'{}' needs a time, such as '{} 17:30'", expression, expression)),
+        Some(day) => resolve_local(&now.timezone(), policy, day.and_time(parse_time_of_day(&time_str)?), &expression),
+        None => {
+            let time = parse_time_of_day(&time_str)?;
+            let today_at = resolve_local(&now.timezone(), policy, today.and_time(time), &expression)?;
+
+            // a time that hasn't happened yet today is taken to mean yesterday
+            if today_at > now.with_timezone(&Utc) {
+                resolve_local(&now.timezone(), policy, today.pred().and_time(time), &expression)
+            } else {
+                Ok(today_at)
+            }
+        }
+    }
+}
+
+const EXAMPLES: &str = "try a time like '17:30', '5:30pm', '2021-03-18 17:30', '15m ago', 'yesterday 17:30' or 'last friday 9am'";
+
+// a hundred years, well past anything worth tracking but far from overflowing
+const MAX_OFFSET_SECONDS: i64 = 100 * 365 * 24 * 60 * 60;
+
+fn unrecognized(expression: &str) -> Error {
+    anyhow!("'{}' isn't a time that taskmao understands.  {}", expression, EXAMPLES)
+}
+
+// returns `None` when the expression isn't an offset at all, so that other forms can be tried
+fn parse_relative_offset(expression: &str) -> Option<Result<Duration, Error>> {
+    lazy_static! {
+        static ref OFFSET_RE: Regex = Regex::new(r"^(?:-\s*(?P<minus>.+)|(?P<ago>.+)\s+ago)$").unwrap();
+    }
+
+    let captures = OFFSET_RE.captures(expression)?;
+    let amounts = captures.name("minus").or_else(|| captures.name("ago"))?.as_str();
+
+    Some(parse_amounts(amounts, expression).and_then(|offset| offset.ok_or_else(|| unrecognized(expression))))
+}
+
+// parses an amount of time, such as '30d' or '1h 30m'
+pub fn parse_duration(text: &str) -> Result<Duration, Error> {
+    let text = text.trim().to_lowercase();
+
+    parse_amounts(&text, &text)?.ok_or_else(|| {
+        anyhow!("'{}' isn't an amount of time that taskmao understands, such as '30d' or '1h 30m'", text)
+    })
+}
+
+// adds up amounts such as '1h 30m', returning `None` if anything else is in `amounts`
+fn parse_amounts(amounts: &str, expression: &str) -> Result<Option<Duration>, Error> {
+    lazy_static! {
+        static ref UNIT_RE: Regex = Regex::new(r"(\d+)\s*([a-z]+)").unwrap();
+    }
+
+    // every character has to be part of an amount for it to be valid
+    let mut matched_len = 0;
+    let mut offset = Duration::zero();
+    for unit_captures in UNIT_RE.captures_iter(amounts) {
+        matched_len += unit_captures[0].chars().filter(|c| !c.is_whitespace()).count();
+
+        let unit_seconds: i64 = match &unit_captures[2] {
+            "s" | "sec" | "secs" | "second" | "seconds" => 1,
+            "m" | "min" | "mins" | "minute" | "minutes" => 60,
+            "h" | "hr" | "hrs" | "hour" | "hours" => 60 * 60,
+            "d" | "day" | "days" => 24 * 60 * 60,
+            "w" | "week" | "weeks" => 7 * 24 * 60 * 60,
+            unit => {
+                return Err(anyhow!(
+                    "'{}' isn't a unit of time taskmao understands, use seconds, minutes, hours, days or weeks",
+                    unit
+                ))
+            }
+        };
+        match unit_captures[1].parse::<i64>().ok().and_then(|amount| amount.checked_mul(unit_seconds)) {
+            Some(seconds) if seconds <= MAX_OFFSET_SECONDS => offset = offset + Duration::seconds(seconds),
+            _ => return Err(anyhow!("'{}' is too large an offset", expression)),
+        }
+    }
+
+    if offset > Duration::seconds(MAX_OFFSET_SECONDS) {
+        return Err(anyhow!("'{}' is too large an offset", expression));
+    }
+
+    if matched_len == 0 || matched_len != amounts.chars().filter(|c| !c.is_whitespace()).count() {
+        return Ok(None);
+    }
+
+    Ok(Some(offset))
+}
+
+fn parse_time_of_day(time: &str) -> Result<NaiveTime, Error> {
+    lazy_static! {
+        static ref CLOCK_RE: Regex =
+            Regex::new(r"^(?P<hour>\d{1,2})(?::(?P<minute>\d{2}))?(?::(?P<second>\d{2}))?\s*(?P<meridiem>am|pm)?$").unwrap();
+    }
+
+    match time {
+        "noon" => return Ok(NaiveTime::from_hms(12, 0, 0)),
+        "midnight" => return Ok(NaiveTime::from_hms(0, 0, 0)),
+        _ => {}
+    }
+
+    let captures = CLOCK_RE.captures(time).ok_or_else(|| unrecognized(time))?;
+    let number = |name: &str| captures.name(name).map_or(Ok(0), |m| m.as_str().parse::<u32>());
+    let (mut hour, minute, second) = (number("hour")?, number("minute")?, number("second")?);
+
+    match captures.name("meridiem").map(|m| m.as_str()) {
+        Some(meridiem) => {
+            if hour == 0 || hour > 12 {
+                return Err(anyhow!("'{}' isn't a valid time, 12-hour clock hours run from 1 to 12", time));
+            }
+            hour = match (meridiem, hour) {
+                ("am", 12) => 0,
+                ("pm", 12) => 12,
+                ("pm", hour) => hour + 12,
+                (_, hour) => hour,
+            };
+        }
+        // a bare number could be anything, so 24-hour clock times need their minutes
+        None if captures.name("minute").is_none() => return Err(unrecognized(time)),
+        None => {}
+    }
+
+    NaiveTime::from_hms_opt(hour, minute, second)
+        .ok_or_else(|| anyhow!("'{}' isn't a valid time of day", time))
+}
+
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    match word {
+        "monday" | "mon" => Some(Weekday::Mon),
+        "tuesday" | "tue" | "tues" => Some(Weekday::Tue),
+        "wednesday" | "wed" => Some(Weekday::Wed),
+        "thursday" | "thu" | "thurs" => Some(Weekday::Thu),
+        "friday" | "fri" => Some(Weekday::Fri),
+        "saturday" | "sat" => Some(Weekday::Sat),
+        "sunday" | "sun" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+// returns the last `weekday` falling on or before `day`
+fn most_recent_weekday(day: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let days_back = (7 + day.weekday().num_days_from_monday() - weekday.num_days_from_monday()) % 7;
+    day - Duration::days(days_back as i64)
+}
+
+fn resolve_local<Tz: TimeZone>(
+    timezone: &Tz,
+    policy: AmbiguousTimePolicy,
+    local: NaiveDateTime,
+    expression: &str,
+) -> Result<DateTime<Utc>, Error> {
+    convert_local_to_utc(&local, timezone, policy).map_err(|err| anyhow!("'{}' can't be used, {}", expression, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::DstZone;
+
+    // a thursday, in a timezone an hour ahead of utc
+    fn now() -> DateTime<FixedOffset> {
+        FixedOffset::east(3600).ymd(2021, 3, 18).and_hms(12, 0, 0)
+    }
+
+    fn local(y: i32, m: u32, d: u32, h: u32, min: u32, s: u32) -> DateTime<Utc> {
+        FixedOffset::east(3600).ymd(y, m, d).and_hms(h, min, s).with_timezone(&Utc)
+    }
+
+    fn parse(expression: &str) -> DateTime<Utc> {
+        parse_time_expression(expression, now(), AmbiguousTimePolicy::Error).unwrap()
+    }
+
+    #[test]
+    fn test_now() {
+        assert_eq!(parse("now"), now().with_timezone(&Utc));
+    }
+
+    #[test]
+    fn test_iso_8601() {
+        assert_eq!(parse("2021-03-10T08:15:00Z"), Utc.ymd(2021, 3, 10).and_hms(8, 15, 0));
+        assert_eq!(parse("2021-03-10T08:15:00-05:00"), Utc.ymd(2021, 3, 10).and_hms(13, 15, 0));
+        assert_eq!(parse("2021-03-10T08:15"), local(2021, 3, 10, 8, 15, 0));
+    }
+
+    #[test]
+    fn test_date_and_time() {
+        assert_eq!(parse("2021-03-10 08:15"), local(2021, 3, 10, 8, 15, 0));
+        assert_eq!(parse("2021-03-10 08:15:30"), local(2021, 3, 10, 8, 15, 30));
+        assert_eq!(parse("2021-03-10 8:15pm"), local(2021, 3, 10, 20, 15, 0));
+    }
+
+    #[test]
+    fn test_time_of_day() {
+        assert_eq!(parse("09:30"), local(2021, 3, 18, 9, 30, 0));
+        assert_eq!(parse("9:30:15"), local(2021, 3, 18, 9, 30, 15));
+        assert_eq!(parse("noon"), local(2021, 3, 18, 12, 0, 0));
+    }
+
+    #[test]
+    fn test_time_of_day_later_than_now_is_yesterday() {
+        assert_eq!(parse("23:09"), local(2021, 3, 17, 23, 9, 0));
+        assert_eq!(parse("12:00:01"), local(2021, 3, 17, 12, 0, 1));
+    }
+
+    #[test]
+    fn test_12_hour_clock() {
+        assert_eq!(parse("9am"), local(2021, 3, 18, 9, 0, 0));
+        assert_eq!(parse("9:45 AM"), local(2021, 3, 18, 9, 45, 0));
+        assert_eq!(parse("12am"), local(2021, 3, 18, 0, 0, 0));
+        assert_eq!(parse("12pm"), local(2021, 3, 18, 12, 0, 0));
+        assert_eq!(parse("5:30pm"), local(2021, 3, 17, 17, 30, 0));
+    }
+
+    #[test]
+    fn test_relative_offsets() {
+        assert_eq!(parse("-15m"), local(2021, 3, 18, 11, 45, 0));
+        assert_eq!(parse("2h ago"), local(2021, 3, 18, 10, 0, 0));
+        assert_eq!(parse("15 minutes ago"), local(2021, 3, 18, 11, 45, 0));
+        assert_eq!(parse("1h30m ago"), local(2021, 3, 18, 10, 30, 0));
+        assert_eq!(parse("1 hour 30 mins ago"), local(2021, 3, 18, 10, 30, 0));
+        assert_eq!(parse("- 3d"), local(2021, 3, 15, 12, 0, 0));
+    }
+
+    #[test]
+    fn test_durations() -> Result<(), Error> {
+        assert_eq!(parse_duration("30d")?, Duration::days(30));
+        assert_eq!(parse_duration(" 1H 30m ")?, Duration::minutes(90));
+        assert_eq!(parse_duration("2 weeks")?, Duration::weeks(2));
+        for text in &["", "30", "d", "30 days ago", "-30d", "99999999999999999999h"] {
+            assert!(parse_duration(text).is_err(), "'{}' should be rejected", text);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_days() {
+        assert_eq!(parse("today 8:00"), local(2021, 3, 18, 8, 0, 0));
+        assert_eq!(parse("yesterday 17:30"), local(2021, 3, 17, 17, 30, 0));
+        assert_eq!(parse("yesterday at 5pm"), local(2021, 3, 17, 17, 0, 0));
+    }
+
+    #[test]
+    fn test_weekdays() {
+        assert_eq!(parse("last friday 9am"), local(2021, 3, 12, 9, 0, 0));
+        assert_eq!(parse("friday 9am"), local(2021, 3, 12, 9, 0, 0));
+        assert_eq!(parse("tue 14:00"), local(2021, 3, 16, 14, 0, 0));
+        // the day of the week that it is today means today, unless it's the last one
+        assert_eq!(parse("thursday 9am"), local(2021, 3, 18, 9, 0, 0));
+        assert_eq!(parse("last thursday 9am"), local(2021, 3, 11, 9, 0, 0));
+    }
+
+    #[test]
+    fn test_times_around_daylight_saving_changes() {
+        let after_fall_back = DstZone.ymd(2021, 10, 31).and_hms(12, 0, 0);
+        let after_spring_forward = DstZone.ymd(2021, 3, 28).and_hms(12, 0, 0);
+
+        assert_eq!(
+            parse_time_expression("2:30", after_fall_back, AmbiguousTimePolicy::Earliest).unwrap(),
+            Utc.ymd(2021, 10, 31).and_hms(0, 30, 0)
+        );
+        assert_eq!(
+            parse_time_expression("2021-10-31 02:30", after_fall_back, AmbiguousTimePolicy::Latest).unwrap(),
+            Utc.ymd(2021, 10, 31).and_hms(1, 30, 0)
+        );
+        assert_eq!(
+            parse_time_expression("2021-10-31T02:30:00+01:00", after_fall_back, AmbiguousTimePolicy::Error).unwrap(),
+            Utc.ymd(2021, 10, 31).and_hms(1, 30, 0)
+        );
+
+        let err = parse_time_expression("2:30", after_fall_back, AmbiguousTimePolicy::Error).unwrap_err();
+        assert!(err.to_string().contains("'2021-10-31T02:30:00+02:00' or '2021-10-31T02:30:00+01:00'"));
+
+        let err = parse_time_expression("2:30", after_spring_forward, AmbiguousTimePolicy::Earliest).unwrap_err();
+        assert!(err.to_string().starts_with("'2:30' can't be used"));
+    }
+
+    #[test]
+    fn test_illegal_expressions_are_rejected() {
+        let illegal = vec![
+            "", "10", "100:10:10", "25:00", "10:61", "13pm", "0am", "yesterday", "last 9am",
+            "soon", "-15q", "15 ago", "2h from now", "2021-03-32 10:00", "2021-03-10",
+            "99999999999999999999h ago", "9999999999d ago",
+        ];
+
+        for expression in illegal {
+            assert!(
+                parse_time_expression(expression, now(), AmbiguousTimePolicy::Error).is_err(),
+                "'{}' should be rejected",
+                expression
+            );
+        }
+    }
+
+    #[test]
+    fn test_error_messages_name_the_expression() {
+        let err = parse_time_expression("yesterday", now(), AmbiguousTimePolicy::Error).unwrap_err();
+        assert_eq!(err.to_string(), "'yesterday' needs a time, such as 'yesterday 17:30'");
+
+        let err = parse_time_expression("-15q", now(), AmbiguousTimePolicy::Error).unwrap_err();
+        assert!(err.to_string().contains("'q' isn't a unit of time"));
+    }
+}
@@ -0,0 +1,1367 @@
+use crate::config::ProjectCheck;
+use crate::data::{check_project_color, Project, Task, TaskNote};
+use crate::error::Error;
+use crate::ids::{resolve_id, resolve_id_among, ShortIds};
+use crate::journal::{Change, Operation, ProjectChange};
+use crate::report::{build_report, Report};
+use crate::search::{parse_query, SearchHit};
+use crate::store::{SqliteStore, TaskStore};
+use crate::time::{convert_to_utc_datetime, get_local_day_start, get_todays_local_date, AmbiguousTimePolicy, Clock};
+use crate::transfer::{import_csv, ImportSummary};
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use regex::Regex;
+use std::path::Path;
+
+// narrows down the tasks returned by `list`, `matching`, `find_by_prefix` and `report`.  a task has
+// to match every part that's given: be in the project, have every one of the tags, have a
+// description the regex matches and an id starting with the prefix.  the days from `from` up to and
+// including `to` depend on the timezone, so they're applied by `Tracker::matching` and `list`
+#[derive(Clone, Debug, Default)]
+pub struct TaskFilter {
+    pub project: Option<String>,
+    pub tags: Vec<String>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub description: Option<Regex>,
+    pub id_prefix: Option<String>,
+}
+
+impl TaskFilter {
+    pub fn matches(&self, task: &Task) -> bool {
+        let in_project = match &self.project {
+            Some(project) => &task.project_name == project,
+            None => true,
+        };
+        let description_matches = match &self.description {
+            Some(description) => description.is_match(&task.description),
+            None => true,
+        };
+        let id_matches = match &self.id_prefix {
+            Some(prefix) => task.unique_id.starts_with(prefix.as_str()),
+            None => true,
+        };
+
+        in_project && task.has_tags(&self.tags) && description_matches && id_matches
+    }
+
+    pub fn has_date_range(&self) -> bool {
+        self.from.is_some() || self.to.is_some()
+    }
+
+    // whether anything at all is filtered out
+    pub fn is_empty(&self) -> bool {
+        self.project.is_none()
+            && self.tags.is_empty()
+            && !self.has_date_range()
+            && self.description.is_none()
+            && self.id_prefix.is_none()
+    }
+}
+
+// from the first time up until the second
+type TimeRange = (DateTime<Utc>, DateTime<Utc>);
+
+// a change made to every task matched by a filter
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BulkEdit {
+    pub project: Option<String>,
+    pub add_tags: Vec<String>,
+    pub remove_tags: Vec<String>,
+}
+
+impl BulkEdit {
+    pub fn is_empty(&self) -> bool {
+        self.project.is_none() && self.add_tags.is_empty() && self.remove_tags.is_empty()
+    }
+
+    pub fn apply(&self, task: &mut Task) {
+        if let Some(project) = &self.project {
+            task.project_name = project.clone();
+        }
+        task.tags.extend(self.add_tags.iter().cloned());
+        task.tags.retain(|tag| !self.remove_tags.contains(tag));
+        task.tags.sort();
+        task.tags.dedup();
+    }
+}
+
+// the changes a bulk command is going to make, worked out ahead of time so that they can be shown
+// before they're made
+#[derive(Clone, Debug, PartialEq)]
+pub struct BulkPlan {
+    command: &'static str,
+    // each task as it is now and as it'll be
+    changes: Vec<(Task, Task)>,
+}
+
+impl BulkPlan {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    // the tasks as they'll be after the changes
+    pub fn tasks(&self) -> Vec<Task> {
+        self.changes.iter().map(|(_, after)| after.clone()).collect()
+    }
+}
+
+// saves and deletes tasks and projects for a command, noting each change so that the command can
+// be undone
+struct Changes<'a> {
+    store: &'a dyn TaskStore,
+    changes: Vec<Change>,
+    project_changes: Vec<ProjectChange>,
+}
+
+impl Changes<'_> {
+    fn save(&mut self, task: &Task) -> Result<(), Error> {
+        let before = self.store.find_by_id(&task.unique_id)?;
+        self.store.save(task)?;
+        self.note(&task.unique_id, before)
+    }
+
+    fn delete(&mut self, task: &Task) -> Result<(), Error> {
+        let before = self.store.find_by_id(&task.unique_id)?;
+        self.store.delete_by_id(&task.unique_id)?;
+        self.note(&task.unique_id, before)
+    }
+
+    fn save_project(&mut self, project: &Project) -> Result<(), Error> {
+        let before = self.find_project(&project.name)?;
+        self.store.save_project(project)?;
+        self.note_project(&project.name, before)
+    }
+
+    fn delete_project(&mut self, name: &str) -> Result<(), Error> {
+        let before = self.find_project(name)?;
+        self.store.delete_project(name)?;
+        self.note_project(name, before)
+    }
+
+    fn find_project(&self, name: &str) -> Result<Option<Project>, Error> {
+        Ok(self.store.projects()?.into_iter().find(|project| project.name == name))
+    }
+
+    fn note_project(&mut self, name: &str, before: Option<Project>) -> Result<(), Error> {
+        let after = self.find_project(name)?;
+        self.project_changes.push(ProjectChange {
+            name: name.to_string(),
+            before,
+            after,
+        });
+
+        Ok(())
+    }
+
+    // notes a change that has already been made, reading the task back as it was stored
+    fn note(&mut self, task_unique_id: &str, before: Option<Task>) -> Result<(), Error> {
+        let after = self.store.find_by_id(task_unique_id)?;
+        self.changes.push(Change {
+            task_unique_id: task_unique_id.to_string(),
+            before,
+            after,
+        });
+
+        Ok(())
+    }
+}
+
+// the tracked tasks, along with the clock and timezone used to work with them.  every change goes
+// through here so that the rules around running tasks and overlapping times are applied the same
+// way for the cli and for anything embedding taskmao
+pub struct Tracker<Tz: TimeZone> {
+    store: Box<dyn TaskStore>,
+    clock: Box<dyn Clock>,
+    tz: Tz,
+    policy: AmbiguousTimePolicy,
+    history_limit: Option<usize>,
+}
+
+impl<Tz: TimeZone> Tracker<Tz> {
+    // opens the sqlite data store at `path`, creating it if it doesn't exist yet
+    pub fn open(path: &Path, clock: Box<dyn Clock>, tz: Tz) -> Result<Tracker<Tz>, Error> {
+        Ok(Tracker::new(Box::new(SqliteStore::open(path)?), clock, tz))
+    }
+
+    pub fn new(store: Box<dyn TaskStore>, clock: Box<dyn Clock>, tz: Tz) -> Tracker<Tz> {
+        Tracker {
+            store,
+            clock,
+            tz,
+            policy: AmbiguousTimePolicy::Earliest,
+            history_limit: None,
+        }
+    }
+
+    pub fn set_ambiguous_time_policy(&mut self, policy: AmbiguousTimePolicy) {
+        self.policy = policy;
+    }
+
+    // keeps only the newest `limit` operations in the journal as each change is recorded, rather
+    // than letting it grow without bound.  `None` keeps every operation
+    pub fn set_history_limit(&mut self, limit: Option<usize>) {
+        self.history_limit = limit;
+    }
+
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    pub fn timezone(&self) -> &Tz {
+        &self.tz
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    pub fn today(&self) -> NaiveDate {
+        get_todays_local_date(self.clock(), &self.tz)
+    }
+
+    // parses a time expression, such as '17:30' or '2h ago', relative to now
+    pub fn parse_time(&self, expression: &str) -> Result<DateTime<Utc>, Error> {
+        convert_to_utc_datetime(expression, self.clock(), &self.tz, self.policy)
+            .map_err(|err| Error::Invalid(err.to_string()))
+    }
+
+    pub fn current(&self) -> Result<Task, Error> {
+        self.store.most_recent_running()?.ok_or(Error::NoRunningTask)
+    }
+
+    // finds a task by its id, or by the start of an id that no other task's id starts with
+    pub fn find(&self, id: &str) -> Result<Task, Error> {
+        resolve_id(self.store.as_ref(), id)
+    }
+
+    pub fn find_by_prefix(&self, prefix: &str, filter: &TaskFilter) -> Result<Vec<Task>, Error> {
+        let mut tasks = self.store.search_by_id_prefix(prefix)?;
+        tasks.retain(|task| filter.matches(task));
+
+        Ok(tasks)
+    }
+
+    // the shortest unique start of every task's id, for showing in listings
+    pub fn short_ids(&self) -> Result<ShortIds, Error> {
+        Ok(ShortIds::new(&self.store.ids()?))
+    }
+
+    // returns the tasks worked on in the last 24 hours, or on the filter's days if it has any
+    pub fn list(&self, filter: &TaskFilter) -> Result<Vec<Task>, Error> {
+        if filter.has_date_range() {
+            return self.matching(filter);
+        }
+
+        let now = self.now();
+        let mut tasks = self.store.tasks_in_range(now - Duration::hours(24), now, now)?;
+        tasks.retain(|task| filter.matches(task));
+
+        Ok(tasks)
+    }
+
+    // returns every task matching the filter, ordered by start time.  without a date range all of
+    // the tasks are looked through
+    pub fn matching(&self, filter: &TaskFilter) -> Result<Vec<Task>, Error> {
+        let mut tasks = match self.filter_range(filter)? {
+            Some((from, to)) => self.store.tasks_in_range(from, to, self.now())?,
+            None => self.store.all()?,
+        };
+        tasks.retain(|task| filter.matches(task));
+
+        Ok(tasks)
+    }
+
+    // the time covered by the filter's days.  without a `to` the range runs up until the end of today
+    fn filter_range(&self, filter: &TaskFilter) -> Result<Option<TimeRange>, Error> {
+        if !filter.has_date_range() {
+            return Ok(None);
+        }
+
+        let from = filter.from.unwrap_or_else(|| NaiveDate::from_ymd(1970, 1, 1));
+        let to = filter.to.unwrap_or_else(|| self.today());
+        if to < from {
+            return Err(Error::EmptyRange);
+        }
+
+        Ok(Some((get_local_day_start(from, &self.tz)?, get_local_day_start(to.succ(), &self.tz)?)))
+    }
+
+    // searches task descriptions and projects, returning the best `limit` matches that the filter
+    // also matches
+    pub fn search(&self, query: &str, filter: &TaskFilter, limit: usize) -> Result<Vec<SearchHit>, Error> {
+        if query.trim().is_empty() {
+            return Err(Error::Invalid("a search can't be empty".to_string()));
+        }
+
+        let range = self.filter_range(filter)?;
+        let now = self.now();
+        let query = parse_query(query)?;
+        let mut hits = self.store.search(&query)?;
+        hits.retain(|hit| {
+            let in_range = range.is_none_or(|(from, to)| hit.task.start_time < to && hit.task.end_time.unwrap_or(now) > from);
+            in_range && filter.matches(&hit.task)
+        });
+        hits.truncate(limit);
+
+        Ok(hits)
+    }
+
+    pub fn all_tasks(&self) -> Result<Vec<Task>, Error> {
+        Ok(self.store.all()?)
+    }
+
+    // returns the tasks worked on from the start of the local day `from` up until the start of `to`
+    pub fn tasks_between(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<Task>, Error> {
+        let from = get_local_day_start(from, &self.tz)?;
+        let to = get_local_day_start(to, &self.tz)?;
+
+        Ok(self.store.tasks_in_range(from, to, self.now())?)
+    }
+
+    // runs `f` in a transaction of the data store, so that either everything it does is kept or
+    // nothing is
+    fn in_transaction<T>(&self, f: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+        let mut f = Some(f);
+        let mut value = None;
+        let result = self.store.transaction(&mut || {
+            if let Some(f) = f.take() {
+                value = Some(f()?);
+            }
+            Ok(())
+        });
+
+        match (result, value) {
+            (Ok(()), Some(value)) => Ok(value),
+            (Ok(()), None) => Err(Error::Storage(anyhow::anyhow!("the transaction didn't run"))),
+            // errors from `f` pass through the data store as `anyhow` errors
+            (Err(err), _) => Err(err.downcast::<Error>().unwrap_or_else(Error::Storage)),
+        }
+    }
+
+    // runs `f` in a transaction, recording the changes it makes as one operation in the journal
+    fn record<T>(&self, command: &str, f: impl FnOnce(&mut Changes) -> Result<T, Error>) -> Result<T, Error> {
+        self.in_transaction(|| {
+            let mut changes = Changes {
+                store: self.store.as_ref(),
+                changes: Vec::new(),
+                project_changes: Vec::new(),
+            };
+            let value = f(&mut changes)?;
+            if !changes.changes.is_empty() || !changes.project_changes.is_empty() {
+                self.store
+                    .record_operation(command, self.now(), &changes.changes, &changes.project_changes)?;
+                if let Some(limit) = self.history_limit {
+                    self.store.prune_operations(limit)?;
+                }
+            }
+
+            Ok(value)
+        })
+    }
+
+    // errors if the task's times are out of order or overlap other tasks
+    fn check_task_times(&self, task: &Task) -> Result<(), Error> {
+        let now = self.now();
+        let end_time = task.end_time.unwrap_or(now);
+
+        if end_time < task.start_time {
+            return Err(Error::EndBeforeStart);
+        }
+
+        let overlapping_tasks: Vec<Task> = self.store.overlapping(task.start_time, end_time, now)?
+            .into_iter()
+            .filter(|t| t.unique_id != task.unique_id)
+            .collect();
+        if !overlapping_tasks.is_empty() {
+            return Err(Error::Overlap(overlapping_tasks));
+        }
+
+        Ok(())
+    }
+
+    // starts `new_task`, ending the currently running task, if there is one, at the new task's
+    // start.  the new task can't start before the running task or overlap other tasks.  returns
+    // the task that was ended
+    pub fn start(&self, new_task: &Task) -> Result<Option<Task>, Error> {
+        self.start_as("start", new_task)
+    }
+
+    fn start_as(&self, command: &str, new_task: &Task) -> Result<Option<Task>, Error> {
+        self.record(command, |changes| {
+            let ended_task = match self.store.most_recent_running()? {
+                // the running task would end before it started
+                Some(prev_task) if new_task.start_time < prev_task.start_time => return Err(Error::EndBeforeStart),
+                Some(mut prev_task) => {
+                    prev_task.end_task(new_task.start_time);
+                    changes.save(&prev_task)?;
+                    Some(prev_task)
+                }
+                None => None,
+            };
+            self.check_task_times(new_task)?;
+            changes.save(new_task)?;
+
+            Ok(ended_task)
+        })
+    }
+
+    // starts a new task copying the description, project and tags of the task found by `id`, or
+    // of the last ended task.  returns the new task and the task that was ended to start it
+    pub fn restart(&self, id: Option<&str>, start_time: DateTime<Utc>) -> Result<(Task, Option<Task>), Error> {
+        let prev_task = self.restart_source(id)?;
+
+        if prev_task.is_running() {
+            return Err(Error::TaskRunning(prev_task.unique_id));
+        }
+
+        let mut new_task = Task::new(&prev_task.description, &prev_task.project_name, start_time);
+        new_task.tags = prev_task.tags;
+        let ended_task = self.start_as("restart", &new_task)?;
+
+        Ok((new_task, ended_task))
+    }
+
+    // the task that `restart` would copy
+    pub fn restart_source(&self, id: Option<&str>) -> Result<Task, Error> {
+        match id {
+            Some(id) => self.find(id),
+            None => self.store.most_recent_ended()?.ok_or(Error::NoEndedTask),
+        }
+    }
+
+    pub fn end(&self, end_time: DateTime<Utc>) -> Result<Task, Error> {
+        let mut task = self.current()?;
+        task.end_task(end_time);
+        self.record("end", |changes| changes.save(&task))?;
+
+        Ok(task)
+    }
+
+    // moves the running task to the trash
+    pub fn cancel(&self) -> Result<Task, Error> {
+        let mut task = self.current()?;
+        task.deleted_at = Some(self.now());
+        self.record("cancel", |changes| changes.save(&task))?;
+
+        Ok(task)
+    }
+
+    // moves an ended task to the trash
+    pub fn delete(&self, id: &str) -> Result<Task, Error> {
+        let mut task = self.find(id)?;
+        if task.is_running() {
+            return Err(Error::TaskRunning(task.unique_id));
+        }
+
+        task.deleted_at = Some(self.now());
+        self.record("delete", |changes| changes.save(&task))?;
+        Ok(task)
+    }
+
+    // the tasks in the trash, in the order they were deleted
+    pub fn trash(&self) -> Result<Vec<Task>, Error> {
+        Ok(self.store.trashed()?)
+    }
+
+    // takes a task back out of the trash, as long as its times don't clash with the tasks that
+    // were tracked since it was deleted
+    pub fn restore_from_trash(&self, id: &str) -> Result<Task, Error> {
+        let mut task = resolve_id_among(self.trash()?, id)?;
+        task.deleted_at = None;
+        self.check_task_times(&task)?;
+        self.record("trash restore", |changes| changes.save(&task))?;
+
+        Ok(task)
+    }
+
+    // removes the tasks that have been in the trash for longer than `older_than`, or every task in
+    // the trash.  returns the tasks that were removed
+    pub fn empty_trash(&self, older_than: Option<Duration>) -> Result<Vec<Task>, Error> {
+        let cutoff = self.now() - older_than.unwrap_or_else(Duration::zero);
+        let mut tasks = self.trash()?;
+        tasks.retain(|task| task.deleted_at.is_some_and(|deleted_at| deleted_at <= cutoff));
+
+        self.record("trash empty", |changes| {
+            for task in &tasks {
+                changes.delete(task)?;
+            }
+            Ok(())
+        })?;
+
+        Ok(tasks)
+    }
+
+    // makes the same change to every task matching the filter, all together.  returns the tasks
+    // as they were saved
+    pub fn bulk_edit(&self, filter: &TaskFilter, edit: &BulkEdit) -> Result<Vec<Task>, Error> {
+        self.apply_bulk(&self.plan_bulk_edit(filter, edit)?)
+    }
+
+    // moves every task matching the filter to the trash, all together.  a running task among them
+    // stops any of them being deleted
+    pub fn bulk_delete(&self, filter: &TaskFilter) -> Result<Vec<Task>, Error> {
+        self.apply_bulk(&self.plan_bulk_delete(filter)?)
+    }
+
+    // works out how `bulk_edit` would change the tasks matching the filter, without changing them
+    pub fn plan_bulk_edit(&self, filter: &TaskFilter, edit: &BulkEdit) -> Result<BulkPlan, Error> {
+        let changes = self
+            .matching(filter)?
+            .into_iter()
+            .map(|task| {
+                let mut edited = task.clone();
+                edit.apply(&mut edited);
+                (task, edited)
+            })
+            .collect();
+
+        Ok(BulkPlan {
+            command: "bulk-edit",
+            changes,
+        })
+    }
+
+    // works out which tasks `bulk_delete` would move to the trash, without moving them
+    pub fn plan_bulk_delete(&self, filter: &TaskFilter) -> Result<BulkPlan, Error> {
+        let deleted_at = self.now();
+        let mut changes = Vec::new();
+        for task in self.matching(filter)? {
+            if task.is_running() {
+                return Err(Error::TaskRunning(task.unique_id));
+            }
+            let mut deleted = task.clone();
+            deleted.deleted_at = Some(deleted_at);
+            changes.push((task, deleted));
+        }
+
+        Ok(BulkPlan {
+            command: "bulk-delete",
+            changes,
+        })
+    }
+
+    // makes exactly the changes in the plan, all together.  if any of the tasks has changed since
+    // the plan was made none of them are changed
+    pub fn apply_bulk(&self, plan: &BulkPlan) -> Result<Vec<Task>, Error> {
+        self.record(plan.command, |changes| {
+            for (before, after) in &plan.changes {
+                if self.store.find_by_id(&before.unique_id)?.as_ref() != Some(before) {
+                    return Err(Error::Invalid(format!(
+                        "the task '{}' changed after the tasks were matched, so none of them were changed.  run '{}' again",
+                        before.unique_id, plan.command
+                    )));
+                }
+                changes.save(after)?;
+            }
+
+            Ok(plan.tasks())
+        })
+    }
+
+    // records a task that has already ended
+    pub fn log(&self, task: &Task) -> Result<(), Error> {
+        self.check_task_times(task)?;
+        self.record("log", |changes| changes.save(task))
+    }
+
+    // saves changes to an existing task.  a running task can't be given an end time
+    pub fn update(&self, task: &Task) -> Result<(), Error> {
+        let stored = self.find(&task.unique_id)?;
+        if stored.is_running() && task.end_time.is_some() {
+            return Err(Error::TaskRunning(task.unique_id.clone()));
+        }
+
+        self.check_task_times(task)?;
+        self.record("edit", |changes| changes.save(task))
+    }
+
+    pub fn pause(&self, time: DateTime<Utc>) -> Result<Task, Error> {
+        let mut task = self.current()?;
+        task.pause(time).map_err(|err| Error::Invalid(err.to_string()))?;
+        self.record("pause", |changes| changes.save(&task))?;
+
+        Ok(task)
+    }
+
+    pub fn resume(&self, time: DateTime<Utc>) -> Result<Task, Error> {
+        let mut task = self.current()?;
+        task.resume(time).map_err(|err| Error::Invalid(err.to_string()))?;
+        self.record("resume", |changes| changes.save(&task))?;
+
+        Ok(task)
+    }
+
+    // adds a note to the task with the id, or to the running task when there's no id.  notes
+    // are kept in the order they were added
+    pub fn add_note(&self, id: Option<&str>, text: &str) -> Result<Task, Error> {
+        let text = text.trim_end();
+        if text.trim().is_empty() {
+            return Err(Error::Invalid("a note can't be empty".to_string()));
+        }
+
+        let mut task = match id {
+            Some(id) => self.find(id)?,
+            None => self.current()?,
+        };
+        task.notes.push(TaskNote {
+            time: self.now(),
+            text: text.to_string(),
+        });
+        self.record("note", |changes| changes.save(&task))?;
+
+        Ok(task)
+    }
+
+    // returns every project, ordered by name
+    pub fn projects(&self) -> Result<Vec<Project>, Error> {
+        Ok(self.store.projects()?)
+    }
+
+    fn find_project(&self, name: &str) -> Result<Option<Project>, Error> {
+        Ok(self.store.projects()?.into_iter().find(|project| project.name == name))
+    }
+
+    // errors if tasks shouldn't be started in the project, because it hasn't been added or is
+    // archived.  it's up to the caller whether that stops the task from being started
+    pub fn check_project(&self, name: &str) -> Result<(), Error> {
+        match self.find_project(name)? {
+            None => Err(Error::ProjectNotFound(name.to_string())),
+            Some(project) if project.archived => Err(Error::ProjectArchived(project.name)),
+            Some(_) => Ok(()),
+        }
+    }
+
+    pub fn add_project(&self, project: &Project) -> Result<(), Error> {
+        if project.name.trim().is_empty() {
+            return Err(Error::Invalid("a project's name can't be empty".to_string()));
+        }
+        if let Some(color) = &project.color {
+            check_project_color(color).map_err(|err| Error::Invalid(err.to_string()))?;
+        }
+        if self.find_project(&project.name)?.is_some() {
+            return Err(Error::ProjectExists(project.name.clone()));
+        }
+
+        self.record("project add", |changes| changes.save_project(project))
+    }
+
+    // archives the project, or makes it active again, returning it as it now is
+    pub fn archive_project(&self, name: &str, archived: bool) -> Result<Project, Error> {
+        let mut project = self.find_project(name)?.ok_or(Error::ProjectNotFound(name.to_string()))?;
+        if project.archived == archived {
+            let state = if archived { "already archived" } else { "not archived" };
+            return Err(Error::Invalid(format!("the project '{}' is {}", name, state)));
+        }
+
+        project.archived = archived;
+        let command = if archived { "project archive" } else { "project unarchive" };
+        self.record(command, |changes| changes.save_project(&project))?;
+
+        Ok(project)
+    }
+
+    // every task in the project, including those in the trash
+    fn tasks_in_project(&self, name: &str) -> Result<Vec<Task>, Error> {
+        let mut tasks = self.store.all()?;
+        tasks.extend(self.store.trashed()?);
+
+        Ok(tasks.into_iter().filter(|task| task.project_name == name).collect())
+    }
+
+    // renames the project along with every task in it, returning the tasks that were moved.  the
+    // new name can't already be a project or be used by any task
+    pub fn rename_project(&self, name: &str, new_name: &str) -> Result<Vec<Task>, Error> {
+        let project = self.find_project(name)?.ok_or(Error::ProjectNotFound(name.to_string()))?;
+        if new_name.trim().is_empty() {
+            return Err(Error::Invalid("a project's name can't be empty".to_string()));
+        }
+        if self.find_project(new_name)?.is_some() || !self.tasks_in_project(new_name)?.is_empty() {
+            return Err(Error::ProjectExists(new_name.to_string()));
+        }
+
+        let mut tasks = self.tasks_in_project(name)?;
+        self.record("project rename", |changes| {
+            changes.delete_project(name)?;
+            changes.save_project(&Project {
+                name: new_name.to_string(),
+                ..project
+            })?;
+            for task in &mut tasks {
+                task.project_name = new_name.to_string();
+                changes.save(task)?;
+            }
+
+            Ok(())
+        })?;
+
+        Ok(tasks)
+    }
+
+    // moves every task in `name` into the project `into` and removes `name`, returning the tasks
+    // that were moved.  `name` doesn't have to have been added, so that tasks given a mistyped
+    // project can be moved to the right one
+    pub fn merge_projects(&self, name: &str, into: &str) -> Result<Vec<Task>, Error> {
+        if name == into {
+            return Err(Error::Invalid("a project can't be merged into itself".to_string()));
+        }
+        if self.find_project(into)?.is_none() {
+            return Err(Error::ProjectNotFound(into.to_string()));
+        }
+
+        let project = self.find_project(name)?;
+        let mut tasks = self.tasks_in_project(name)?;
+        if project.is_none() && tasks.is_empty() {
+            return Err(Error::ProjectNotFound(name.to_string()));
+        }
+
+        self.record("project merge", |changes| {
+            if project.is_some() {
+                changes.delete_project(name)?;
+            }
+            for task in &mut tasks {
+                task.project_name = into.to_string();
+                changes.save(task)?;
+            }
+
+            Ok(())
+        })?;
+
+        Ok(tasks)
+    }
+
+    // reports on the local days from `from` up until, but not including, `to`
+    pub fn report(&self, from: NaiveDate, to: NaiveDate, filter: &TaskFilter) -> Result<Report, Error> {
+        if to <= from {
+            return Err(Error::EmptyRange);
+        }
+
+        let mut tasks = self.tasks_between(from, to)?;
+        tasks.retain(|task| filter.matches(task));
+
+        Ok(build_report(
+            &tasks,
+            get_local_day_start(from, &self.tz)?,
+            get_local_day_start(to, &self.tz)?,
+            self.now(),
+            &self.tz,
+        )?)
+    }
+
+    // rows without a project go in `default_project`.  with `ProjectCheck::Error`, rows in a project
+    // that hasn't been added or is archived aren't imported;  the default project doesn't need to
+    // have been added.  with `ProjectCheck::Warn` it's up to the caller to warn about
+    // `ImportSummary::projects`
+    pub fn import_csv(
+        &self,
+        reader: impl std::io::Read,
+        default_project: &str,
+        project_check: ProjectCheck,
+    ) -> Result<ImportSummary, Error> {
+        let check_project = |name: &str| -> Result<(), anyhow::Error> {
+            match (self.check_project(name), project_check) {
+                (Err(Error::ProjectNotFound(_)), _) if name == default_project => Ok(()),
+                (Err(err @ (Error::ProjectNotFound(_) | Error::ProjectArchived(_))), ProjectCheck::Error) => Err(err.into()),
+                (Err(Error::ProjectNotFound(_) | Error::ProjectArchived(_)), ProjectCheck::Warn) => Ok(()),
+                (other, _) => Ok(other?),
+            }
+        };
+
+        self.record("import", |changes| {
+            let summary = import_csv(self.store.as_ref(), reader, default_project, &check_project, self.now())?;
+            for id in &summary.imported {
+                changes.note(id, None)?;
+            }
+
+            Ok(summary)
+        })
+    }
+
+    // the operations in the journal, newest first
+    pub fn history(&self, limit: usize) -> Result<Vec<Operation>, Error> {
+        Ok(self.store.operations()?.into_iter().rev().take(limit).collect())
+    }
+
+    // removes all but the newest `keep` operations from the journal, returning how many were
+    // removed.  the changes they made are kept, but can no longer be undone
+    pub fn prune_history(&self, keep: usize) -> Result<usize, Error> {
+        Ok(self.store.prune_operations(keep)?)
+    }
+
+    // reverts the last `count` operations that haven't been undone, newest first
+    pub fn undo(&self, count: usize) -> Result<Vec<Operation>, Error> {
+        let operations: Vec<Operation> =
+            self.store.operations()?.into_iter().rev().filter(|op| !op.undone).take(count).collect();
+        if operations.is_empty() {
+            return Err(Error::NothingToUndo);
+        }
+
+        self.in_transaction(|| {
+            for operation in &operations {
+                for change in operation.changes.iter().rev() {
+                    self.revert_change(&change.task_unique_id, &change.after, &change.before)?;
+                }
+                for change in operation.project_changes.iter().rev() {
+                    self.revert_project_change(&change.name, &change.after, &change.before)?;
+                }
+                self.store.set_operation_undone(operation.id, true)?;
+            }
+            Ok(())
+        })?;
+
+        Ok(operations)
+    }
+
+    // makes the changes of the last `count` undone operations again, oldest first
+    pub fn redo(&self, count: usize) -> Result<Vec<Operation>, Error> {
+        let operations: Vec<Operation> =
+            self.store.operations()?.into_iter().filter(|op| op.undone).take(count).collect();
+        if operations.is_empty() {
+            return Err(Error::NothingToRedo);
+        }
+
+        self.in_transaction(|| {
+            for operation in &operations {
+                for change in &operation.changes {
+                    self.revert_change(&change.task_unique_id, &change.before, &change.after)?;
+                }
+                for change in &operation.project_changes {
+                    self.revert_project_change(&change.name, &change.before, &change.after)?;
+                }
+                self.store.set_operation_undone(operation.id, false)?;
+            }
+            Ok(())
+        })?;
+
+        Ok(operations)
+    }
+
+    // puts a task back to `to`, as long as it's still `from`
+    fn revert_change(&self, task_unique_id: &str, from: &Option<Task>, to: &Option<Task>) -> Result<(), Error> {
+        if &self.store.find_by_id(task_unique_id)? != from {
+            return Err(Error::Invalid(format!(
+                "the task '{}' was changed without being recorded in the history, so it can't be restored",
+                task_unique_id
+            )));
+        }
+
+        match to {
+            Some(task) => self.store.save(task)?,
+            None => self.store.delete_by_id(task_unique_id)?,
+        }
+
+        Ok(())
+    }
+
+    fn revert_project_change(&self, name: &str, from: &Option<Project>, to: &Option<Project>) -> Result<(), Error> {
+        if &self.find_project(name)? != from {
+            return Err(Error::Invalid(format!(
+                "the project '{}' was changed without being recorded in the history, so it can't be restored",
+                name
+            )));
+        }
+
+        match to {
+            Some(project) => self.store.save_project(project)?,
+            None => self.store.delete_project(name)?,
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+    use crate::time::FixedClock;
+    use chrono::FixedOffset;
+
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2021, 3, 18).and_hms(12, 0, 0)
+    }
+
+    fn tracker() -> Result<Tracker<FixedOffset>, Error> {
+        Ok(Tracker::new(Box::new(MemoryStore::new()), Box::new(FixedClock(now())), FixedOffset::east(3600)))
+    }
+
+    fn ended_task(description: &str, start: DateTime<Utc>, end: DateTime<Utc>) -> Task {
+        let mut task = Task::new(description, "work", start);
+        task.end_task(end);
+        task
+    }
+
+    #[test]
+    fn test_start_ends_the_running_task() -> Result<(), Error> {
+        let tracker = tracker()?;
+        let first = Task::new("first", "work", now() - Duration::hours(2));
+        tracker.start(&first)?;
+
+        let second = Task::new("second", "work", now() - Duration::hours(1));
+        let ended = tracker.start(&second)?.unwrap();
+
+        assert_eq!(ended.unique_id, first.unique_id);
+        assert_eq!(ended.end_time, Some(second.start_time));
+        assert_eq!(tracker.current()?, second);
+        Ok(())
+    }
+
+    #[test]
+    fn test_start_cannot_go_back_before_the_running_task() -> Result<(), Error> {
+        let tracker = tracker()?;
+        let running = Task::new("running", "work", now() - Duration::hours(1));
+        tracker.start(&running)?;
+
+        let backdated = Task::new("backdated", "work", now() - Duration::days(3));
+        assert!(matches!(tracker.start(&backdated), Err(Error::EndBeforeStart)));
+        assert_eq!(tracker.current()?, running);
+        Ok(())
+    }
+
+    #[test]
+    fn test_start_rejects_overlapping_logged_tasks() -> Result<(), Error> {
+        let tracker = tracker()?;
+        let logged = ended_task("logged", now() - Duration::hours(3), now() - Duration::hours(2));
+        tracker.log(&logged)?;
+
+        let overlapping = Task::new("overlapping", "work", now() - Duration::minutes(150));
+        match tracker.start(&overlapping) {
+            Err(Error::Overlap(tasks)) => assert_eq!(tasks, vec![logged]),
+            other => panic!("expected an overlap, got {:?}", other),
+        }
+        assert!(matches!(tracker.current(), Err(Error::NoRunningTask)));
+
+        tracker.start(&Task::new("after", "work", now() - Duration::hours(2)))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_start_time_collisions_are_overlaps() -> Result<(), Error> {
+        let sqlite = SqliteStore::new(rusqlite::Connection::open_in_memory().map_err(anyhow::Error::from)?)?;
+        let stores: Vec<Box<dyn TaskStore>> = vec![Box::new(MemoryStore::new()), Box::new(sqlite)];
+        for store in stores {
+            let tracker = Tracker::new(store, Box::new(FixedClock(now())), FixedOffset::east(3600));
+            let logged = ended_task("logged", now() - Duration::hours(3), now() - Duration::hours(2));
+            tracker.log(&logged)?;
+
+            let colliding = Task::new("colliding", "work", logged.start_time);
+            assert!(matches!(tracker.start(&colliding), Err(Error::Overlap(tasks)) if tasks == vec![logged.clone()]));
+            let restarted = tracker.restart(Some(&logged.unique_id), logged.start_time);
+            assert!(matches!(restarted, Err(Error::Overlap(tasks)) if tasks == vec![logged.clone()]));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_tasks_are_typed_errors() -> Result<(), Error> {
+        let tracker = tracker()?;
+
+        assert!(matches!(tracker.find("abc"), Err(Error::TaskNotFound(id)) if id == "abc"));
+        assert!(matches!(tracker.current(), Err(Error::NoRunningTask)));
+        assert!(matches!(tracker.cancel(), Err(Error::NoRunningTask)));
+        assert!(matches!(tracker.restart(None, now()), Err(Error::NoEndedTask)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_running_tasks_cannot_be_deleted() -> Result<(), Error> {
+        let tracker = tracker()?;
+        let task = Task::new("running", "work", now() - Duration::hours(1));
+        tracker.start(&task)?;
+
+        assert!(matches!(tracker.delete(&task.unique_id), Err(Error::TaskRunning(_))));
+        assert_eq!(tracker.cancel()?.unique_id, task.unique_id);
+        assert!(tracker.all_tasks()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_log_rejects_overlapping_and_backwards_times() -> Result<(), Error> {
+        let tracker = tracker()?;
+        let existing = ended_task("existing", now() - Duration::hours(3), now() - Duration::hours(2));
+        tracker.log(&existing)?;
+
+        let overlapping = ended_task("overlapping", now() - Duration::minutes(150), now() - Duration::hours(1));
+        match tracker.log(&overlapping) {
+            Err(Error::Overlap(tasks)) => assert_eq!(tasks, vec![existing]),
+            other => panic!("expected an overlap, got {:?}", other),
+        }
+
+        let mut backwards = ended_task("backwards", now() - Duration::hours(1), now());
+        backwards.end_time = Some(now() - Duration::hours(2));
+        assert!(matches!(tracker.log(&backwards), Err(Error::EndBeforeStart)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_restart_copies_the_last_ended_task() -> Result<(), Error> {
+        let tracker = tracker()?;
+        let mut task = ended_task("write tests", now() - Duration::hours(2), now() - Duration::hours(1));
+        task.tags = vec!["billable".to_string()];
+        tracker.log(&task)?;
+
+        let (restarted, ended) = tracker.restart(None, now())?;
+
+        assert_eq!(restarted.description, task.description);
+        assert_eq!(restarted.tags, task.tags);
+        assert_ne!(restarted.unique_id, task.unique_id);
+        assert!(ended.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_applies_the_filter() -> Result<(), Error> {
+        let tracker = tracker()?;
+        let mut tagged = ended_task("tagged", now() - Duration::hours(3), now() - Duration::hours(2));
+        tagged.tags = vec!["billable".to_string()];
+        tracker.log(&tagged)?;
+        tracker.log(&ended_task("untagged", now() - Duration::hours(2), now() - Duration::hours(1)))?;
+
+        let filter = TaskFilter {
+            project: Some("work".to_string()),
+            tags: vec!["billable".to_string()],
+            ..TaskFilter::default()
+        };
+        let today = tracker.today();
+        let report = tracker.report(today, today.succ(), &filter)?;
+
+        assert_eq!(report.total, Duration::hours(1));
+        assert!(matches!(tracker.report(today, today, &filter), Err(Error::EmptyRange)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_filters_combine_every_part() -> Result<(), Error> {
+        let tracker = tracker()?;
+        let mut oauth = ended_task("fix the OAuth bug", now() - Duration::hours(3), now() - Duration::hours(2));
+        oauth.tags = vec!["billable".to_string()];
+        tracker.log(&oauth)?;
+        tracker.log(&ended_task("review the oauth docs", now() - Duration::hours(2), now() - Duration::hours(1)))?;
+        let mut old = ended_task("fix the OAuth login", now() - Duration::days(3), now() - Duration::days(3) + Duration::hours(1));
+        old.project_name = "client".to_string();
+        tracker.log(&old)?;
+
+        let descriptions = |filter: &TaskFilter| -> Result<Vec<String>, Error> {
+            Ok(tracker.matching(filter)?.into_iter().map(|task| task.description).collect())
+        };
+        let oauth_filter = TaskFilter {
+            description: Some(Regex::new("OAuth").unwrap()),
+            ..TaskFilter::default()
+        };
+        assert_eq!(descriptions(&oauth_filter)?, vec!["fix the OAuth login", "fix the OAuth bug"]);
+
+        let today = TaskFilter {
+            from: Some(tracker.today()),
+            ..oauth_filter.clone()
+        };
+        assert_eq!(descriptions(&today)?, vec!["fix the OAuth bug"]);
+        assert_eq!(tracker.list(&today)?, vec![oauth.clone()]);
+
+        let by_id = TaskFilter {
+            id_prefix: Some(oauth.unique_id[..6].to_string()),
+            tags: vec!["billable".to_string()],
+            ..TaskFilter::default()
+        };
+        assert_eq!(tracker.matching(&by_id)?, vec![oauth]);
+
+        let backwards = TaskFilter {
+            from: Some(tracker.today()),
+            to: Some(tracker.today().pred()),
+            ..TaskFilter::default()
+        };
+        assert!(matches!(tracker.matching(&backwards), Err(Error::EmptyRange)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_applies_the_filter() -> Result<(), Error> {
+        let tracker = tracker()?;
+        let today = ended_task("fix the OAuth bug", now() - Duration::hours(3), now() - Duration::hours(2));
+        tracker.log(&today)?;
+        let earlier = ended_task("oauth review", now() - Duration::days(3), now() - Duration::days(3) + Duration::hours(1));
+        tracker.log(&earlier)?;
+
+        assert_eq!(tracker.search("oauth", &TaskFilter::default(), 10)?.len(), 2);
+        assert_eq!(tracker.search("oauth", &TaskFilter::default(), 1)?.len(), 1);
+
+        let since_yesterday = TaskFilter {
+            from: Some(tracker.today().pred()),
+            ..TaskFilter::default()
+        };
+        let hits = tracker.search("oauth", &since_yesterday, 10)?;
+        assert_eq!(hits.iter().map(|hit| hit.task.clone()).collect::<Vec<Task>>(), vec![today]);
+        assert!(matches!(tracker.search(" ", &TaskFilter::default(), 10), Err(Error::Invalid(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bulk_changes_are_made_together() -> Result<(), Error> {
+        let tracker = tracker()?;
+        let first = ended_task("first", now() - Duration::hours(3), now() - Duration::hours(2));
+        let second = ended_task("second", now() - Duration::hours(2), now() - Duration::hours(1));
+        tracker.log(&first)?;
+        tracker.log(&second)?;
+
+        let filter = TaskFilter {
+            project: Some("work".to_string()),
+            ..TaskFilter::default()
+        };
+        let edit = BulkEdit {
+            project: Some("client".to_string()),
+            add_tags: vec!["billable".to_string()],
+            ..BulkEdit::default()
+        };
+        let edited = tracker.bulk_edit(&filter, &edit)?;
+        assert_eq!(edited.len(), 2);
+        assert!(tracker.all_tasks()?.iter().all(|task| task.project_name == "client" && task.tags == ["billable"]));
+
+        // one running task stops the whole delete
+        tracker.start(&Task::new("running", "client", now() - Duration::minutes(30)))?;
+        let client = TaskFilter {
+            project: Some("client".to_string()),
+            ..TaskFilter::default()
+        };
+        assert!(matches!(tracker.bulk_delete(&client), Err(Error::TaskRunning(_))));
+        assert_eq!(tracker.all_tasks()?.len(), 3);
+
+        let billable = TaskFilter {
+            tags: vec!["billable".to_string()],
+            ..TaskFilter::default()
+        };
+        assert_eq!(tracker.bulk_delete(&billable)?.len(), 2);
+        assert_eq!(tracker.trash()?.len(), 2);
+
+        // each bulk change is a single operation
+        tracker.undo(1)?;
+        assert_eq!(tracker.all_tasks()?.len(), 3);
+        let last = &tracker.history(1)?[0];
+        assert_eq!((last.command.as_str(), last.undone, last.changes.len()), ("bulk-delete", true, 2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bulk_plans_are_applied_as_they_were_made() -> Result<(), Error> {
+        let tracker = tracker()?;
+        let first = ended_task("first", now() - Duration::hours(3), now() - Duration::hours(2));
+        tracker.log(&first)?;
+
+        let edit = BulkEdit {
+            add_tags: vec!["billable".to_string()],
+            ..BulkEdit::default()
+        };
+        let plan = tracker.plan_bulk_edit(&TaskFilter::default(), &edit)?;
+        assert_eq!(plan.tasks()[0].tags, ["billable"]);
+        // planning doesn't change anything, and a task added later isn't part of the plan
+        assert!(tracker.find(&first.unique_id)?.tags.is_empty());
+        tracker.log(&ended_task("second", now() - Duration::hours(2), now() - Duration::hours(1)))?;
+        assert_eq!(tracker.apply_bulk(&plan)?, plan.tasks());
+        assert_eq!(tracker.list(&TaskFilter::default())?.iter().filter(|task| task.tags.is_empty()).count(), 1);
+
+        // a task changed since the plan was made stops all of it
+        let plan = tracker.plan_bulk_delete(&TaskFilter::default())?;
+        let mut edited = tracker.find(&first.unique_id)?;
+        edited.description = "edited".to_string();
+        tracker.update(&edited)?;
+        assert!(matches!(tracker.apply_bulk(&plan), Err(Error::Invalid(_))));
+        assert!(tracker.trash()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_undo_reverts_a_start_and_redo_makes_it_again() -> Result<(), Error> {
+        let tracker = tracker()?;
+        let first = Task::new("first", "work", now() - Duration::hours(2));
+        tracker.start(&first)?;
+        let second = Task::new("second", "work", now() - Duration::hours(1));
+        tracker.start(&second)?;
+
+        let undone = tracker.undo(1)?;
+        assert_eq!(undone[0].command, "start");
+        assert_eq!(undone[0].changes.len(), 2);
+        assert_eq!(tracker.current()?, first);
+        assert_eq!(tracker.all_tasks()?, vec![first.clone()]);
+
+        tracker.redo(1)?;
+        assert_eq!(tracker.current()?, second);
+        assert_eq!(tracker.all_tasks()?.len(), 2);
+        assert!(matches!(tracker.redo(1), Err(Error::NothingToRedo)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_undo_restores_deleted_tasks_and_new_changes_drop_redo() -> Result<(), Error> {
+        let tracker = tracker()?;
+        let mut task = ended_task("logged", now() - Duration::hours(3), now() - Duration::hours(2));
+        task.tags = vec!["billable".to_string()];
+        tracker.log(&task)?;
+        tracker.delete(&task.unique_id)?;
+
+        tracker.undo(1)?;
+        assert_eq!(tracker.find(&task.unique_id)?, task);
+
+        tracker.undo(5)?;
+        assert!(tracker.all_tasks()?.is_empty());
+        assert!(matches!(tracker.undo(1), Err(Error::NothingToUndo)));
+
+        tracker.log(&ended_task("other", now() - Duration::hours(2), now() - Duration::hours(1)))?;
+        assert!(matches!(tracker.redo(1), Err(Error::NothingToRedo)));
+
+        let commands: Vec<String> = tracker.history(10)?.into_iter().map(|op| op.command).collect();
+        assert_eq!(commands, vec!["log"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_deleted_tasks_go_to_the_trash_until_it_is_emptied() -> Result<(), Error> {
+        let tracker = tracker()?;
+        let task = ended_task("logged", now() - Duration::hours(3), now() - Duration::hours(2));
+        tracker.log(&task)?;
+        tracker.delete(&task.unique_id)?;
+
+        assert!(tracker.all_tasks()?.is_empty());
+        assert!(matches!(tracker.find(&task.unique_id), Err(Error::TaskNotFound(_))));
+        assert_eq!(tracker.trash()?[0].deleted_at, Some(now()));
+
+        // the start time is free to be used again, which keeps the task in the trash
+        let replacement = ended_task("replacement", task.start_time, now() - Duration::hours(2));
+        tracker.log(&replacement)?;
+        assert!(matches!(tracker.restore_from_trash(&task.unique_id), Err(Error::Overlap(_))));
+        tracker.delete(&replacement.unique_id)?;
+
+        assert_eq!(tracker.restore_from_trash(&task.unique_id[..8])?, task);
+        assert_eq!(tracker.all_tasks()?, vec![task.clone()]);
+        assert_eq!(tracker.trash()?.len(), 1);
+
+        tracker.delete(&task.unique_id)?;
+        assert!(tracker.empty_trash(Some(Duration::days(30)))?.is_empty());
+        assert_eq!(tracker.empty_trash(None)?.len(), 2);
+        assert!(tracker.trash()?.is_empty());
+
+        tracker.undo(1)?;
+        assert_eq!(tracker.trash()?.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_notes_are_added_to_a_task_and_can_be_undone() -> Result<(), Error> {
+        let tracker = tracker()?;
+        assert!(matches!(tracker.add_note(None, "no task yet"), Err(Error::NoRunningTask)));
+
+        let logged = ended_task("logged", now() - Duration::hours(3), now() - Duration::hours(2));
+        tracker.log(&logged)?;
+        tracker.start(&Task::new("running", "work", now() - Duration::hours(1)))?;
+
+        assert_eq!(tracker.add_note(None, "first\nsecond line\n\n")?.notes[0].text, "first\nsecond line");
+        let noted = tracker.add_note(Some(&logged.unique_id[..8]), "on the logged task")?;
+        assert_eq!(noted.notes, vec![TaskNote { time: now(), text: "on the logged task".to_string() }]);
+        assert_eq!(tracker.find(&logged.unique_id)?, noted);
+        assert!(matches!(tracker.add_note(None, " \n "), Err(Error::Invalid(_))));
+
+        tracker.undo(1)?;
+        assert!(tracker.find(&logged.unique_id)?.notes.is_empty());
+        assert_eq!(tracker.current()?.notes.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_projects_are_checked_renamed_and_merged() -> Result<(), Error> {
+        let tracker = tracker()?;
+        let mut client = Project::new("client", now());
+        client.color = Some("teal".to_string());
+        assert!(matches!(tracker.add_project(&client), Err(Error::Invalid(_))));
+        client.color = Some("cyan".to_string());
+        tracker.add_project(&client)?;
+        assert!(matches!(tracker.add_project(&client), Err(Error::ProjectExists(_))));
+        tracker.add_project(&Project::new("work", now()))?;
+
+        let mut logged = ended_task("logged", now() - Duration::hours(4), now() - Duration::hours(3));
+        logged.project_name = "client".to_string();
+        let mut typo = ended_task("typo", now() - Duration::hours(2), now() - Duration::hours(1));
+        typo.project_name = "clinet".to_string();
+        tracker.log(&logged)?;
+        tracker.log(&typo)?;
+        tracker.delete(&logged.unique_id)?;
+
+        tracker.check_project("client")?;
+        assert!(matches!(tracker.check_project("clinet"), Err(Error::ProjectNotFound(_))));
+
+        assert!(matches!(tracker.rename_project("client", "work"), Err(Error::ProjectExists(_))));
+        assert!(matches!(tracker.rename_project("client", "clinet"), Err(Error::ProjectExists(_))));
+        let moved = tracker.rename_project("client", "client-x")?;
+        assert_eq!(moved.len(), 1);
+        assert_eq!(tracker.trash()?[0].project_name, "client-x");
+        let names: Vec<String> = tracker.projects()?.into_iter().map(|project| project.name).collect();
+        assert_eq!(names, vec!["client-x", "work"]);
+        assert_eq!(tracker.projects()?[0].color.as_deref(), Some("cyan"));
+
+        // a mistyped project that was never added can be merged into the right one
+        assert_eq!(tracker.merge_projects("clinet", "client-x")?.len(), 1);
+        assert_eq!(tracker.find(&typo.unique_id)?.project_name, "client-x");
+        assert!(matches!(tracker.merge_projects("clinet", "client-x"), Err(Error::ProjectNotFound(_))));
+        assert!(matches!(tracker.merge_projects("work", "missing"), Err(Error::ProjectNotFound(_))));
+
+        tracker.archive_project("work", true)?;
+        assert!(matches!(tracker.check_project("work"), Err(Error::ProjectArchived(_))));
+        assert!(matches!(tracker.archive_project("work", true), Err(Error::Invalid(_))));
+
+        // undoing puts the projects back along with their tasks
+        tracker.undo(3)?;
+        let names: Vec<String> = tracker.projects()?.into_iter().map(|project| project.name).collect();
+        assert_eq!(names, vec!["client", "work"]);
+        assert_eq!(tracker.find(&typo.unique_id)?.project_name, "clinet");
+        assert_eq!(tracker.trash()?[0].project_name, "client");
+        tracker.check_project("work")?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_checks_projects() -> Result<(), Error> {
+        let tracker = tracker()?;
+        tracker.add_project(&Project::new("work", now()))?;
+        tracker.add_project(&Project::new("old", now()))?;
+        tracker.archive_project("old", true)?;
+        let csv = "description,project,start_time,end_time\n\
+                   known,work,2021-03-17 10:00:00,2021-03-17 11:00:00\n\
+                   archived,old,2021-03-17 11:00:00,2021-03-17 12:00:00\n\
+                   unknown,typo,2021-03-17 12:00:00,2021-03-17 13:00:00\n\
+                   no project,,2021-03-17 13:00:00,2021-03-17 14:00:00\n";
+
+        // the default project is exempt, as it doesn't need to have been added
+        let summary = tracker.import_csv(csv.as_bytes(), "inbox", ProjectCheck::Error)?;
+        assert_eq!(summary.imported.len(), 2);
+        assert_eq!(summary.errors.iter().map(|e| e.line).collect::<Vec<u64>>(), vec![3, 4]);
+        assert_eq!(summary.projects, vec!["inbox".to_string(), "work".to_string()]);
+
+        tracker.undo(1)?;
+        let summary = tracker.import_csv(csv.as_bytes(), "inbox", ProjectCheck::Warn)?;
+        assert_eq!(summary.imported.len(), 4);
+        assert_eq!(summary.projects, vec!["inbox", "old", "typo", "work"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_history_is_limited_and_pruned() -> Result<(), Error> {
+        let mut tracker = tracker()?;
+        tracker.set_history_limit(Some(2));
+        for hour in 1..5 {
+            tracker.log(&ended_task("logged", now() - Duration::hours(hour), now() - Duration::hours(hour) + Duration::minutes(30)))?;
+        }
+        assert_eq!(tracker.history(10)?.len(), 2);
+
+        assert_eq!(tracker.prune_history(1)?, 1);
+        tracker.undo(1)?;
+        assert!(matches!(tracker.undo(1), Err(Error::NothingToUndo)));
+        assert_eq!(tracker.all_tasks()?.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_commands_are_not_recorded() -> Result<(), Error> {
+        let tracker = tracker()?;
+        let task = ended_task("logged", now() - Duration::hours(3), now() - Duration::hours(2));
+        tracker.log(&task)?;
+        assert!(tracker.log(&ended_task("overlapping", now() - Duration::hours(3), now())).is_err());
+
+        assert_eq!(tracker.history(10)?.len(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,362 @@
+extern crate csv;
+
+use crate::data::{Task, TaskBreak, TaskStatus};
+use crate::store::TaskStore;
+use crate::tags::normalize_tags;
+use anyhow::{anyhow, Error};
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use uuid::Uuid;
+
+// columns written by `export --csv`.  `import --csv` reads the same columns by name, in any order
+const CSV_HEADERS: [&str; 9] = [
+    "id",
+    "description",
+    "project",
+    "status",
+    "start_time",
+    "end_time",
+    "duration_seconds",
+    "tags",
+    "breaks",
+];
+
+#[derive(Debug, PartialEq)]
+pub struct RowError {
+    pub line: u64,
+    pub message: String,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportSummary {
+    pub imported: Vec<String>,
+    pub duplicates: Vec<String>,
+    pub errors: Vec<RowError>,
+    // the projects the imported tasks are in, ordered by name
+    pub projects: Vec<String>,
+}
+
+// accepts ISO-8601 timestamps with an offset, or `YYYY-MM-DD HH:MM:SS` which is taken to be utc
+fn parse_csv_timestamp(timestamp: &str) -> Result<DateTime<Utc>, Error> {
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(timestamp) {
+        return Ok(date_time.with_timezone(&Utc));
+    }
+
+    NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S")
+        .map(|naive| DateTime::<Utc>::from_utc(naive, Utc))
+        .map_err(|_| {
+            anyhow!(
+                "'{}' is an illegal timestamp, timestamps should be ISO-8601 or of the format YYYY-MM-DD HH:MM:SS",
+                timestamp
+            )
+        })
+}
+
+// breaks are written as space separated `start/end` pairs
+fn breaks_to_field(breaks: &[TaskBreak]) -> String {
+    breaks
+        .iter()
+        .map(|task_break| {
+            let end_time = match task_break.end_time {
+                Some(end_time) => end_time.to_rfc3339_opts(SecondsFormat::Secs, true),
+                None => String::new(),
+            };
+            format!("{}/{}", task_break.start_time.to_rfc3339_opts(SecondsFormat::Secs, true), end_time)
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// imported tasks have ended, so every break must have ended within the task
+fn breaks_from_field(breaks: &str, start_time: DateTime<Utc>, end_time: DateTime<Utc>) -> Result<Vec<TaskBreak>, Error> {
+    breaks
+        .split_whitespace()
+        .map(|task_break| {
+            let (break_start, break_end) = task_break
+                .split_once('/')
+                .ok_or(anyhow!("'{}' is an illegal break, breaks should be of the format START/END", task_break))?;
+            let break_start = parse_csv_timestamp(break_start)?;
+            let break_end = parse_csv_timestamp(break_end)?;
+
+            if break_start < start_time || break_end > end_time || break_end < break_start {
+                return Err(anyhow!("the break '{}' isn't within the task", task_break));
+            }
+
+            Ok(TaskBreak {
+                start_time: break_start,
+                end_time: Some(break_end),
+            })
+        })
+        .collect()
+}
+
+pub fn export_csv(tasks: &[Task], now: DateTime<Utc>, writer: impl std::io::Write) -> Result<(), Error> {
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    csv_writer.write_record(CSV_HEADERS)?;
+
+    for task in tasks {
+        let status = match task.status {
+            TaskStatus::Running => "running",
+            TaskStatus::Ended => "ended",
+        };
+        let end_time = match task.end_time {
+            Some(end_time) => end_time.to_rfc3339_opts(SecondsFormat::Secs, true),
+            None => String::new(),
+        };
+
+        csv_writer.write_record(&[
+            task.unique_id.clone(),
+            task.description.clone(),
+            task.project_name.clone(),
+            status.to_string(),
+            task.start_time.to_rfc3339_opts(SecondsFormat::Secs, true),
+            end_time,
+            task.duration(now).num_seconds().to_string(),
+            task.tags.join(" "),
+            breaks_to_field(&task.breaks),
+        ])?;
+    }
+
+    csv_writer.flush()?;
+    Ok(())
+}
+
+// maps a csv record onto a task.  rows without an id are given a new one, and rows without a
+// project are put in the default project
+fn task_from_record(record: &csv::StringRecord, headers: &csv::StringRecord, default_project: &str) -> Result<Task, Error> {
+    let field = |name: &str| -> Option<&str> {
+        headers
+            .iter()
+            .position(|h| h == name)
+            .and_then(|idx| record.get(idx))
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+    };
+
+    let description = field("description").ok_or(anyhow!("the description is empty"))?;
+    let start_time = parse_csv_timestamp(field("start_time").ok_or(anyhow!("the start_time is empty"))?)?;
+    let end_time = parse_csv_timestamp(field("end_time").ok_or(anyhow!("the end_time is empty;  running tasks can't be imported"))?)?;
+
+    if let Some(status) = field("status") {
+        if status != "ended" {
+            return Err(anyhow!("the status is '{}';  only ended tasks can be imported", status));
+        }
+    }
+
+    if end_time < start_time {
+        return Err(anyhow!("the end_time is before the start_time"));
+    }
+
+    let mut task = Task::new(description, field("project").unwrap_or(default_project), start_time);
+    task.end_task(end_time);
+    if let Some(tags) = field("tags") {
+        task.tags = normalize_tags(&tags.split_whitespace().map(String::from).collect::<Vec<String>>())?;
+    }
+    if let Some(breaks) = field("breaks") {
+        task.breaks = breaks_from_field(breaks, start_time, end_time)?;
+    }
+    task.unique_id = match field("id") {
+        Some(id) => id.to_string(),
+        None => Uuid::new_v4().to_string(),
+    };
+
+    Ok(task)
+}
+
+fn import_task(store: &dyn TaskStore, task: &Task, now: DateTime<Utc>) -> Result<(), Error> {
+    let overlapping_tasks = store.overlapping(task.start_time, task.end_time.unwrap_or(task.start_time), now)?;
+    if let Some(overlapping) = overlapping_tasks.first() {
+        return Err(anyhow!(
+            "the task overlaps the existing task '{}' ({})",
+            overlapping.description,
+            overlapping.unique_id
+        ));
+    }
+
+    store.save(task)
+}
+
+// imports every valid row, skipping tasks whose id already exists.  invalid rows, and rows whose
+// project `check_project` refuses, are reported back rather than aborting the import
+pub fn import_csv(
+    store: &dyn TaskStore,
+    reader: impl std::io::Read,
+    default_project: &str,
+    check_project: &dyn Fn(&str) -> Result<(), Error>,
+    now: DateTime<Utc>,
+) -> Result<ImportSummary, Error> {
+    let mut csv_reader = csv::Reader::from_reader(reader);
+    let headers = csv_reader.headers()?.clone();
+
+    for required in &["description", "start_time", "end_time"] {
+        if !headers.iter().any(|h| h == *required) {
+            return Err(anyhow!("the csv is missing the required '{}' column", required));
+        }
+    }
+
+    let mut summary = ImportSummary::default();
+    store.transaction(&mut || {
+        for result in csv_reader.records() {
+            let record = match result {
+                Ok(record) => record,
+                Err(err) => {
+                    summary.errors.push(RowError {
+                        line: err.position().map(|p| p.line()).unwrap_or(0),
+                        message: err.to_string(),
+                    });
+                    continue;
+                }
+            };
+            let line = record.position().map(|p| p.line()).unwrap_or(0);
+
+            let imported = task_from_record(&record, &headers, default_project).and_then(|task| {
+                if store.find_by_id(&task.unique_id)?.is_some() {
+                    summary.duplicates.push(task.unique_id);
+                    return Ok(());
+                }
+
+                check_project(&task.project_name)?;
+                import_task(store, &task, now)?;
+                if !summary.projects.contains(&task.project_name) {
+                    summary.projects.push(task.project_name);
+                }
+                summary.imported.push(task.unique_id);
+                Ok(())
+            });
+
+            if let Err(err) = imported {
+                summary.errors.push(RowError { line, message: err.to_string() });
+            }
+        }
+
+        Ok(())
+    })?;
+
+    summary.projects.sort();
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+    use chrono::TimeZone;
+
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2021, 3, 20).and_hms(0, 0, 0)
+    }
+
+    fn any_project(_: &str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_then_import_round_trips() -> Result<(), Error> {
+        let mut task = Task::new("write tests", "taskmao", Utc.ymd(2021, 3, 18).and_hms(23, 36, 24));
+        task.end_task(Utc.ymd(2021, 3, 19).and_hms(1, 38, 59));
+        task.tags = vec!["billable".to_string(), "testing".to_string()];
+        task.breaks = vec![TaskBreak {
+            start_time: Utc.ymd(2021, 3, 19).and_hms(0, 0, 0),
+            end_time: Some(Utc.ymd(2021, 3, 19).and_hms(0, 30, 0)),
+        }];
+
+        let mut exported = Vec::new();
+        export_csv(&[task.clone()], now(), &mut exported)?;
+
+        let store = MemoryStore::new();
+        let summary = import_csv(&store, exported.as_slice(), "default", &any_project, now())?;
+
+        assert_eq!(summary.imported, vec![task.unique_id.clone()]);
+        assert_eq!(store.all()?, vec![task]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_skips_duplicate_ids() -> Result<(), Error> {
+        let store = MemoryStore::new();
+        let csv = "id,description,start_time,end_time\nabc,write tests,2021-03-18T10:00:00Z,2021-03-18T11:00:00Z\n";
+
+        import_csv(&store, csv.as_bytes(), "default", &any_project, now())?;
+        let summary = import_csv(&store, csv.as_bytes(), "default", &any_project, now())?;
+
+        assert!(summary.imported.is_empty());
+        assert_eq!(summary.duplicates, vec!["abc".to_string()]);
+        assert_eq!(store.all()?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_reports_row_errors_without_aborting() -> Result<(), Error> {
+        let store = MemoryStore::new();
+        let csv = "description,project,start_time,end_time\n\
+                   good,work,2021-03-18 10:00:00,2021-03-18 11:00:00\n\
+                   bad time,work,yesterday,2021-03-18 11:00:00\n\
+                   backwards,work,2021-03-18 13:00:00,2021-03-18 12:00:00\n\
+                   overlapping,work,2021-03-18T10:30:00+00:00,2021-03-18T12:00:00+00:00\n\
+                   also good,work,2021-03-18 12:00:00,2021-03-18 12:30:00\n";
+
+        let summary = import_csv(&store, csv.as_bytes(), "default", &any_project, now())?;
+
+        assert_eq!(summary.imported.len(), 2);
+        assert_eq!(
+            summary.errors.iter().map(|e| e.line).collect::<Vec<u64>>(),
+            vec![3, 4, 5]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_does_not_overwrite_tasks_sharing_a_start_time() -> Result<(), Error> {
+        let store = MemoryStore::new();
+        let mut existing = Task::new("existing", "work", Utc.ymd(2021, 3, 18).and_hms(10, 0, 0));
+        existing.end_task(Utc.ymd(2021, 3, 18).and_hms(10, 0, 0));
+        store.save(&existing)?;
+
+        let csv = "description,start_time,end_time\nimported,2021-03-18 10:00:00,2021-03-18 10:00:00\n";
+        let summary = import_csv(&store, csv.as_bytes(), "default", &any_project, now())?;
+
+        assert_eq!(summary.errors.len(), 1);
+        assert_eq!(store.all()?, vec![existing]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_requires_columns() -> Result<(), Error> {
+        let store = MemoryStore::new();
+        assert!(import_csv(&store, "description,start_time\nfoo,2021-03-18 10:00:00\n".as_bytes(), "default", &any_project, now()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_puts_rows_without_a_project_in_the_default_project() -> Result<(), Error> {
+        let store = MemoryStore::new();
+        let csv = "description,project,start_time,end_time\n\
+                   no project,,2021-03-18 10:00:00,2021-03-18 11:00:00\n\
+                   own project,work,2021-03-18 11:00:00,2021-03-18 12:00:00\n";
+
+        let summary = import_csv(&store, csv.as_bytes(), "inbox", &any_project, now())?;
+
+        assert_eq!(summary.projects, vec!["inbox".to_string(), "work".to_string()]);
+        let projects: Vec<String> = store.all()?.into_iter().map(|task| task.project_name).collect();
+        assert_eq!(projects, vec!["inbox".to_string(), "work".to_string()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_reports_rows_whose_project_is_refused() -> Result<(), Error> {
+        let store = MemoryStore::new();
+        let csv = "description,project,start_time,end_time\n\
+                   known,work,2021-03-18 10:00:00,2021-03-18 11:00:00\n\
+                   unknown,typo,2021-03-18 11:00:00,2021-03-18 12:00:00\n";
+        let check_project = |name: &str| match name {
+            "work" => Ok(()),
+            _ => Err(anyhow!("the project '{}' hasn't been added", name)),
+        };
+
+        let summary = import_csv(&store, csv.as_bytes(), "default", &check_project, now())?;
+
+        assert_eq!(summary.imported.len(), 1);
+        assert_eq!(summary.errors, vec![RowError { line: 3, message: "the project 'typo' hasn't been added".to_string() }]);
+        assert_eq!(summary.projects, vec!["work".to_string()]);
+        Ok(())
+    }
+}